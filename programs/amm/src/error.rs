@@ -38,4 +38,84 @@ pub enum AMMError {
     NotEnoughTokenSupply,
     #[msg("Insufficient funds in pool")]
     InsufficientPoolFund,
+    #[msg("Swap output is below the minimum amount out")]
+    ExceededSlippage,
+    #[msg("Swap input is above the maximum amount in")]
+    ExceededMaxAmountIn,
+    #[msg("Pool invariant decreased after swap")]
+    InvariantViolated,
+    #[msg("Signer is not authorized for this action")]
+    Unauthorized,
+    #[msg("Deposit is outside the provided slippage bounds")]
+    DepositSlippageExceeded,
+    #[msg("Withdrawn amount is below the minimum amount out")]
+    WithdrawSlippageExceeded,
+    #[msg("Fee numerator must be below a non-zero denominator")]
+    InvalidFee,
+    #[msg("Fee tier does not exist")]
+    InvalidFeeTier,
+    #[msg("No more fee tiers can be added")]
+    FeeTierLimitReached,
+    #[msg("Pool is not in the legacy layout")]
+    PoolAlreadyMigrated,
+    #[msg("Mint uses a Token-2022 extension the pool does not support")]
+    UnsupportedMintExtension,
+    #[msg("Observation capacity can only grow")]
+    InvalidObservationCapacity,
+    #[msg("No observation covers the requested timestamp")]
+    ObservationNotAvailable,
+    #[msg("TWAP window has to be at least one second")]
+    InvalidTwapWindow,
+    #[msg("Route accounts do not form a valid path through the pools")]
+    InvalidRoute,
+    #[msg("Amplification coefficient or ramp schedule is out of bounds")]
+    InvalidAmp,
+    #[msg("Instruction does not apply to the pool's curve type")]
+    InvalidCurveType,
+    #[msg("StableSwap invariant did not converge")]
+    CurveDidNotConverge,
+    #[msg("Weighted pool trades are limited to 30% of the reserve")]
+    TradeTooLarge,
+    #[msg("Pool weights must both be between 1% and 99%")]
+    InvalidWeights,
+    #[msg("Ticks must be aligned to the tick spacing and within the tick bounds")]
+    InvalidTickRange,
+    #[msg("Square-root price is outside the tick bounds")]
+    InvalidSqrtPrice,
+    #[msg("Tick arrays do not cover the ticks the instruction needs")]
+    InvalidTickArray,
+    #[msg("Position still holds liquidity or uncollected fees")]
+    PositionNotEmpty,
+    #[msg("Price limit has to be past the current price in the swap direction and within the tick range")]
+    InvalidSqrtPriceLimit,
+    #[msg("Pool has an open flash loan")]
+    FlashLoanActive,
+    #[msg("Flash loan has no matching flash_loan_end later in the transaction")]
+    FlashLoanNotRepaid,
+    #[msg("Flash loan must be opened at the top level and closed by its borrower")]
+    InvalidFlashLoan,
+    #[msg("Pool status has unknown bits set")]
+    InvalidPoolStatus,
+    #[msg("Swaps are paused for this pool")]
+    SwapDisabled,
+    #[msg("Deposits are paused for this pool")]
+    DepositDisabled,
+    #[msg("Withdrawals are paused for this pool")]
+    WithdrawDisabled,
+    #[msg("Pool has not reached its open time yet")]
+    PoolNotOpen,
+    #[msg("Open time can only move earlier before the pool opens")]
+    InvalidOpenTime,
+    #[msg("Launch fee needs a duration and a start fee below 100%")]
+    InvalidLaunchFee,
+    #[msg("Dynamic fee periods, reduction factor or variable fee are out of bounds")]
+    InvalidDynamicFee,
+    #[msg("Pool still has LP tokens outstanding")]
+    PoolNotEmpty,
+    #[msg("Protocol fees have to be collected before the pool is closed")]
+    ProtocolFeesNotCollected,
+    #[msg("Locked liquidity has to be above zero")]
+    InvalidLockedLiquidity,
+    #[msg("Pool has locked liquidity, so its lock account has to be passed")]
+    LockedLpAccountRequired,
 }
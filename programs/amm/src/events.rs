@@ -1,5 +1,7 @@
 use anchor_lang::prelude::*;
 
+use crate::{AmpRamp, DynamicFeeParams, Fees};
+
 #[event]
 pub struct InitializeLiquidityPoolEvent {
     pub liquidity_provider: Pubkey,
@@ -16,6 +18,30 @@ pub struct SwapEvent {
     pub user_source: Pubkey,
     pub user_destination: Pubkey,
     pub swap_amount_out: u64,
+    /// Swap fee charged, including any launch fee
+    pub swap_fee: u64,
+}
+
+#[event]
+pub struct SwapBaseOutEvent {
+    pub amount_in: u64,
+    pub direction: u8,
+    pub user_source: Pubkey,
+    pub user_destination: Pubkey,
+    pub amount_out: u64,
+    /// Swap fee charged, including any launch fee
+    pub swap_fee: u64,
+}
+
+/// Emitted once per routed swap, after the `SwapEvent` of each hop
+#[event]
+pub struct SwapRouteEvent {
+    pub user: Pubkey,
+    pub source_mint: Pubkey,
+    pub destination_mint: Pubkey,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub hops: u8,
 }
 
 #[event]
@@ -27,6 +53,80 @@ pub struct DepositEvent {
     pub pc_token_amount: u64,
 }
 
+#[event]
+pub struct MigratePoolEvent {
+    pub amm_pda: Pubkey,
+    pub base_token_vault: Pubkey,
+    pub pc_token_vault: Pubkey,
+    pub base_token_amount: u64,
+    pub pc_token_amount: u64,
+}
+
+#[event]
+pub struct FeeTierEvent {
+    pub fee_tier_index: u16,
+    pub swap_fee_numerator: u64,
+    pub swap_fee_denominator: u64,
+    pub protocol_fee_numerator: u64,
+    pub protocol_fee_denominator: u64,
+}
+
+impl FeeTierEvent {
+    pub fn new(fee_tier_index: u16, fees: &Fees) -> Self {
+        Self {
+            fee_tier_index,
+            swap_fee_numerator: fees.swap_fee_numerator,
+            swap_fee_denominator: fees.swap_fee_denominator,
+            protocol_fee_numerator: fees.protocol_fee_numerator,
+            protocol_fee_denominator: fees.protocol_fee_denominator,
+        }
+    }
+}
+
+#[event]
+pub struct SetPoolFeesEvent {
+    pub amm_pda: Pubkey,
+    pub fee_tier_index: u16,
+    pub swap_fee_numerator: u64,
+    pub swap_fee_denominator: u64,
+    pub protocol_fee_numerator: u64,
+    pub protocol_fee_denominator: u64,
+}
+
+#[event]
+pub struct RampAmpEvent {
+    pub amm_pda: Pubkey,
+    pub initial_amp: u64,
+    pub target_amp: u64,
+    pub ramp_start_timestamp: i64,
+    pub ramp_stop_timestamp: i64,
+}
+
+impl RampAmpEvent {
+    pub fn new(amm_pda: Pubkey, amp: &AmpRamp) -> Self {
+        Self {
+            amm_pda,
+            initial_amp: amp.initial_amp,
+            target_amp: amp.target_amp,
+            ramp_start_timestamp: amp.ramp_start_timestamp,
+            ramp_stop_timestamp: amp.ramp_stop_timestamp,
+        }
+    }
+}
+
+#[event]
+pub struct SetTreasuryEvent {
+    pub treasury: Pubkey,
+}
+
+#[event]
+pub struct CollectProtocolFeesEvent {
+    pub amm_pda: Pubkey,
+    pub treasury: Pubkey,
+    pub base_token_amount: u64,
+    pub pc_token_amount: u64,
+}
+
 #[event]
 pub struct WithdrawEvent {
     pub user: Pubkey,
@@ -34,3 +134,95 @@ pub struct WithdrawEvent {
     pub base_token_amount: u64,
     pub pc_token_amount: u64,
 }
+
+/// Emitted by `withdraw_single_side`. `amount_out` is the LP share of the
+/// paid out token plus `swap_amount_out`, after any transfer fee.
+#[event]
+pub struct WithdrawSingleSideEvent {
+    pub user: Pubkey,
+    pub lp_amount: u64,
+    pub destination_mint: Pubkey,
+    pub share_amount: u64,
+    pub swap_amount_in: u64,
+    pub swap_fee: u64,
+    pub swap_amount_out: u64,
+    pub amount_out: u64,
+}
+
+#[event]
+pub struct InitializeConcentratedPoolEvent {
+    pub concentrated_pool: Pubkey,
+    pub base_token_mint: Pubkey,
+    pub pc_token_mint: Pubkey,
+    pub tick_spacing: u16,
+    pub sqrt_price_q64: u128,
+}
+
+/// Emitted when a position's liquidity changes. Amounts are what moved
+/// between the vaults and the owner, fees included.
+#[event]
+pub struct ModifyPositionEvent {
+    pub position: Pubkey,
+    pub owner: Pubkey,
+    pub liquidity_delta: i128,
+    pub base_token_amount: u64,
+    pub pc_token_amount: u64,
+    pub base_token_fees: u64,
+    pub pc_token_fees: u64,
+}
+
+#[event]
+pub struct SetFlashLoanFeeEvent {
+    pub flash_loan_fee_numerator: u64,
+    pub flash_loan_fee_denominator: u64,
+}
+
+/// Emitted by `flash_loan_end` once the loan is repaid
+#[event]
+pub struct FlashLoanEvent {
+    pub amm_pda: Pubkey,
+    pub borrower: Pubkey,
+    pub base_token_amount: u64,
+    pub pc_token_amount: u64,
+    pub base_token_fee: u64,
+    pub pc_token_fee: u64,
+}
+
+#[event]
+pub struct SetPoolStatusEvent {
+    pub amm_pda: Pubkey,
+    pub status: u8,
+}
+
+#[event]
+pub struct SetGlobalPauseEvent {
+    pub status: u8,
+}
+
+#[event]
+pub struct UpdateOpenTimeEvent {
+    pub amm_pda: Pubkey,
+    pub open_time: i64,
+}
+
+#[event]
+pub struct SetDynamicFeeEvent {
+    pub amm_pda: Pubkey,
+    pub enabled: bool,
+    pub params: DynamicFeeParams,
+}
+
+#[event]
+pub struct SetLockedLiquidityEvent {
+    pub locked_liquidity: u64,
+}
+
+#[event]
+pub struct ClosePoolEvent {
+    pub amm_pda: Pubkey,
+    /// Creator or config admin that closed the pool
+    pub authority: Pubkey,
+    /// Dust swept from the vaults to the recipient
+    pub base_token_amount: u64,
+    pub pc_token_amount: u64,
+}
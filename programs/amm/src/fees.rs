@@ -0,0 +1,274 @@
+use anchor_lang::prelude::*;
+
+use crate::{AMMError, Converter, Fees, U256};
+
+/// Swap fee denominator once a variable fee is added on top
+pub const FEE_RATE_DENOMINATOR: u64 = 1_000_000_000;
+const BASIS_POINT_MAX: u64 = 10_000;
+
+/// Shape of a pool's volatility-based variable fee, after Trader Joe's
+/// Liquidity Book. Volatility is measured in basis points of price movement.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct DynamicFeeParams {
+    /// Swaps this many seconds apart or less keep the same reference price,
+    /// so a burst of trades adds up its price movement
+    pub filter_period: u16,
+    /// Swaps this many seconds apart or more start from zero volatility
+    pub decay_period: u16,
+    /// Share of the volatility carried into the next reference, in basis
+    /// points, for swaps between the two periods
+    pub reduction_factor: u16,
+    /// Variable fee, over `FEE_RATE_DENOMINATOR`, per squared basis point of
+    /// volatility
+    pub variable_fee_control: u32,
+    /// Cap on the volatility, which bounds the variable fee
+    pub max_volatility_accumulator: u32,
+}
+
+impl DynamicFeeParams {
+    pub const SIZE: usize = 2 + 2 + 2 + 4 + 4;
+
+    pub fn validate(&self) -> Result<()> {
+        require!(
+            self.filter_period < self.decay_period
+                && u64::from(self.reduction_factor) <= BASIS_POINT_MAX
+                && self.variable_fee_control > 0
+                && self.max_volatility_accumulator > 0,
+            AMMError::InvalidDynamicFee
+        );
+        // The variable fee alone has to stay below 100%
+        require!(
+            self.variable_fee(self.max_volatility_accumulator) < u128::from(FEE_RATE_DENOMINATOR),
+            AMMError::InvalidDynamicFee
+        );
+        Ok(())
+    }
+
+    /// Variable fee over `FEE_RATE_DENOMINATOR` at `volatility_accumulator`
+    fn variable_fee(&self, volatility_accumulator: u32) -> u128 {
+        let volatility_accumulator = u128::from(volatility_accumulator);
+        u128::from(self.variable_fee_control) * volatility_accumulator * volatility_accumulator
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct Volatility {
+    /// Volatility the current swap is charged for
+    pub volatility_accumulator: u32,
+    /// Volatility carried over from before the reference price was taken
+    pub volatility_reference: u32,
+    /// Q64.64 pc per base price that movement is measured from
+    pub reference_price_q64: u128,
+    pub last_update_timestamp: i64,
+}
+
+impl Volatility {
+    pub const SIZE: usize = 4 + 4 + 16 + 8;
+}
+
+/// Optional variable fee added to a pool's swap fee. While enabled, every
+/// swap measures how far the price has moved from a reference price taken
+/// at the start of the current burst of trades.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct DynamicFee {
+    pub enabled: bool,
+    pub params: DynamicFeeParams,
+    pub volatility: Volatility,
+}
+
+impl DynamicFee {
+    pub const SIZE: usize = 1 + DynamicFeeParams::SIZE + Volatility::SIZE;
+
+    /// Dynamic fee following `params`, starting from zero volatility, or a
+    /// disabled one for `None`
+    pub fn new(params: Option<DynamicFeeParams>) -> Result<Self> {
+        match params {
+            Some(params) => {
+                params.validate()?;
+                Ok(Self {
+                    enabled: true,
+                    params,
+                    volatility: Volatility::default(),
+                })
+            }
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Volatility for a swap at `timestamp` with the pool at `price_q64`
+    pub fn volatility_at(&self, price_q64: u128, timestamp: i64) -> Volatility {
+        let params = &self.params;
+        let mut volatility = self.volatility;
+        let elapsed = timestamp.saturating_sub(volatility.last_update_timestamp);
+        if elapsed >= i64::from(params.filter_period) {
+            volatility.reference_price_q64 = price_q64;
+            volatility.volatility_reference = if elapsed < i64::from(params.decay_period) {
+                (u64::from(volatility.volatility_accumulator) * u64::from(params.reduction_factor)
+                    / BASIS_POINT_MAX) as u32
+            } else {
+                0
+            };
+        }
+        let price_movement = Self::price_movement_bps(volatility.reference_price_q64, price_q64);
+        volatility.volatility_accumulator = volatility
+            .volatility_reference
+            .saturating_add(price_movement)
+            .min(params.max_volatility_accumulator);
+        volatility.last_update_timestamp = timestamp;
+        volatility
+    }
+
+    /// `fees` with the variable fee for `volatility` added to the swap fee.
+    /// A disabled dynamic fee leaves `fees` unchanged.
+    pub fn fees_with(&self, fees: &Fees, volatility: &Volatility) -> Result<Fees> {
+        if !self.enabled {
+            return Ok(fees.clone());
+        }
+        let base_fee = u128::from(fees.swap_fee_numerator)
+            .checked_mul(FEE_RATE_DENOMINATOR.into())
+            .ok_or(AMMError::MathOverflow)?
+            .checked_div(fees.swap_fee_denominator.into())
+            .ok_or(AMMError::MathOverflow)?;
+        let swap_fee = (base_fee + self.params.variable_fee(volatility.volatility_accumulator))
+            .min(u128::from(FEE_RATE_DENOMINATOR - 1));
+        Ok(Fees {
+            swap_fee_numerator: Converter::to_u64(swap_fee)?,
+            swap_fee_denominator: FEE_RATE_DENOMINATOR,
+            ..fees.clone()
+        })
+    }
+
+    /// Keeps the volatility of a swap at `timestamp` for the next one
+    pub fn update(&mut self, price_q64: u128, timestamp: i64) {
+        if self.enabled {
+            self.volatility = self.volatility_at(price_q64, timestamp);
+        }
+    }
+
+    /// Relative move from `reference_price_q64` to `price_q64`, in basis
+    /// points. Without a reference yet there is no movement to measure.
+    fn price_movement_bps(reference_price_q64: u128, price_q64: u128) -> u32 {
+        if reference_price_q64 == 0 {
+            return 0;
+        }
+        let movement = U256::from(reference_price_q64.abs_diff(price_q64))
+            * U256::from(BASIS_POINT_MAX)
+            / U256::from(reference_price_q64);
+        if movement > U256::from(u32::MAX) {
+            u32::MAX
+        } else {
+            movement.as_u32()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params() -> DynamicFeeParams {
+        DynamicFeeParams {
+            filter_period: 10,
+            decay_period: 60,
+            reduction_factor: 5_000,
+            variable_fee_control: 100,
+            max_volatility_accumulator: 1_000,
+        }
+    }
+
+    fn dynamic_fee() -> DynamicFee {
+        DynamicFee::new(Some(params())).unwrap()
+    }
+
+    fn fees() -> Fees {
+        Fees {
+            swap_fee_numerator: 25,
+            swap_fee_denominator: 10000,
+            protocol_fee_numerator: 1,
+            protocol_fee_denominator: 5,
+        }
+    }
+
+    /// Q64.64 price `bps` basis points above `price(0)`
+    fn price(bps: u128) -> u128 {
+        (10_000 + bps) << 60
+    }
+
+    #[test]
+    fn movement_within_the_filter_period_adds_up() {
+        let mut dynamic_fee = dynamic_fee();
+        dynamic_fee.update(price(0), 1_000);
+        assert_eq!(dynamic_fee.volatility.volatility_accumulator, 0);
+        dynamic_fee.update(price(100), 1_005);
+        assert_eq!(dynamic_fee.volatility.volatility_accumulator, 100);
+        // Still measured from the same reference price
+        dynamic_fee.update(price(300), 1_009);
+        assert_eq!(dynamic_fee.volatility.volatility_accumulator, 300);
+        dynamic_fee.update(price(5_000), 1_009);
+        assert_eq!(dynamic_fee.volatility.volatility_accumulator, 1_000);
+    }
+
+    #[test]
+    fn volatility_decays_with_time() {
+        let mut dynamic_fee = dynamic_fee();
+        dynamic_fee.update(price(0), 1_000);
+        dynamic_fee.update(price(400), 1_005);
+        // Between the periods half the volatility carries over to the new
+        // reference price
+        let volatility = dynamic_fee.volatility_at(price(400), 1_030);
+        assert_eq!(volatility.volatility_reference, 200);
+        assert_eq!(volatility.volatility_accumulator, 200);
+        assert_eq!(volatility.reference_price_q64, price(400));
+        // After the decay period it is gone
+        let volatility = dynamic_fee.volatility_at(price(400), 1_065);
+        assert_eq!(volatility.volatility_reference, 0);
+        assert_eq!(volatility.volatility_accumulator, 0);
+    }
+
+    #[test]
+    fn variable_fee_grows_with_the_square_of_volatility() {
+        let dynamic_fee = dynamic_fee();
+        let volatility = |volatility_accumulator| Volatility {
+            volatility_accumulator,
+            ..Default::default()
+        };
+        let calm = dynamic_fee.fees_with(&fees(), &volatility(0)).unwrap();
+        assert_eq!(calm.swap_fee_numerator, 2_500_000);
+        assert_eq!(calm.swap_fee_denominator, FEE_RATE_DENOMINATOR);
+        assert_eq!(calm.protocol_fee_numerator, 1);
+        // 100 * 100^2 = 0.1% on top of the 0.25% base fee
+        let volatile = dynamic_fee.fees_with(&fees(), &volatility(100)).unwrap();
+        assert_eq!(volatile.swap_fee_numerator, 3_500_000);
+        let capped = dynamic_fee.fees_with(&fees(), &volatility(1_000)).unwrap();
+        assert_eq!(capped.swap_fee_numerator, 102_500_000);
+
+        let disabled = DynamicFee::new(None).unwrap();
+        let unchanged = disabled.fees_with(&fees(), &volatility(100)).unwrap();
+        assert_eq!(unchanged.swap_fee_numerator, 25);
+        assert_eq!(unchanged.swap_fee_denominator, 10000);
+    }
+
+    #[test]
+    fn params_keep_the_fee_below_one() {
+        assert!(params().validate().is_ok());
+        let err = DynamicFeeParams {
+            max_volatility_accumulator: 4_000,
+            ..params()
+        }
+        .validate()
+        .unwrap_err();
+        assert_eq!(err, AMMError::InvalidDynamicFee.into());
+        assert!(DynamicFeeParams {
+            decay_period: 10,
+            ..params()
+        }
+        .validate()
+        .is_err());
+        assert!(DynamicFeeParams {
+            reduction_factor: 10_001,
+            ..params()
+        }
+        .validate()
+        .is_err());
+    }
+}
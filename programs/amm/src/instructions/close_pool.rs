@@ -0,0 +1,193 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{burn, close_account, Burn, CloseAccount, Token};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{
+    AMMError, AmmConfig, ClosePoolEvent, InitalizeLiquidityAccount, ObservationState,
+    ProcessTokenInstructions,
+};
+
+/// Retires a pool once every LP has withdrawn. The LP mint stays behind,
+/// since SPL Token mints can't be closed.
+#[derive(Accounts)]
+#[instruction(amm_pda_index: u64)]
+pub struct ClosePool<'info> {
+    #[account(
+        constraint = authority.key() == amm_pda.liquidity_provider
+            || authority.key() == amm_config.admin @ AMMError::Unauthorized
+    )]
+    pub authority: Signer<'info>,
+    /// CHECK: Pool creator, who gets the rent back
+    #[account(mut, address = amm_pda.liquidity_provider @ AMMError::Unauthorized)]
+    pub liquidity_provider: UncheckedAccount<'info>,
+    #[account(
+        seeds = [b"amm_config"],
+        bump = amm_config.bump,
+    )]
+    pub amm_config: Account<'info, AmmConfig>,
+    #[account(
+        mut,
+        close = liquidity_provider,
+        seeds=[b"amm_pda", &amm_pda_index.to_le_bytes()],
+        bump = amm_pda.bump
+    )]
+    amm_pda: Account<'info, InitalizeLiquidityAccount>,
+    #[account(
+        mut,
+        close = liquidity_provider,
+        seeds = [b"observation", amm_pda.key().as_ref()],
+        bump = observation_state.load()?.bump,
+    )]
+    pub observation_state: AccountLoader<'info, ObservationState>,
+    #[account(
+        mut,
+        seeds=[b"base_token_vault", base_token_mint.key().as_ref(), amm_pda.key().as_ref()],
+        bump=amm_pda.base_token_vault_bump,
+        token::mint = base_token_mint,
+        token::authority = amm_pda,
+        token::token_program = base_token_program,
+    )]
+    pub base_token_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds=[b"pc_token_vault", pc_token_mint.key().as_ref(), amm_pda.key().as_ref()],
+        bump=amm_pda.pc_token_vault_bump,
+        token::mint = pc_token_mint,
+        token::authority = amm_pda,
+        token::token_program = pc_token_program,
+    )]
+    pub pc_token_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds=[b"lp_mint", base_token_mint.key().as_ref(), pc_token_mint.key().as_ref(), amm_pda.key().as_ref()],
+        bump=amm_pda.lp_token_mint_bump,
+    )]
+    pub lp_token_mint: Box<InterfaceAccount<'info, Mint>>,
+    /// Left out for pools without locked liquidity, such as those migrated
+    /// from the previous release
+    #[account(
+        mut,
+        seeds = [b"locked_lp", amm_pda.key().as_ref()],
+        bump,
+        token::mint = lp_token_mint,
+        token::authority = amm_pda,
+    )]
+    pub locked_lp_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    /// Receives whatever dust is left in the base token vault
+    #[account(mut, token::mint = base_token_mint)]
+    pub recipient_base_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    /// Receives whatever dust is left in the pc token vault
+    #[account(mut, token::mint = pc_token_mint)]
+    pub recipient_pc_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    /// Writable so withheld transfer fees can be harvested into it
+    #[account(mut, mint::token_program = base_token_program)]
+    pub base_token_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mut, mint::token_program = pc_token_program)]
+    pub pc_token_mint: Box<InterfaceAccount<'info, Mint>>,
+    /// Program of the LP mint, which is always created with SPL Token
+    pub token_program: Program<'info, Token>,
+    pub base_token_program: Interface<'info, TokenInterface>,
+    pub pc_token_program: Interface<'info, TokenInterface>,
+}
+
+/// Sweeps the vaults to the recipient accounts, closes them and the pool,
+/// burns the locked liquidity and returns the rent to the pool creator.
+/// Only the creator or the config admin can close a pool, and only once
+/// its LP supply is down to the locked minimum and the protocol fees have
+/// been collected.
+pub fn _close_pool(ctx: Context<ClosePool>, amm_pda_index: u64) -> Result<()> {
+    let accounts = &ctx.accounts;
+    let amm_pda = &accounts.amm_pda;
+    amm_pda.flash_loan.require_inactive()?;
+    require!(
+        accounts.lp_token_mint.supply == amm_pda.locked_lp_amount,
+        AMMError::PoolNotEmpty
+    );
+    require!(
+        amm_pda.protocol_fees_base_token == 0 && amm_pda.protocol_fees_pc_token == 0,
+        AMMError::ProtocolFeesNotCollected
+    );
+    require!(
+        amm_pda.locked_lp_amount == 0 || accounts.locked_lp_token_account.is_some(),
+        AMMError::LockedLpAccountRequired
+    );
+
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        b"amm_pda",
+        &amm_pda_index.to_le_bytes()[..],
+        &[amm_pda.bump],
+    ]];
+    let base_token_amount = accounts.base_token_vault.amount;
+    let pc_token_amount = accounts.pc_token_vault.amount;
+
+    // Sweep what is left in the vaults to the recipient
+    ProcessTokenInstructions::transfer(
+        &accounts.base_token_program,
+        &accounts.base_token_mint,
+        accounts.base_token_vault.to_account_info(),
+        accounts.recipient_base_token_account.to_account_info(),
+        amm_pda.to_account_info(),
+        signer_seeds,
+        base_token_amount,
+    )?;
+    ProcessTokenInstructions::transfer(
+        &accounts.pc_token_program,
+        &accounts.pc_token_mint,
+        accounts.pc_token_vault.to_account_info(),
+        accounts.recipient_pc_token_account.to_account_info(),
+        amm_pda.to_account_info(),
+        signer_seeds,
+        pc_token_amount,
+    )?;
+
+    // Close the emptied vaults and refund their rent to the pool creator
+    ProcessTokenInstructions::close(
+        &accounts.base_token_program,
+        &accounts.base_token_mint,
+        accounts.base_token_vault.to_account_info(),
+        accounts.liquidity_provider.to_account_info(),
+        amm_pda.to_account_info(),
+        signer_seeds,
+    )?;
+    ProcessTokenInstructions::close(
+        &accounts.pc_token_program,
+        &accounts.pc_token_mint,
+        accounts.pc_token_vault.to_account_info(),
+        accounts.liquidity_provider.to_account_info(),
+        amm_pda.to_account_info(),
+        signer_seeds,
+    )?;
+
+    // Burn the locked liquidity and close its account as well
+    if let Some(locked_lp_token_account) = &accounts.locked_lp_token_account {
+        let token_program = accounts.token_program.to_account_info();
+        let cpi_context = CpiContext::new_with_signer(
+            token_program.clone(),
+            Burn {
+                mint: accounts.lp_token_mint.to_account_info(),
+                from: locked_lp_token_account.to_account_info(),
+                authority: amm_pda.to_account_info(),
+            },
+            signer_seeds,
+        );
+        burn(cpi_context, locked_lp_token_account.amount)?;
+        let cpi_context = CpiContext::new_with_signer(
+            token_program,
+            CloseAccount {
+                account: locked_lp_token_account.to_account_info(),
+                destination: accounts.liquidity_provider.to_account_info(),
+                authority: amm_pda.to_account_info(),
+            },
+            signer_seeds,
+        );
+        close_account(cpi_context)?;
+    }
+
+    emit!(ClosePoolEvent {
+        amm_pda: amm_pda.key(),
+        authority: accounts.authority.key(),
+        base_token_amount,
+        pc_token_amount,
+    });
+    Ok(())
+}
@@ -0,0 +1,40 @@
+use anchor_lang::prelude::*;
+
+use crate::{AMMError, ConcentratedPool, Position};
+
+#[derive(Accounts)]
+#[instruction(concentrated_pool_index: u64)]
+pub struct ClosePosition<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        seeds = [b"concentrated_pool", &concentrated_pool_index.to_le_bytes()[..]],
+        bump = concentrated_pool.bump,
+    )]
+    pub concentrated_pool: Account<'info, ConcentratedPool>,
+    #[account(
+        mut,
+        close = owner,
+        seeds = [
+            b"position",
+            concentrated_pool.key().as_ref(),
+            owner.key().as_ref(),
+            &position.tick_lower.to_le_bytes()[..],
+            &position.tick_upper.to_le_bytes()[..],
+        ],
+        bump = position.bump,
+    )]
+    pub position: Account<'info, Position>,
+}
+
+/// Closes a position once all of its liquidity and fees have been taken out.
+pub fn _close_position(ctx: Context<ClosePosition>, _concentrated_pool_index: u64) -> Result<()> {
+    let position = &ctx.accounts.position;
+    require!(
+        position.liquidity == 0
+            && position.fees_owed_base_token == 0
+            && position.fees_owed_pc_token == 0,
+        AMMError::PositionNotEmpty
+    );
+    Ok(())
+}
@@ -0,0 +1,98 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{
+    AMMError, AmmConfig, CollectProtocolFeesEvent, ConcentratedPool, ProcessTokenInstructions,
+};
+
+#[derive(Accounts)]
+#[instruction(concentrated_pool_index: u64)]
+pub struct CollectConcentratedProtocolFees<'info> {
+    pub treasury: Signer<'info>,
+    #[account(
+        seeds = [b"amm_config"],
+        bump = amm_config.bump,
+        has_one = treasury @ AMMError::Unauthorized,
+    )]
+    pub amm_config: Account<'info, AmmConfig>,
+    #[account(
+        mut,
+        seeds = [b"concentrated_pool", &concentrated_pool_index.to_le_bytes()[..]],
+        bump = concentrated_pool.bump,
+    )]
+    pub concentrated_pool: Account<'info, ConcentratedPool>,
+    #[account(
+        mut,
+        seeds=[b"base_token_vault", base_token_mint.key().as_ref(), concentrated_pool.key().as_ref()],
+        bump=concentrated_pool.base_token_vault_bump,
+        token::mint = base_token_mint,
+        token::authority = concentrated_pool,
+        token::token_program = base_token_program,
+    )]
+    pub base_token_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds=[b"pc_token_vault", pc_token_mint.key().as_ref(), concentrated_pool.key().as_ref()],
+        bump=concentrated_pool.pc_token_vault_bump,
+        token::mint = pc_token_mint,
+        token::authority = concentrated_pool,
+        token::token_program = pc_token_program,
+    )]
+    pub pc_token_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut, token::mint = base_token_mint)]
+    pub treasury_base_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut, token::mint = pc_token_mint)]
+    pub treasury_pc_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mint::token_program = base_token_program)]
+    pub base_token_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = pc_token_program)]
+    pub pc_token_mint: Box<InterfaceAccount<'info, Mint>>,
+    pub base_token_program: Interface<'info, TokenInterface>,
+    pub pc_token_program: Interface<'info, TokenInterface>,
+}
+
+pub fn _collect_concentrated_protocol_fees(
+    ctx: Context<CollectConcentratedProtocolFees>,
+    concentrated_pool_index: u64,
+) -> Result<()> {
+    let (base_token_amount, pc_token_amount) =
+        ctx.accounts.concentrated_pool.collect_protocol_fees();
+    let accounts = &ctx.accounts;
+    let concentrated_pool = &accounts.concentrated_pool;
+
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        b"concentrated_pool",
+        &concentrated_pool_index.to_le_bytes()[..],
+        &[concentrated_pool.bump],
+    ]];
+
+    // Transfer accrued base token protocol fees to the treasury
+    ProcessTokenInstructions::transfer(
+        &accounts.base_token_program,
+        &accounts.base_token_mint,
+        accounts.base_token_vault.to_account_info(),
+        accounts.treasury_base_token_account.to_account_info(),
+        concentrated_pool.to_account_info(),
+        signer_seeds,
+        base_token_amount,
+    )?;
+
+    // Transfer accrued pc token protocol fees to the treasury
+    ProcessTokenInstructions::transfer(
+        &accounts.pc_token_program,
+        &accounts.pc_token_mint,
+        accounts.pc_token_vault.to_account_info(),
+        accounts.treasury_pc_token_account.to_account_info(),
+        concentrated_pool.to_account_info(),
+        signer_seeds,
+        pc_token_amount,
+    )?;
+
+    emit!(CollectProtocolFeesEvent {
+        amm_pda: concentrated_pool.key(),
+        treasury: accounts.treasury.key(),
+        base_token_amount,
+        pc_token_amount,
+    });
+    Ok(())
+}
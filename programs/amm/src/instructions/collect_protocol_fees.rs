@@ -0,0 +1,96 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{
+    AMMError, AmmConfig, CollectProtocolFeesEvent, InitalizeLiquidityAccount,
+    ProcessTokenInstructions,
+};
+
+#[derive(Accounts)]
+#[instruction(amm_pda_index: u64)]
+pub struct CollectProtocolFees<'info> {
+    pub treasury: Signer<'info>,
+    #[account(
+        seeds = [b"amm_config"],
+        bump = amm_config.bump,
+        has_one = treasury @ AMMError::Unauthorized,
+    )]
+    pub amm_config: Account<'info, AmmConfig>,
+    #[account(
+        mut,
+        seeds=[b"amm_pda", &amm_pda_index.to_le_bytes()],
+        bump = amm_pda.bump
+    )]
+    amm_pda: Account<'info, InitalizeLiquidityAccount>,
+    #[account(
+        mut,
+        seeds=[b"base_token_vault", base_token_mint.key().as_ref(), amm_pda.key().as_ref()],
+        bump=amm_pda.base_token_vault_bump,
+        token::mint = base_token_mint,
+        token::authority = amm_pda,
+        token::token_program = base_token_program,
+    )]
+    pub base_token_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds=[b"pc_token_vault", pc_token_mint.key().as_ref(), amm_pda.key().as_ref()],
+        bump=amm_pda.pc_token_vault_bump,
+        token::mint = pc_token_mint,
+        token::authority = amm_pda,
+        token::token_program = pc_token_program,
+    )]
+    pub pc_token_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut, token::mint = base_token_mint)]
+    pub treasury_base_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut, token::mint = pc_token_mint)]
+    pub treasury_pc_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mint::token_program = base_token_program)]
+    pub base_token_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = pc_token_program)]
+    pub pc_token_mint: Box<InterfaceAccount<'info, Mint>>,
+    pub base_token_program: Interface<'info, TokenInterface>,
+    pub pc_token_program: Interface<'info, TokenInterface>,
+}
+
+pub fn _collect_protocol_fees(ctx: Context<CollectProtocolFees>, amm_pda_index: u64) -> Result<()> {
+    let (base_token_amount, pc_token_amount) = ctx.accounts.amm_pda.collect_protocol_fees();
+    let accounts = &ctx.accounts;
+    let amm_pda = &accounts.amm_pda;
+    amm_pda.flash_loan.require_inactive()?;
+
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        b"amm_pda",
+        &amm_pda_index.to_le_bytes()[..],
+        &[amm_pda.bump],
+    ]];
+
+    // Transfer accrued base token protocol fees to the treasury
+    ProcessTokenInstructions::transfer(
+        &accounts.base_token_program,
+        &accounts.base_token_mint,
+        accounts.base_token_vault.to_account_info(),
+        accounts.treasury_base_token_account.to_account_info(),
+        amm_pda.to_account_info(),
+        signer_seeds,
+        base_token_amount,
+    )?;
+
+    // Transfer accrued pc token protocol fees to the treasury
+    ProcessTokenInstructions::transfer(
+        &accounts.pc_token_program,
+        &accounts.pc_token_mint,
+        accounts.pc_token_vault.to_account_info(),
+        accounts.treasury_pc_token_account.to_account_info(),
+        amm_pda.to_account_info(),
+        signer_seeds,
+        pc_token_amount,
+    )?;
+
+    emit!(CollectProtocolFeesEvent {
+        amm_pda: amm_pda.key(),
+        treasury: accounts.treasury.key(),
+        base_token_amount,
+        pc_token_amount,
+    });
+    Ok(())
+}
@@ -1,12 +1,11 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{Mint, Token, TokenAccount};
-use anchor_spl::{
-    token::{self, Transfer},
-    token_interface::{self, MintTo},
-};
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token::Token;
+use anchor_spl::token_interface::{self, Mint, MintTo, TokenAccount, TokenInterface};
 
 use crate::{
-    AMMError, DepositEvent, InitalizeLiquidityAccount, ProcessTokenInstructions, TokenCalculator,
+    AMMError, AmmConfig, DepositEvent, InitalizeLiquidityAccount, ObservationState, PoolStatus,
+    ProcessTokenInstructions, SlippageValidator, TokenCalculator,
 };
 
 #[derive(Accounts)]
@@ -14,6 +13,11 @@ use crate::{
 pub struct Deposit<'info> {
     #[account(mut)]
     user: Signer<'info>,
+    #[account(
+        seeds = [b"amm_config"],
+        bump = amm_config.bump,
+    )]
+    pub amm_config: Account<'info, AmmConfig>,
     #[account(
         mut,
         seeds=[b"amm_pda", &amm_pda_index.to_le_bytes()],
@@ -22,20 +26,28 @@ pub struct Deposit<'info> {
     amm_pda: Account<'info, InitalizeLiquidityAccount>,
     #[account(
         mut,
-        seeds=[b"base_token_vault", base_token_mint.key().as_ref()],
+        seeds = [b"observation", amm_pda.key().as_ref()],
+        bump = observation_state.load()?.bump,
+    )]
+    pub observation_state: AccountLoader<'info, ObservationState>,
+    #[account(
+        mut,
+        seeds=[b"base_token_vault", base_token_mint.key().as_ref(), amm_pda.key().as_ref()],
         bump=amm_pda.base_token_vault_bump,
         token::mint = base_token_mint,
         token::authority = amm_pda,
+        token::token_program = base_token_program,
     )]
-    pub base_token_vault: Account<'info, TokenAccount>,
+    pub base_token_vault: Box<InterfaceAccount<'info, TokenAccount>>,
     #[account(
         mut,
-        seeds=[b"pc_token_vault", pc_token_mint.key().as_ref()],
+        seeds=[b"pc_token_vault", pc_token_mint.key().as_ref(), amm_pda.key().as_ref()],
         bump=amm_pda.pc_token_vault_bump,
         token::mint = pc_token_mint,
         token::authority = amm_pda,
+        token::token_program = pc_token_program,
     )]
-    pub pc_token_vault: Account<'info, TokenAccount>,
+    pub pc_token_vault: Box<InterfaceAccount<'info, TokenAccount>>,
     #[account(
         mut,
         mint::decimals = _lp_token_mint_decimal,
@@ -44,26 +56,31 @@ pub struct Deposit<'info> {
         seeds=[b"lp_mint", base_token_mint.key().as_ref(), pc_token_mint.key().as_ref(), amm_pda.key().as_ref()],
         bump=amm_pda.lp_token_mint_bump,
     )]
-    pub lp_token_mint: Account<'info, Mint>,
+    pub lp_token_mint: Box<InterfaceAccount<'info, Mint>>,
     #[account(
-        init,
-        seeds=[b"lp_token_ata", user.key().as_ref(), amm_pda.key().as_ref()],
-        bump,
-        token::mint = lp_token_mint,
-        token::authority = user,
+        init_if_needed,
+        associated_token::mint = lp_token_mint,
+        associated_token::authority = user,
         payer = user
     )]
-    pub liquidity_provider_lp_token_ata: Account<'info, TokenAccount>,
+    pub liquidity_provider_lp_token_ata: Box<InterfaceAccount<'info, TokenAccount>>,
     #[account(mut)]
-    pub liquidity_provider_base_token_ata: Account<'info, TokenAccount>,
+    pub liquidity_provider_base_token_ata: Box<InterfaceAccount<'info, TokenAccount>>,
     #[account(mut)]
-    pub liquidity_provider_pc_token_ata: Account<'info, TokenAccount>,
-    pub base_token_mint: Account<'info, Mint>,
-    pub pc_token_mint: Account<'info, Mint>,
+    pub liquidity_provider_pc_token_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mint::token_program = base_token_program)]
+    pub base_token_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = pc_token_program)]
+    pub pc_token_mint: Box<InterfaceAccount<'info, Mint>>,
+    /// Program of the LP mint, which is always created with SPL Token
     pub token_program: Program<'info, Token>,
+    pub base_token_program: Interface<'info, TokenInterface>,
+    pub pc_token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn _deposit(
     ctx: Context<Deposit>,
     amm_pda_index: u64,
@@ -72,6 +89,7 @@ pub fn _deposit(
     max_base_coin_amount: u64,
     max_pc_coin_amount: u64,
     base_side: u8,
+    min_lp_token_amount: u64,
 ) -> Result<()> {
     let accounts = &ctx.accounts;
 
@@ -82,6 +100,11 @@ pub fn _deposit(
     let base_token_vault_account_info = accounts.base_token_vault.to_account_info();
     let pc_token_vault_account_info = accounts.pc_token_vault.to_account_info();
     let amm_pda = &accounts.amm_pda;
+    amm_pda.flash_loan.require_inactive()?;
+    if accounts.user.key() != amm_pda.liquidity_provider {
+        amm_pda.require_open()?;
+    }
+    PoolStatus::require_deposit_enabled(amm_pda.status | accounts.amm_config.status)?;
     let amm_pda_account_info = amm_pda.to_account_info();
     let lp_mint_account_info = accounts.lp_token_mint.to_account_info();
     let liquidity_provider_lp_token_ata =
@@ -95,8 +118,8 @@ pub fn _deposit(
 
     let lp_mint = &accounts.lp_token_mint;
 
-    let total_base_token = accounts.base_token_vault.amount;
-    let total_pc_token = accounts.pc_token_vault.amount;
+    let total_base_token = amm_pda.base_token_amount;
+    let total_pc_token = amm_pda.pc_token_amount;
 
     require!(
         base_token.to_string() == amm_pda.base_token.to_string(),
@@ -112,31 +135,66 @@ pub fn _deposit(
         return Err(AMMError::NotAllowZeroLP.into());
     }
 
-    let deduct_base_amount;
-    let deduct_pc_amount;
-    let mint_lp_amount;
-
+    let base_token_mint = &accounts.base_token_mint;
+    let pc_token_mint = &accounts.pc_token_mint;
     let token_calculator = TokenCalculator {
         base_token: total_base_token,
         pc_token: total_pc_token,
     };
 
+    // The fixed side is what the user sends and the computed side is what
+    // the vault has to receive. Pool math and LP minting only use amounts
+    // that reach the vaults after any transfer fee.
+    let deduct_base_amount;
+    let deduct_pc_amount;
+    let base_amount_received;
+    let pc_amount_received;
+    let mint_lp_amount;
+
     if base_side == 0 {
-        deduct_pc_amount = token_calculator.exchange_base_to_pc(max_base_coin_amount);
         deduct_base_amount = max_base_coin_amount;
-        mint_lp_amount = token_calculator.exchange_token_to_pool(
+        base_amount_received = deduct_base_amount
+            - ProcessTokenInstructions::transfer_fee(base_token_mint, deduct_base_amount)?;
+        pc_amount_received = token_calculator.exchange_base_to_pc(base_amount_received);
+        deduct_pc_amount = pc_amount_received
+            .checked_add(ProcessTokenInstructions::transfer_inverse_fee(
+                pc_token_mint,
+                pc_amount_received,
+            )?)
+            .ok_or(AMMError::MathOverflow)?;
+        mint_lp_amount = amm_pda.lp_tokens_for_deposit(
             lp_mint.supply,
+            base_amount_received,
+            pc_amount_received,
+        )?;
+        SlippageValidator::validate_deposit(
+            mint_lp_amount,
+            min_lp_token_amount,
             deduct_pc_amount,
-            deduct_base_amount,
-        );
+            max_pc_coin_amount,
+        )?;
     } else {
-        deduct_base_amount = token_calculator.exchange_pc_to_base(max_pc_coin_amount);
         deduct_pc_amount = max_pc_coin_amount;
-        mint_lp_amount = token_calculator.exchange_token_to_pool(
+        pc_amount_received = deduct_pc_amount
+            - ProcessTokenInstructions::transfer_fee(pc_token_mint, deduct_pc_amount)?;
+        base_amount_received = token_calculator.exchange_pc_to_base(pc_amount_received);
+        deduct_base_amount = base_amount_received
+            .checked_add(ProcessTokenInstructions::transfer_inverse_fee(
+                base_token_mint,
+                base_amount_received,
+            )?)
+            .ok_or(AMMError::MathOverflow)?;
+        mint_lp_amount = amm_pda.lp_tokens_for_deposit(
             lp_mint.supply,
-            deduct_pc_amount,
+            base_amount_received,
+            pc_amount_received,
+        )?;
+        SlippageValidator::validate_deposit(
+            mint_lp_amount,
+            min_lp_token_amount,
             deduct_base_amount,
-        );
+            max_base_coin_amount,
+        )?;
     }
 
     require!(
@@ -159,39 +217,41 @@ pub fn _deposit(
         to: liquidity_provider_lp_token_ata,
         authority: amm_pda_account_info,
     };
-    let cpi_context = CpiContext::new_with_signer(token_program.clone(), cpi_account, signer_seeds);
+    let cpi_context = CpiContext::new_with_signer(token_program, cpi_account, signer_seeds);
     token_interface::mint_to(cpi_context, mint_lp_amount)?;
 
     // Transfer base token to on-chain token vault
-    let liquidity_provider_info = liquidity_provider.clone();
-    let cpi_ctx = CpiContext::new(
-        token_program.clone(),
-        Transfer {
-            from: liquidity_provider_base_token_ata,
-            to: base_token_vault_account_info,
-            authority: liquidity_provider_info.clone(),
-        },
-    );
-    token::transfer(cpi_ctx, deduct_base_amount)?;
+    ProcessTokenInstructions::transfer(
+        &accounts.base_token_program,
+        &accounts.base_token_mint,
+        liquidity_provider_base_token_ata,
+        base_token_vault_account_info,
+        liquidity_provider.clone(),
+        &[],
+        deduct_base_amount,
+    )?;
 
     // Transfer cp token to on-chain token vault
-
-    let cpi_ctx = CpiContext::new(
-        token_program,
-        Transfer {
-            from: liquidity_provider_pc_token_ata,
-            to: pc_token_vault_account_info,
-            authority: liquidity_provider_info,
-        },
-    );
-    token::transfer(cpi_ctx, deduct_pc_amount)?;
+    ProcessTokenInstructions::transfer(
+        &accounts.pc_token_program,
+        &accounts.pc_token_mint,
+        liquidity_provider_pc_token_ata,
+        pc_token_vault_account_info,
+        liquidity_provider.clone(),
+        &[],
+        deduct_pc_amount,
+    )?;
     emit!(DepositEvent {
         liquidity_provider: liquidity_provider.key(),
         base_token_mint: base_token,
         pc_token_mint: pc_token,
-        base_token_amount: deduct_base_amount,
-        pc_token_amount: deduct_pc_amount
+        base_token_amount: base_amount_received,
+        pc_token_amount: pc_amount_received
     });
+    ObservationState::record(&ctx.accounts.observation_state, &ctx.accounts.amm_pda)?;
+    ctx.accounts
+        .amm_pda
+        .add_liquidity(base_amount_received, pc_amount_received)?;
 
     Ok(())
 }
@@ -0,0 +1,268 @@
+use anchor_lang::prelude::*;
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token::Token;
+use anchor_spl::token_interface::{self, Mint, MintTo, TokenAccount, TokenInterface};
+
+use crate::{
+    AMMCalculator, AMMError, AmmConfig, CurveType, DepositEvent, InitalizeLiquidityAccount,
+    ObservationState, PoolStatus, ProcessTokenInstructions, SwapDirection, SwapEvent,
+    TokenCalculator,
+};
+
+#[derive(Accounts)]
+#[instruction(amm_pda_index: u64)]
+pub struct DepositSingleSide<'info> {
+    #[account(mut)]
+    user: Signer<'info>,
+    #[account(
+        seeds = [b"amm_config"],
+        bump = amm_config.bump,
+    )]
+    pub amm_config: Account<'info, AmmConfig>,
+    #[account(
+        mut,
+        seeds=[b"amm_pda", &amm_pda_index.to_le_bytes()],
+        bump = amm_pda.bump,
+        constraint = amm_pda.curve_type == CurveType::ConstantProduct @ AMMError::InvalidCurveType,
+    )]
+    amm_pda: Account<'info, InitalizeLiquidityAccount>,
+    #[account(
+        mut,
+        seeds = [b"observation", amm_pda.key().as_ref()],
+        bump = observation_state.load()?.bump,
+    )]
+    pub observation_state: AccountLoader<'info, ObservationState>,
+    #[account(
+        mut,
+        seeds=[b"base_token_vault", base_token_mint.key().as_ref(), amm_pda.key().as_ref()],
+        bump=amm_pda.base_token_vault_bump,
+        token::mint = base_token_mint,
+        token::authority = amm_pda,
+        token::token_program = base_token_program,
+    )]
+    pub base_token_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds=[b"pc_token_vault", pc_token_mint.key().as_ref(), amm_pda.key().as_ref()],
+        bump=amm_pda.pc_token_vault_bump,
+        token::mint = pc_token_mint,
+        token::authority = amm_pda,
+        token::token_program = pc_token_program,
+    )]
+    pub pc_token_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        mint::authority= amm_pda,
+        seeds=[b"lp_mint", base_token_mint.key().as_ref(), pc_token_mint.key().as_ref(), amm_pda.key().as_ref()],
+        bump=amm_pda.lp_token_mint_bump,
+    )]
+    pub lp_token_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        init_if_needed,
+        associated_token::mint = lp_token_mint,
+        associated_token::authority = user,
+        payer = user
+    )]
+    pub liquidity_provider_lp_token_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    /// Source of base deposits and receiver of base refunds
+    #[account(mut, token::mint = base_token_mint)]
+    pub liquidity_provider_base_token_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    /// Source of pc deposits and receiver of pc refunds
+    #[account(mut, token::mint = pc_token_mint)]
+    pub liquidity_provider_pc_token_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mint::token_program = base_token_program)]
+    pub base_token_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = pc_token_program)]
+    pub pc_token_mint: Box<InterfaceAccount<'info, Mint>>,
+    /// Program of the LP mint, which is always created with SPL Token
+    pub token_program: Program<'info, Token>,
+    pub base_token_program: Interface<'info, TokenInterface>,
+    pub pc_token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Deposits `amount_in` of one token into a constant product pool, base when
+/// `base_side` is 0 and pc otherwise. Part of it is swapped for the other
+/// token at the pool's fee so the rest can be added in the pool's ratio.
+/// Whatever rounding leaves over on either side is refunded.
+pub fn _deposit_single_side(
+    ctx: Context<DepositSingleSide>,
+    amm_pda_index: u64,
+    amount_in: u64,
+    base_side: u8,
+    min_lp_token_amount: u64,
+) -> Result<()> {
+    require!(amount_in > 0, AMMError::InvalidAmount);
+    let accounts = &ctx.accounts;
+    let amm_pda = &accounts.amm_pda;
+    amm_pda.flash_loan.require_inactive()?;
+    amm_pda.require_open()?;
+    PoolStatus::require_swap_enabled(amm_pda.status | accounts.amm_config.status)?;
+    PoolStatus::require_deposit_enabled(amm_pda.status | accounts.amm_config.status)?;
+    require!(accounts.lp_token_mint.supply > 0, AMMError::NotAllowZeroLP);
+
+    let base_side_accounts = (
+        &accounts.base_token_vault,
+        &accounts.base_token_mint,
+        &accounts.base_token_program,
+        &accounts.liquidity_provider_base_token_ata,
+    );
+    let pc_side_accounts = (
+        &accounts.pc_token_vault,
+        &accounts.pc_token_mint,
+        &accounts.pc_token_program,
+        &accounts.liquidity_provider_pc_token_ata,
+    );
+    let (
+        swap_direction,
+        (source_token_vault, source_token_mint, source_token_program, user_source_ata),
+        total_in,
+    ) = if base_side == 0 {
+        (
+            SwapDirection::Coin2Pc,
+            base_side_accounts,
+            amm_pda.base_token_amount,
+        )
+    } else {
+        (
+            SwapDirection::Pc2Coin,
+            pc_side_accounts,
+            amm_pda.pc_token_amount,
+        )
+    };
+
+    let amount_in_received =
+        amount_in - ProcessTokenInstructions::transfer_fee(source_token_mint, amount_in)?;
+    let fees = amm_pda.swap_fees()?;
+    let swap_amount = u64::try_from(AMMCalculator::zap_swap_amount(
+        amount_in_received.into(),
+        total_in.into(),
+        &fees,
+    )?)
+    .map_err(|_| AMMError::MathOverflow)?;
+    let swap_fee = fees.swap_fee(swap_amount)?;
+    let protocol_fee = fees.protocol_fee(swap_fee)?;
+    // The swapped tokens never leave the vault, so no transfer fee applies
+    let swap_amount_out = amm_pda.swap_amount_out(swap_amount - swap_fee, swap_direction)?;
+
+    let source_mint = source_token_mint.key();
+    let destination_mint = match swap_direction {
+        SwapDirection::Coin2Pc => accounts.pc_token_mint.key(),
+        SwapDirection::Pc2Coin => accounts.base_token_mint.key(),
+    };
+    // Transfer source token to source token vault
+    ProcessTokenInstructions::transfer(
+        source_token_program,
+        source_token_mint,
+        user_source_ata.to_account_info(),
+        source_token_vault.to_account_info(),
+        accounts.user.to_account_info(),
+        &[],
+        amount_in,
+    )?;
+
+    ObservationState::record(&ctx.accounts.observation_state, &ctx.accounts.amm_pda)?;
+    let amm_pda = &mut ctx.accounts.amm_pda;
+    amm_pda.apply_swap(swap_amount, swap_amount_out, protocol_fee, swap_direction)?;
+    emit!(SwapEvent {
+        amount_in: swap_amount,
+        direction: swap_direction as u8,
+        user_source: source_mint,
+        user_destination: destination_mint,
+        swap_amount_out,
+        swap_fee,
+    });
+
+    // Add as much as fits the post-swap ratio
+    let amount_left = amount_in_received - swap_amount;
+    let (base_token_available, pc_token_available) = match swap_direction {
+        SwapDirection::Coin2Pc => (amount_left, swap_amount_out),
+        SwapDirection::Pc2Coin => (swap_amount_out, amount_left),
+    };
+    let token_calculator = TokenCalculator {
+        base_token: amm_pda.base_token_amount,
+        pc_token: amm_pda.pc_token_amount,
+    };
+    let pc_token_needed = token_calculator.exchange_base_to_pc(base_token_available);
+    let (base_token_amount, pc_token_amount) = if pc_token_needed <= pc_token_available {
+        (base_token_available, pc_token_needed)
+    } else {
+        (
+            token_calculator.exchange_pc_to_base(pc_token_available),
+            pc_token_available,
+        )
+    };
+    let accounts = &ctx.accounts;
+    let mint_lp_amount = accounts.amm_pda.lp_tokens_for_deposit(
+        accounts.lp_token_mint.supply,
+        base_token_amount,
+        pc_token_amount,
+    )?;
+    require!(mint_lp_amount > 0, AMMError::InvalidAmount);
+    require_gte!(
+        mint_lp_amount,
+        min_lp_token_amount,
+        AMMError::DepositSlippageExceeded
+    );
+
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        b"amm_pda",
+        &amm_pda_index.to_le_bytes()[..],
+        &[accounts.amm_pda.bump],
+    ]];
+    let cpi_account = MintTo {
+        mint: accounts.lp_token_mint.to_account_info(),
+        to: accounts.liquidity_provider_lp_token_ata.to_account_info(),
+        authority: accounts.amm_pda.to_account_info(),
+    };
+    let cpi_context = CpiContext::new_with_signer(
+        accounts.token_program.to_account_info(),
+        cpi_account,
+        signer_seeds,
+    );
+    token_interface::mint_to(cpi_context, mint_lp_amount)?;
+
+    // Refund what didn't fit the ratio
+    let refunds = [
+        (
+            base_token_available - base_token_amount,
+            &accounts.base_token_program,
+            &accounts.base_token_mint,
+            &accounts.base_token_vault,
+            &accounts.liquidity_provider_base_token_ata,
+        ),
+        (
+            pc_token_available - pc_token_amount,
+            &accounts.pc_token_program,
+            &accounts.pc_token_mint,
+            &accounts.pc_token_vault,
+            &accounts.liquidity_provider_pc_token_ata,
+        ),
+    ];
+    for (refund, token_program, token_mint, token_vault, user_ata) in refunds {
+        if refund > 0 {
+            ProcessTokenInstructions::transfer(
+                token_program,
+                token_mint,
+                token_vault.to_account_info(),
+                user_ata.to_account_info(),
+                accounts.amm_pda.to_account_info(),
+                signer_seeds,
+                refund,
+            )?;
+        }
+    }
+
+    emit!(DepositEvent {
+        liquidity_provider: accounts.user.key(),
+        base_token_mint: accounts.base_token_mint.key(),
+        pc_token_mint: accounts.pc_token_mint.key(),
+        base_token_amount,
+        pc_token_amount
+    });
+    ctx.accounts
+        .amm_pda
+        .add_liquidity(base_token_amount, pc_token_amount)?;
+    Ok(())
+}
@@ -0,0 +1,35 @@
+use anchor_lang::prelude::*;
+
+use crate::{AMMError, AmmConfig, FeeTierEvent, Fees};
+
+#[derive(Accounts)]
+pub struct ManageFeeTier<'info> {
+    pub admin: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"amm_config"],
+        bump = amm_config.bump,
+        has_one = admin @ AMMError::Unauthorized,
+    )]
+    pub amm_config: Account<'info, AmmConfig>,
+}
+
+pub fn _create_fee_tier(ctx: Context<ManageFeeTier>, fees: Fees) -> Result<()> {
+    let fee_tier_index = ctx.accounts.amm_config.add_fee_tier(fees.clone())?;
+    emit!(FeeTierEvent::new(fee_tier_index, &fees));
+    Ok(())
+}
+
+/// Pools copy their tier's fees, so pools already on this tier keep the old
+/// fees until `set_pool_fees` is called for them.
+pub fn _update_fee_tier(
+    ctx: Context<ManageFeeTier>,
+    fee_tier_index: u16,
+    fees: Fees,
+) -> Result<()> {
+    ctx.accounts
+        .amm_config
+        .update_fee_tier(fee_tier_index, fees.clone())?;
+    emit!(FeeTierEvent::new(fee_tier_index, &fees));
+    Ok(())
+}
@@ -0,0 +1,252 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::{get_stack_height, TRANSACTION_LEVEL_STACK_HEIGHT};
+use anchor_lang::solana_program::sysvar::instructions::{
+    self, load_current_index_checked, load_instruction_at_checked,
+};
+use anchor_lang::Discriminator;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{
+    AMMError, AmmConfig, FlashLoanEvent, InitalizeLiquidityAccount, ObservationState, PoolStatus,
+    ProcessTokenInstructions,
+};
+
+/// Position of `amm_pda` in the accounts of `flash_loan_end`, used to match
+/// a loan with its repayment
+pub const FLASH_LOAN_END_AMM_PDA_ACCOUNT: usize = 1;
+
+#[derive(Accounts)]
+#[instruction(amm_pda_index: u64)]
+pub struct FlashLoanBegin<'info> {
+    pub borrower: Signer<'info>,
+    #[account(
+        mut,
+        seeds=[b"amm_pda", &amm_pda_index.to_le_bytes()],
+        bump = amm_pda.bump
+    )]
+    amm_pda: Account<'info, InitalizeLiquidityAccount>,
+    #[account(
+        seeds = [b"amm_config"],
+        bump = amm_config.bump,
+    )]
+    pub amm_config: Account<'info, AmmConfig>,
+    #[account(
+        mut,
+        seeds=[b"base_token_vault", base_token_mint.key().as_ref(), amm_pda.key().as_ref()],
+        bump=amm_pda.base_token_vault_bump,
+        token::mint = base_token_mint,
+        token::authority = amm_pda,
+        token::token_program = base_token_program,
+    )]
+    pub base_token_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds=[b"pc_token_vault", pc_token_mint.key().as_ref(), amm_pda.key().as_ref()],
+        bump=amm_pda.pc_token_vault_bump,
+        token::mint = pc_token_mint,
+        token::authority = amm_pda,
+        token::token_program = pc_token_program,
+    )]
+    pub pc_token_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut, token::mint = base_token_mint)]
+    pub borrower_base_token_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut, token::mint = pc_token_mint)]
+    pub borrower_pc_token_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mint::token_program = base_token_program)]
+    pub base_token_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = pc_token_program)]
+    pub pc_token_mint: Box<InterfaceAccount<'info, Mint>>,
+    pub base_token_program: Interface<'info, TokenInterface>,
+    pub pc_token_program: Interface<'info, TokenInterface>,
+    /// CHECK: the instructions sysvar, checked by address
+    #[account(address = instructions::ID)]
+    pub instructions_sysvar: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(amm_pda_index: u64)]
+pub struct FlashLoanEnd<'info> {
+    pub borrower: Signer<'info>,
+    // Has to stay at `FLASH_LOAN_END_AMM_PDA_ACCOUNT`
+    #[account(
+        mut,
+        seeds=[b"amm_pda", &amm_pda_index.to_le_bytes()],
+        bump = amm_pda.bump
+    )]
+    amm_pda: Account<'info, InitalizeLiquidityAccount>,
+    #[account(
+        mut,
+        seeds = [b"observation", amm_pda.key().as_ref()],
+        bump = observation_state.load()?.bump,
+    )]
+    pub observation_state: AccountLoader<'info, ObservationState>,
+    #[account(
+        mut,
+        seeds=[b"base_token_vault", base_token_mint.key().as_ref(), amm_pda.key().as_ref()],
+        bump=amm_pda.base_token_vault_bump,
+        token::mint = base_token_mint,
+        token::authority = amm_pda,
+        token::token_program = base_token_program,
+    )]
+    pub base_token_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds=[b"pc_token_vault", pc_token_mint.key().as_ref(), amm_pda.key().as_ref()],
+        bump=amm_pda.pc_token_vault_bump,
+        token::mint = pc_token_mint,
+        token::authority = amm_pda,
+        token::token_program = pc_token_program,
+    )]
+    pub pc_token_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut, token::mint = base_token_mint)]
+    pub borrower_base_token_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut, token::mint = pc_token_mint)]
+    pub borrower_pc_token_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mint::token_program = base_token_program)]
+    pub base_token_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = pc_token_program)]
+    pub pc_token_mint: Box<InterfaceAccount<'info, Mint>>,
+    pub base_token_program: Interface<'info, TokenInterface>,
+    pub pc_token_program: Interface<'info, TokenInterface>,
+}
+
+/// Lends up to the pool's reserves to the borrower. The transaction must
+/// call `flash_loan_end` on the same pool in a later top-level instruction,
+/// which takes the loan back with the flash loan fee. Until then the pool
+/// rejects swaps, deposits and withdrawals.
+pub fn _flash_loan_begin(
+    ctx: Context<FlashLoanBegin>,
+    amm_pda_index: u64,
+    base_token_amount: u64,
+    pc_token_amount: u64,
+) -> Result<()> {
+    // Called through CPI, the sysvar would describe the caller's instruction
+    // instead of this one
+    require_eq!(
+        get_stack_height(),
+        TRANSACTION_LEVEL_STACK_HEIGHT,
+        AMMError::InvalidFlashLoan
+    );
+    let accounts = &mut *ctx.accounts;
+    PoolStatus::require_swap_enabled(accounts.amm_pda.status | accounts.amm_config.status)?;
+    accounts.amm_pda.require_open()?;
+    let amm_pda_key = accounts.amm_pda.key();
+    let instructions_sysvar = accounts.instructions_sysvar.to_account_info();
+    let mut index = usize::from(load_current_index_checked(&instructions_sysvar)?) + 1;
+    loop {
+        let instruction = load_instruction_at_checked(index, &instructions_sysvar)
+            .map_err(|_| AMMError::FlashLoanNotRepaid)?;
+        if instruction.program_id == crate::ID
+            && instruction
+                .data
+                .starts_with(crate::instruction::FlashLoanEnd::DISCRIMINATOR)
+            && instruction
+                .accounts
+                .get(FLASH_LOAN_END_AMM_PDA_ACCOUNT)
+                .is_some_and(|account| account.pubkey == amm_pda_key)
+        {
+            break;
+        }
+        index += 1;
+    }
+
+    let base_token_fee = accounts.amm_config.flash_loan_fee(base_token_amount)?;
+    let pc_token_fee = accounts.amm_config.flash_loan_fee(pc_token_amount)?;
+    accounts.amm_pda.begin_flash_loan(
+        accounts.borrower.key(),
+        base_token_amount,
+        pc_token_amount,
+        base_token_fee,
+        pc_token_fee,
+    )?;
+
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        b"amm_pda",
+        &amm_pda_index.to_le_bytes()[..],
+        &[accounts.amm_pda.bump],
+    ]];
+    if base_token_amount > 0 {
+        ProcessTokenInstructions::transfer(
+            &accounts.base_token_program,
+            &accounts.base_token_mint,
+            accounts.base_token_vault.to_account_info(),
+            accounts.borrower_base_token_ata.to_account_info(),
+            accounts.amm_pda.to_account_info(),
+            signer_seeds,
+            base_token_amount,
+        )?;
+    }
+    if pc_token_amount > 0 {
+        ProcessTokenInstructions::transfer(
+            &accounts.pc_token_program,
+            &accounts.pc_token_mint,
+            accounts.pc_token_vault.to_account_info(),
+            accounts.borrower_pc_token_ata.to_account_info(),
+            accounts.amm_pda.to_account_info(),
+            signer_seeds,
+            pc_token_amount,
+        )?;
+    }
+    Ok(())
+}
+
+/// Repays the pool's open flash loan plus its fee. For transfer-fee mints
+/// the borrower also covers the transfer fee, so the vaults get the full
+/// amount back.
+pub fn _flash_loan_end(ctx: Context<FlashLoanEnd>, _amm_pda_index: u64) -> Result<()> {
+    let accounts = &mut *ctx.accounts;
+    // The fees go into the reserves
+    ObservationState::record(&accounts.observation_state, &accounts.amm_pda)?;
+    let flash_loan = accounts.amm_pda.end_flash_loan(accounts.borrower.key())?;
+
+    let base_token_repayment = flash_loan
+        .base_token_amount
+        .checked_add(flash_loan.base_token_fee)
+        .ok_or(AMMError::MathOverflow)?;
+    let pc_token_repayment = flash_loan
+        .pc_token_amount
+        .checked_add(flash_loan.pc_token_fee)
+        .ok_or(AMMError::MathOverflow)?;
+    let borrower = accounts.borrower.to_account_info();
+    if base_token_repayment > 0 {
+        ProcessTokenInstructions::transfer(
+            &accounts.base_token_program,
+            &accounts.base_token_mint,
+            accounts.borrower_base_token_ata.to_account_info(),
+            accounts.base_token_vault.to_account_info(),
+            borrower.clone(),
+            &[],
+            base_token_repayment
+                .checked_add(ProcessTokenInstructions::transfer_inverse_fee(
+                    &accounts.base_token_mint,
+                    base_token_repayment,
+                )?)
+                .ok_or(AMMError::MathOverflow)?,
+        )?;
+    }
+    if pc_token_repayment > 0 {
+        ProcessTokenInstructions::transfer(
+            &accounts.pc_token_program,
+            &accounts.pc_token_mint,
+            accounts.borrower_pc_token_ata.to_account_info(),
+            accounts.pc_token_vault.to_account_info(),
+            borrower,
+            &[],
+            pc_token_repayment
+                .checked_add(ProcessTokenInstructions::transfer_inverse_fee(
+                    &accounts.pc_token_mint,
+                    pc_token_repayment,
+                )?)
+                .ok_or(AMMError::MathOverflow)?,
+        )?;
+    }
+    emit!(FlashLoanEvent {
+        amm_pda: accounts.amm_pda.key(),
+        borrower: accounts.borrower.key(),
+        base_token_amount: flash_loan.base_token_amount,
+        pc_token_amount: flash_loan.pc_token_amount,
+        base_token_fee: flash_loan.base_token_fee,
+        pc_token_fee: flash_loan.pc_token_fee,
+    });
+    Ok(())
+}
@@ -0,0 +1,35 @@
+use anchor_lang::prelude::*;
+
+use crate::{InitalizeLiquidityAccount, ObservationState};
+
+#[derive(Accounts)]
+#[instruction(amm_pda_index: u64, capacity: u16)]
+pub struct GrowObservations<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        seeds=[b"amm_pda", &amm_pda_index.to_le_bytes()],
+        bump = amm_pda.bump
+    )]
+    amm_pda: Account<'info, InitalizeLiquidityAccount>,
+    #[account(
+        mut,
+        seeds = [b"observation", amm_pda.key().as_ref()],
+        bump = observation_state.load()?.bump,
+        realloc = ObservationState::space(capacity),
+        realloc::payer = payer,
+        realloc::zero = false,
+    )]
+    pub observation_state: AccountLoader<'info, ObservationState>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Grows the pool's observation buffer to `capacity` slots, with the payer
+/// covering the extra rent. A single call can add about 180 slots.
+pub fn _grow_observations(
+    ctx: Context<GrowObservations>,
+    _amm_pda_index: u64,
+    capacity: u16,
+) -> Result<()> {
+    ctx.accounts.observation_state.load_mut()?.grow(capacity)
+}
@@ -0,0 +1,43 @@
+use anchor_lang::prelude::*;
+
+use crate::{program::Amm, AmmConfig, FeeTierEvent, Fees, DEFAULT_LOCKED_LIQUIDITY};
+
+#[derive(Accounts)]
+pub struct InitializeAmmConfig<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + AmmConfig::MAX_SIZE,
+        seeds = [b"amm_config"],
+        bump
+    )]
+    pub amm_config: Account<'info, AmmConfig>,
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, Amm>,
+    // Only the upgrade authority of the program can claim the admin role
+    #[account(constraint = program_data.upgrade_authority_address == Some(admin.key()))]
+    pub program_data: Account<'info, ProgramData>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn _initialize_amm_config(ctx: Context<InitializeAmmConfig>) -> Result<()> {
+    let amm_config = &mut ctx.accounts.amm_config;
+    amm_config.admin = ctx.accounts.admin.key();
+    amm_config.bump = ctx.bumps.amm_config;
+    amm_config.treasury = ctx.accounts.admin.key();
+    amm_config.set_flash_loan_fee(9, 10000)?;
+    amm_config.set_locked_liquidity(DEFAULT_LOCKED_LIQUIDITY)?;
+
+    // Tier 0 keeps the fee pools were created with before fee tiers existed
+    let fees = Fees {
+        swap_fee_numerator: 25,
+        swap_fee_denominator: 10000,
+        protocol_fee_numerator: 0,
+        protocol_fee_denominator: 10000,
+    };
+    let fee_tier_index = amm_config.add_fee_tier(fees.clone())?;
+    emit!(FeeTierEvent::new(fee_tier_index, &fees));
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{
+    AmmConfig, ConcentratedPool, InitializeConcentratedPoolEvent, ProcessTokenInstructions,
+};
+
+#[derive(Accounts)]
+#[instruction(concentrated_pool_index: u64)]
+pub struct InitializeConcentratedPool<'info> {
+    #[account(mut)]
+    pub creator: Signer<'info>,
+    #[account(
+        seeds = [b"amm_config"],
+        bump = amm_config.bump,
+    )]
+    pub amm_config: Account<'info, AmmConfig>,
+    #[account(
+        init,
+        payer = creator,
+        space = 8 + ConcentratedPool::MAX_SIZE,
+        seeds = [b"concentrated_pool", &concentrated_pool_index.to_le_bytes()[..]],
+        bump,
+    )]
+    pub concentrated_pool: Account<'info, ConcentratedPool>,
+    #[account(
+        init,
+        seeds=[b"base_token_vault", base_token_mint.key().as_ref(), concentrated_pool.key().as_ref()],
+        bump,
+        token::mint = base_token_mint,
+        token::authority = concentrated_pool,
+        token::token_program = base_token_program,
+        payer = creator
+    )]
+    pub base_token_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init,
+        seeds=[b"pc_token_vault", pc_token_mint.key().as_ref(), concentrated_pool.key().as_ref()],
+        bump,
+        token::mint = pc_token_mint,
+        token::authority = concentrated_pool,
+        token::token_program = pc_token_program,
+        payer = creator
+    )]
+    pub pc_token_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mint::token_program = base_token_program)]
+    pub base_token_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = pc_token_program)]
+    pub pc_token_mint: Box<InterfaceAccount<'info, Mint>>,
+    pub base_token_program: Interface<'info, TokenInterface>,
+    pub pc_token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Creates an empty concentrated liquidity pool at `initial_sqrt_price_q64`,
+/// the Q64.64 square root of the price in pc units per base unit.
+pub fn _initialize_concentrated_pool(
+    ctx: Context<InitializeConcentratedPool>,
+    _concentrated_pool_index: u64,
+    tick_spacing: u16,
+    initial_sqrt_price_q64: u128,
+    fee_tier_index: u16,
+) -> Result<()> {
+    let accounts = &mut *ctx.accounts;
+    ProcessTokenInstructions::validate_mint_extensions(&accounts.base_token_mint)?;
+    ProcessTokenInstructions::validate_mint_extensions(&accounts.pc_token_mint)?;
+
+    let fees = accounts.amm_config.fee_tier(fee_tier_index)?;
+    accounts.concentrated_pool.initialize(
+        accounts.base_token_mint.key(),
+        accounts.pc_token_mint.key(),
+        accounts.creator.key(),
+        ctx.bumps.concentrated_pool,
+        ctx.bumps.base_token_vault,
+        ctx.bumps.pc_token_vault,
+        tick_spacing,
+        fee_tier_index,
+        fees,
+        initial_sqrt_price_q64,
+    )?;
+    emit!(InitializeConcentratedPoolEvent {
+        concentrated_pool: accounts.concentrated_pool.key(),
+        base_token_mint: accounts.base_token_mint.key(),
+        pc_token_mint: accounts.pc_token_mint.key(),
+        tick_spacing,
+        sqrt_price_q64: initial_sqrt_price_q64,
+    });
+    Ok(())
+}
@@ -1,17 +1,23 @@
 use crate::InitializeLiquidityPoolEvent;
-use crate::{state::InitalizeLiquidityAccount, AMMError, Converter};
-use anchor_lang::prelude::*;
-use anchor_spl::token::{Mint, Token, TokenAccount};
-use anchor_spl::{
-    token::{self, Transfer},
-    token_interface::{self, MintTo},
+use crate::{
+    state::InitalizeLiquidityAccount, AMMError, AmmConfig, Converter, CurveType, LaunchFee,
+    ObservationState, ProcessTokenInstructions, Weights,
 };
+use anchor_lang::prelude::*;
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token::Token;
+use anchor_spl::token_interface::{self, Mint, MintTo, TokenAccount, TokenInterface};
 use integer_sqrt::IntegerSquareRoot;
 #[derive(Accounts)]
 #[instruction(lp_token_mint_decimal: u8, amm_pda_index: u64)]
 pub struct InitializeLiquidity<'info> {
     #[account(mut)]
     pub liquidity_provider: Signer<'info>,
+    #[account(
+        seeds = [b"amm_config"],
+        bump = amm_config.bump,
+    )]
+    pub amm_config: Account<'info, AmmConfig>,
     #[account(
     init_if_needed,
     payer=liquidity_provider,
@@ -22,22 +28,32 @@ pub struct InitializeLiquidity<'info> {
     pub amm_pda: Account<'info, InitalizeLiquidityAccount>,
     #[account(
         init,
-        seeds=[b"base_token_vault", base_token_mint.key().as_ref()],
+        payer = liquidity_provider,
+        space = ObservationState::space(1),
+        seeds = [b"observation", amm_pda.key().as_ref()],
+        bump,
+    )]
+    pub observation_state: AccountLoader<'info, ObservationState>,
+    #[account(
+        init,
+        seeds=[b"base_token_vault", base_token_mint.key().as_ref(), amm_pda.key().as_ref()],
         bump,
         token::mint = base_token_mint,
         token::authority = amm_pda,
+        token::token_program = base_token_program,
         payer = liquidity_provider
     )]
-    pub base_token_vault: Account<'info, TokenAccount>,
+    pub base_token_vault: Box<InterfaceAccount<'info, TokenAccount>>,
     #[account(
         init,
-        seeds=[b"pc_token_vault", pc_token_mint.key().as_ref()],
+        seeds=[b"pc_token_vault", pc_token_mint.key().as_ref(), amm_pda.key().as_ref()],
         bump,
         token::mint = pc_token_mint,
         token::authority = amm_pda,
+        token::token_program = pc_token_program,
         payer = liquidity_provider
     )]
-    pub pc_token_vault: Account<'info, TokenAccount>,
+    pub pc_token_vault: Box<InterfaceAccount<'info, TokenAccount>>,
 
     #[account(
         init,
@@ -48,27 +64,43 @@ pub struct InitializeLiquidity<'info> {
         seeds=[b"lp_mint", base_token_mint.key().as_ref(), pc_token_mint.key().as_ref(), amm_pda.key().as_ref()],
         bump,
     )]
-    pub lp_token_mint: Account<'info, Mint>,
+    pub lp_token_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        init,
+        associated_token::mint = lp_token_mint,
+        associated_token::authority = liquidity_provider,
+        payer = liquidity_provider
+    )]
+    pub liquidity_provider_lp_token_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    /// Holds the locked minimum liquidity. Only `close_pool` ever moves it.
     #[account(
         init,
-        seeds=[b"lp_token_ata", liquidity_provider.key().as_ref(), amm_pda.key().as_ref()],
+        seeds = [b"locked_lp", amm_pda.key().as_ref()],
         bump,
         token::mint = lp_token_mint,
-        token::authority = liquidity_provider,
+        token::authority = amm_pda,
+        token::token_program = token_program,
         payer = liquidity_provider
     )]
-    pub liquidity_provider_lp_token_ata: Account<'info, TokenAccount>,
+    pub locked_lp_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
     #[account(mut)]
-    pub liquidity_provider_base_token_ata: Account<'info, TokenAccount>,
+    pub liquidity_provider_base_token_ata: Box<InterfaceAccount<'info, TokenAccount>>,
     #[account(mut)]
-    pub liquidity_provider_pc_token_ata: Account<'info, TokenAccount>,
-    pub base_token_mint: Account<'info, Mint>,
-    pub pc_token_mint: Account<'info, Mint>,
+    pub liquidity_provider_pc_token_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mint::token_program = base_token_program)]
+    pub base_token_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = pc_token_program)]
+    pub pc_token_mint: Box<InterfaceAccount<'info, Mint>>,
+    /// Program of the LP mint, which is always created with SPL Token
     pub token_program: Program<'info, Token>,
+    pub base_token_program: Interface<'info, TokenInterface>,
+    pub pc_token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
     pub rent: Sysvar<'info, Rent>,
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn _initialize_liquidity_pool(
     ctx: Context<InitializeLiquidity>,
     lp_token_mint_decimal: u8,
@@ -77,6 +109,12 @@ pub fn _initialize_liquidity_pool(
     pc_token: Pubkey,
     base_token_amount: u64,
     pc_token_amount: u64,
+    fee_tier_index: u16,
+    curve_type: CurveType,
+    amp: u64,
+    weights: Weights,
+    open_time: i64,
+    launch_fee: Option<LaunchFee>,
 ) -> Result<()> {
     require!(lp_token_mint_decimal > 0, AMMError::InvalidLPMintDecimal);
 
@@ -94,11 +132,19 @@ pub fn _initialize_liquidity_pool(
         ctx.program_id,
     );
     let (_, base_token_vault_bump) = Pubkey::find_program_address(
-        &[b"base_token_vault", accounts.base_token_mint.key().as_ref()],
+        &[
+            b"base_token_vault",
+            accounts.base_token_mint.key().as_ref(),
+            accounts.amm_pda.key().as_ref(),
+        ],
         ctx.program_id,
     );
     let (_, pc_token_vault_bump) = Pubkey::find_program_address(
-        &[b"pc_token_vault", accounts.pc_token_mint.key().as_ref()],
+        &[
+            b"pc_token_vault",
+            accounts.pc_token_mint.key().as_ref(),
+            accounts.amm_pda.key().as_ref(),
+        ],
         ctx.program_id,
     );
     let (_, lp_token_mint_bump) = Pubkey::find_program_address(
@@ -116,6 +162,7 @@ pub fn _initialize_liquidity_pool(
     let mint_authority = accounts.amm_pda.to_account_info();
     let liquidity_provider_lp_token_ata =
         accounts.liquidity_provider_lp_token_ata.to_account_info();
+    let locked_lp_token_account = accounts.locked_lp_token_account.to_account_info();
 
     let base_token_vault = accounts.base_token_vault.to_account_info();
     let pc_token_vault = accounts.pc_token_vault.to_account_info();
@@ -131,28 +178,50 @@ pub fn _initialize_liquidity_pool(
     );
     require!(
         !liquidity_provider_base_token_ata.data_is_empty()
-            && liquidity_provider_base_token_ata.owner.key() == token_program.key(),
+            && liquidity_provider_base_token_ata.owner.key() == accounts.base_token_program.key(),
         AMMError::InvalidOrUninitializedAta
     );
     require!(
         !liquidity_provider_pc_token_ata.data_is_empty()
-            && liquidity_provider_pc_token_ata.owner.key() == token_program.key(),
+            && liquidity_provider_pc_token_ata.owner.key() == accounts.pc_token_program.key(),
         AMMError::InvalidOrUninitializedAta
     );
+    ProcessTokenInstructions::validate_mint_extensions(&accounts.base_token_mint)?;
+    ProcessTokenInstructions::validate_mint_extensions(&accounts.pc_token_mint)?;
+    // Reserves start from what reaches the vaults after any transfer fee
+    let base_amount_received = base_token_amount
+        - ProcessTokenInstructions::transfer_fee(&accounts.base_token_mint, base_token_amount)?;
+    let pc_amount_received = pc_token_amount
+        - ProcessTokenInstructions::transfer_fee(&accounts.pc_token_mint, pc_token_amount)?;
+
+    let fees = accounts.amm_config.fee_tier(fee_tier_index)?;
     ctx.accounts.amm_pda.initialize(
         base_token,
         pc_token,
         liquidity_provider.key(),
-        base_token_amount,
-        pc_token_amount,
+        base_amount_received,
+        pc_amount_received,
         bump,
         base_token_vault_bump,
         pc_token_vault_bump,
         lp_token_mint_bump,
+        fee_tier_index,
+        fees,
+        curve_type,
+        amp,
+        weights,
+        open_time,
+        launch_fee,
+    )?;
+    ObservationState::create(
+        &ctx.accounts.observation_state,
+        ctx.accounts.amm_pda.key(),
+        &ctx.accounts.amm_pda,
+        ctx.bumps.observation_state,
     )?;
 
-    let base_u128 = Converter::to_u128(base_token_amount)?;
-    let pc_u128 = Converter::to_u128(pc_token_amount)?;
+    let base_u128 = Converter::to_u128(base_amount_received)?;
+    let pc_u128 = Converter::to_u128(pc_amount_received)?;
 
     let product = base_u128
         .checked_mul(pc_u128)
@@ -161,22 +230,21 @@ pub fn _initialize_liquidity_pool(
     let sqrt = product.integer_sqrt();
 
     let total_share = Converter::to_u64(sqrt)?;
+    let locked_lp_amount = ctx.accounts.amm_config.locked_liquidity;
     require!(
-        total_share > 10u64.checked_pow(lp_token_mint_decimal.into()).unwrap(),
+        total_share > locked_lp_amount,
         AMMError::InsufficientInitialLiquidity
     );
+    ctx.accounts.amm_pda.locked_lp_amount = locked_lp_amount;
 
     // Calculate total lp tokens to mint to liquidity provider
-    // lp_token_to_mint = total_share(sqrt(x * y)) - 10^lp_token_mint_decimal
+    // lp_token_to_mint = total_share(sqrt(x * y)) - locked_lp_amount
     // ------------------------------------------------------------------------
-    // This makes sure the liqudity pool creator gets slightly less lp tokens
-    // This helps not only stabilize the pool but also this makes sure
-    // There is some liquidity left in the pool even after everyone of the
-    // Liquidity provider pulls out their funds
-
-    let lp_token_to_mint = total_share
-        .checked_sub(10u64.checked_pow(lp_token_mint_decimal.into()).unwrap())
-        .unwrap();
+    // The locked part is minted to the pool's own lock account, so the LP
+    // supply matches the reserves it has a claim on, and some liquidity
+    // stays in the pool even after every liquidity provider pulls out
+
+    let lp_token_to_mint = total_share - locked_lp_amount;
     // anchor_spl::token::initialize_mint(
     //     CpiContext::new(
     //         token_program.clone(),
@@ -192,42 +260,49 @@ pub fn _initialize_liquidity_pool(
 
     let signer_seeds: &[&[&[u8]]] = &[&[b"amm_pda", &amm_pda_index.to_le_bytes()[..], &[bump]]];
     let cpi_account = MintTo {
-        mint: lp_token_mint,
+        mint: lp_token_mint.clone(),
         to: liquidity_provider_lp_token_ata,
-        authority: mint_authority,
+        authority: mint_authority.clone(),
     };
     let cpi_context = CpiContext::new_with_signer(token_program.clone(), cpi_account, signer_seeds);
     token_interface::mint_to(cpi_context, lp_token_to_mint)?;
 
+    // Lock the minimum liquidity for good
+    let cpi_account = MintTo {
+        mint: lp_token_mint,
+        to: locked_lp_token_account,
+        authority: mint_authority,
+    };
+    let cpi_context = CpiContext::new_with_signer(token_program, cpi_account, signer_seeds);
+    token_interface::mint_to(cpi_context, locked_lp_amount)?;
+
     // Transfer base token to on-chain token vault
-    let liquidity_provider_info = liquidity_provider.clone();
-    let cpi_ctx = CpiContext::new(
-        token_program.clone(),
-        Transfer {
-            from: liquidity_provider_base_token_ata,
-            to: base_token_vault,
-            authority: liquidity_provider_info.clone(),
-        },
-    );
-    token::transfer(cpi_ctx, base_token_amount)?;
+    ProcessTokenInstructions::transfer(
+        &ctx.accounts.base_token_program,
+        &ctx.accounts.base_token_mint,
+        liquidity_provider_base_token_ata,
+        base_token_vault,
+        liquidity_provider.clone(),
+        &[],
+        base_token_amount,
+    )?;
 
     // Transfer cp token to on-chain token vault
-
-    let cpi_ctx = CpiContext::new(
-        token_program,
-        Transfer {
-            from: liquidity_provider_pc_token_ata,
-            to: pc_token_vault,
-            authority: liquidity_provider_info,
-        },
-    );
-    token::transfer(cpi_ctx, pc_token_amount)?;
+    ProcessTokenInstructions::transfer(
+        &ctx.accounts.pc_token_program,
+        &ctx.accounts.pc_token_mint,
+        liquidity_provider_pc_token_ata,
+        pc_token_vault,
+        liquidity_provider.clone(),
+        &[],
+        pc_token_amount,
+    )?;
     emit!(InitializeLiquidityPoolEvent {
         liquidity_provider: liquidity_provider.key(),
         base_token_mint: base_token,
         pc_token_mint: pc_token,
-        base_token_amount: base_token_amount,
-        pc_token_amount: pc_token_amount
+        base_token_amount: base_amount_received,
+        pc_token_amount: pc_amount_received
     });
     Ok(())
 }
@@ -0,0 +1,44 @@
+use anchor_lang::prelude::*;
+
+use crate::{AMMError, ConcentratedPool, TickArray};
+
+/// Creates the tick array starting at `start_tick_index`. Anyone can pay for
+/// it.
+#[derive(Accounts)]
+#[instruction(concentrated_pool_index: u64, start_tick_index: i32)]
+pub struct InitializeTickArray<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        seeds = [b"concentrated_pool", &concentrated_pool_index.to_le_bytes()[..]],
+        bump = concentrated_pool.bump,
+    )]
+    pub concentrated_pool: Account<'info, ConcentratedPool>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + TickArray::SIZE,
+        seeds = [b"tick_array", concentrated_pool.key().as_ref(), &start_tick_index.to_le_bytes()[..]],
+        bump,
+    )]
+    pub tick_array: AccountLoader<'info, TickArray>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn _initialize_tick_array(
+    ctx: Context<InitializeTickArray>,
+    _concentrated_pool_index: u64,
+    start_tick_index: i32,
+) -> Result<()> {
+    require_eq!(
+        ctx.accounts
+            .concentrated_pool
+            .tick_array_start(start_tick_index),
+        start_tick_index,
+        AMMError::InvalidTickArray
+    );
+    let mut tick_array = ctx.accounts.tick_array.load_init()?;
+    tick_array.concentrated_pool = ctx.accounts.concentrated_pool.key();
+    tick_array.start_tick_index = start_tick_index;
+    Ok(())
+}
@@ -0,0 +1,194 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program;
+use anchor_spl::token::{self, close_account, CloseAccount, Mint, Token, TokenAccount, Transfer};
+
+use crate::{
+    AMMError, InitalizeLiquidityAccount, LegacyLiquidityAccount, MigratePoolEvent, MigratedVaults,
+    ObservationState,
+};
+
+/// Brings a pool created by the previous release up to the current pool
+/// layout, moves it from the mint-only vault PDAs to the pool-scoped ones
+/// and creates its observation buffer. Legacy pools fail to load in every
+/// other instruction until their creator has run this once.
+#[derive(Accounts)]
+#[instruction(amm_pda_index: u64)]
+pub struct MigratePool<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    /// CHECK: Pool still in the legacy layout, read by `LegacyLiquidityAccount`
+    #[account(
+        mut,
+        seeds=[b"amm_pda", &amm_pda_index.to_le_bytes()],
+        bump
+    )]
+    pub amm_pda: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = authority,
+        space = ObservationState::space(1),
+        seeds = [b"observation", amm_pda.key().as_ref()],
+        bump,
+    )]
+    pub observation_state: AccountLoader<'info, ObservationState>,
+    #[account(
+        mut,
+        seeds=[b"base_token_vault", base_token_mint.key().as_ref()],
+        bump,
+        token::mint = base_token_mint,
+        token::authority = amm_pda,
+    )]
+    pub legacy_base_token_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds=[b"pc_token_vault", pc_token_mint.key().as_ref()],
+        bump,
+        token::mint = pc_token_mint,
+        token::authority = amm_pda,
+    )]
+    pub legacy_pc_token_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        init,
+        seeds=[b"base_token_vault", base_token_mint.key().as_ref(), amm_pda.key().as_ref()],
+        bump,
+        token::mint = base_token_mint,
+        token::authority = amm_pda,
+        payer = authority
+    )]
+    pub base_token_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        init,
+        seeds=[b"pc_token_vault", pc_token_mint.key().as_ref(), amm_pda.key().as_ref()],
+        bump,
+        token::mint = pc_token_mint,
+        token::authority = amm_pda,
+        payer = authority
+    )]
+    pub pc_token_vault: Box<Account<'info, TokenAccount>>,
+    pub base_token_mint: Box<Account<'info, Mint>>,
+    pub pc_token_mint: Box<Account<'info, Mint>>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Moves the legacy vault balances into the pool-scoped vaults and closes
+/// the legacy vaults, then grows the pool account to the current size, with
+/// the creator paying the extra rent, and rewrites it in the current layout.
+/// The new observation buffer starts from the migrated pool.
+pub fn _migrate_pool(ctx: Context<MigratePool>, amm_pda_index: u64) -> Result<()> {
+    let accounts = &ctx.accounts;
+    let amm_pda = accounts.amm_pda.to_account_info();
+    let legacy_pool = LegacyLiquidityAccount::try_from_account_info(&amm_pda)?;
+    require_keys_eq!(
+        accounts.authority.key(),
+        legacy_pool.liquidity_provider,
+        AMMError::Unauthorized
+    );
+    require_keys_eq!(
+        accounts.base_token_mint.key(),
+        legacy_pool.base_token,
+        AMMError::MintMismatch
+    );
+    require_keys_eq!(
+        accounts.pc_token_mint.key(),
+        legacy_pool.pc_token,
+        AMMError::MintMismatch
+    );
+    let token_program = accounts.token_program.to_account_info();
+    let base_token_amount = accounts.legacy_base_token_vault.amount;
+    let pc_token_amount = accounts.legacy_pc_token_vault.amount;
+
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        b"amm_pda",
+        &amm_pda_index.to_le_bytes()[..],
+        &[legacy_pool.bump],
+    ]];
+
+    // Move everything held by the legacy vaults into the pool-scoped vaults
+    let cpi_context = CpiContext::new_with_signer(
+        token_program.clone(),
+        Transfer {
+            from: accounts.legacy_base_token_vault.to_account_info(),
+            to: accounts.base_token_vault.to_account_info(),
+            authority: amm_pda.clone(),
+        },
+        signer_seeds,
+    );
+    token::transfer(cpi_context, base_token_amount)?;
+
+    let cpi_context = CpiContext::new_with_signer(
+        token_program.clone(),
+        Transfer {
+            from: accounts.legacy_pc_token_vault.to_account_info(),
+            to: accounts.pc_token_vault.to_account_info(),
+            authority: amm_pda.clone(),
+        },
+        signer_seeds,
+    );
+    token::transfer(cpi_context, pc_token_amount)?;
+
+    // Close the emptied legacy vaults and refund their rent to the pool creator
+    let cpi_context = CpiContext::new_with_signer(
+        token_program.clone(),
+        CloseAccount {
+            account: accounts.legacy_base_token_vault.to_account_info(),
+            destination: accounts.authority.to_account_info(),
+            authority: amm_pda.clone(),
+        },
+        signer_seeds,
+    );
+    close_account(cpi_context)?;
+
+    let cpi_context = CpiContext::new_with_signer(
+        token_program,
+        CloseAccount {
+            account: accounts.legacy_pc_token_vault.to_account_info(),
+            destination: accounts.authority.to_account_info(),
+            authority: amm_pda.clone(),
+        },
+        signer_seeds,
+    );
+    close_account(cpi_context)?;
+
+    let pool = legacy_pool.migrate(
+        &MigratedVaults {
+            base_token_vault_bump: ctx.bumps.base_token_vault,
+            pc_token_vault_bump: ctx.bumps.pc_token_vault,
+            base_token_amount,
+            pc_token_amount,
+        },
+        Clock::get()?.unix_timestamp,
+    );
+
+    let space = 8 + InitalizeLiquidityAccount::MAX_SIZE;
+    let rent = Rent::get()?
+        .minimum_balance(space)
+        .saturating_sub(amm_pda.lamports());
+    if rent > 0 {
+        let cpi_context = CpiContext::new(
+            accounts.system_program.to_account_info(),
+            system_program::Transfer {
+                from: accounts.authority.to_account_info(),
+                to: amm_pda.clone(),
+            },
+        );
+        system_program::transfer(cpi_context, rent)?;
+    }
+    amm_pda.resize(space)?;
+    pool.try_serialize(&mut &mut amm_pda.try_borrow_mut_data()?[..])?;
+    ObservationState::create(
+        &accounts.observation_state,
+        amm_pda.key(),
+        &pool,
+        ctx.bumps.observation_state,
+    )?;
+
+    emit!(MigratePoolEvent {
+        amm_pda: amm_pda.key(),
+        base_token_vault: accounts.base_token_vault.key(),
+        pc_token_vault: accounts.pc_token_vault.key(),
+        base_token_amount,
+        pc_token_amount,
+    });
+    Ok(())
+}
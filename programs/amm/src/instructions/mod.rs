@@ -1,8 +1,69 @@
+pub mod close_pool;
+pub mod close_position;
+pub mod collect_concentrated_protocol_fees;
+pub mod collect_protocol_fees;
 pub mod deposit;
+pub mod deposit_single_side;
+pub mod fee_tier;
+pub mod flash_loan;
+pub mod grow_observations;
+pub mod initialize_amm_config;
+pub mod initialize_concentrated_pool;
 pub mod initialize_liquidity;
+pub mod initialize_tick_array;
+pub mod migrate_pool;
+pub mod modify_liquidity;
+pub mod observe;
+pub mod observe_at;
+pub mod open_position;
+pub mod ramp_amp;
+pub mod set_concentrated_pool_status;
+pub mod set_dynamic_fee;
+pub mod set_flash_loan_fee;
+pub mod set_global_pause;
+pub mod set_locked_liquidity;
+pub mod set_pool_fees;
+pub mod set_pool_status;
+pub mod set_treasury;
 pub mod swap_base_in;
+pub mod swap_base_out;
+pub mod swap_concentrated;
+pub mod swap_route;
+pub mod update_open_time;
 pub mod withdraw;
+pub mod withdraw_single_side;
+
+pub use close_pool::*;
+pub use close_position::*;
+pub use collect_concentrated_protocol_fees::*;
+pub use collect_protocol_fees::*;
 pub use deposit::*;
+pub use deposit_single_side::*;
+pub use fee_tier::*;
+pub use flash_loan::*;
+pub use grow_observations::*;
+pub use initialize_amm_config::*;
+pub use initialize_concentrated_pool::*;
 pub use initialize_liquidity::*;
+pub use initialize_tick_array::*;
+pub use migrate_pool::*;
+pub use modify_liquidity::*;
+pub use observe::*;
+pub use observe_at::*;
+pub use open_position::*;
+pub use ramp_amp::*;
+pub use set_concentrated_pool_status::*;
+pub use set_dynamic_fee::*;
+pub use set_flash_loan_fee::*;
+pub use set_global_pause::*;
+pub use set_locked_liquidity::*;
+pub use set_pool_fees::*;
+pub use set_pool_status::*;
+pub use set_treasury::*;
 pub use swap_base_in::*;
+pub use swap_base_out::*;
+pub use swap_concentrated::*;
+pub use swap_route::*;
+pub use update_open_time::*;
 pub use withdraw::*;
+pub use withdraw_single_side::*;
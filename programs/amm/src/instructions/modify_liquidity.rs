@@ -0,0 +1,271 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{
+    AMMError, AmmConfig, ConcentratedPool, ModifyPositionEvent, PoolStatus, Position,
+    ProcessTokenInstructions, SlippageValidator, TickArray,
+};
+
+#[derive(Accounts)]
+#[instruction(concentrated_pool_index: u64)]
+pub struct ModifyLiquidity<'info> {
+    pub owner: Signer<'info>,
+    #[account(
+        seeds = [b"amm_config"],
+        bump = amm_config.bump,
+    )]
+    pub amm_config: Account<'info, AmmConfig>,
+    #[account(
+        mut,
+        seeds = [b"concentrated_pool", &concentrated_pool_index.to_le_bytes()[..]],
+        bump = concentrated_pool.bump,
+    )]
+    pub concentrated_pool: Account<'info, ConcentratedPool>,
+    #[account(
+        mut,
+        seeds = [
+            b"position",
+            concentrated_pool.key().as_ref(),
+            owner.key().as_ref(),
+            &position.tick_lower.to_le_bytes()[..],
+            &position.tick_upper.to_le_bytes()[..],
+        ],
+        bump = position.bump,
+    )]
+    pub position: Account<'info, Position>,
+    /// Tick array holding the position's lower tick
+    #[account(
+        mut,
+        constraint = tick_array_lower.load()?.concentrated_pool == concentrated_pool.key() @ AMMError::InvalidTickArray,
+    )]
+    pub tick_array_lower: AccountLoader<'info, TickArray>,
+    /// Tick array holding the position's upper tick, which can be the same
+    /// account as `tick_array_lower`
+    #[account(
+        mut,
+        constraint = tick_array_upper.load()?.concentrated_pool == concentrated_pool.key() @ AMMError::InvalidTickArray,
+    )]
+    pub tick_array_upper: AccountLoader<'info, TickArray>,
+    #[account(
+        mut,
+        seeds=[b"base_token_vault", base_token_mint.key().as_ref(), concentrated_pool.key().as_ref()],
+        bump=concentrated_pool.base_token_vault_bump,
+        token::mint = base_token_mint,
+        token::authority = concentrated_pool,
+        token::token_program = base_token_program,
+    )]
+    pub base_token_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds=[b"pc_token_vault", pc_token_mint.key().as_ref(), concentrated_pool.key().as_ref()],
+        bump=concentrated_pool.pc_token_vault_bump,
+        token::mint = pc_token_mint,
+        token::authority = concentrated_pool,
+        token::token_program = pc_token_program,
+    )]
+    pub pc_token_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut, token::mint = base_token_mint)]
+    pub owner_base_token_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut, token::mint = pc_token_mint)]
+    pub owner_pc_token_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mint::token_program = base_token_program)]
+    pub base_token_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = pc_token_program)]
+    pub pc_token_mint: Box<InterfaceAccount<'info, Mint>>,
+    pub base_token_program: Interface<'info, TokenInterface>,
+    pub pc_token_program: Interface<'info, TokenInterface>,
+}
+
+/// Adds `liquidity` to a position, taking the tokens it needs at the current
+/// price from the owner. `max_base_token_amount` and `max_pc_token_amount`
+/// bound what the owner sends, transfer fees included.
+pub fn _increase_liquidity(
+    ctx: Context<ModifyLiquidity>,
+    _concentrated_pool_index: u64,
+    liquidity: u128,
+    max_base_token_amount: u64,
+    max_pc_token_amount: u64,
+) -> Result<()> {
+    require!(liquidity > 0, AMMError::InvalidAmount);
+    let liquidity_delta = i128::try_from(liquidity).map_err(|_| AMMError::MathOverflow)?;
+    let accounts = &mut *ctx.accounts;
+    PoolStatus::require_deposit_enabled(
+        accounts.concentrated_pool.status | accounts.amm_config.status,
+    )?;
+    let position = &accounts.position;
+    let (base_token_amount, pc_token_amount) = accounts.concentrated_pool.amounts_for_liquidity(
+        position.tick_lower,
+        position.tick_upper,
+        liquidity,
+        true,
+    )?;
+    // The vaults have to receive the full amounts, so the owner covers any
+    // transfer fee on top
+    let deduct_base_amount = base_token_amount
+        .checked_add(ProcessTokenInstructions::transfer_inverse_fee(
+            &accounts.base_token_mint,
+            base_token_amount,
+        )?)
+        .ok_or(AMMError::MathOverflow)?;
+    let deduct_pc_amount = pc_token_amount
+        .checked_add(ProcessTokenInstructions::transfer_inverse_fee(
+            &accounts.pc_token_mint,
+            pc_token_amount,
+        )?)
+        .ok_or(AMMError::MathOverflow)?;
+    require_gte!(
+        max_base_token_amount,
+        deduct_base_amount,
+        AMMError::DepositSlippageExceeded
+    );
+    require_gte!(
+        max_pc_token_amount,
+        deduct_pc_amount,
+        AMMError::DepositSlippageExceeded
+    );
+
+    accounts.concentrated_pool.modify_position(
+        &mut accounts.position,
+        &accounts.tick_array_lower,
+        &accounts.tick_array_upper,
+        liquidity_delta,
+    )?;
+
+    let owner = accounts.owner.to_account_info();
+    if deduct_base_amount > 0 {
+        ProcessTokenInstructions::transfer(
+            &accounts.base_token_program,
+            &accounts.base_token_mint,
+            accounts.owner_base_token_ata.to_account_info(),
+            accounts.base_token_vault.to_account_info(),
+            owner.clone(),
+            &[],
+            deduct_base_amount,
+        )?;
+    }
+    if deduct_pc_amount > 0 {
+        ProcessTokenInstructions::transfer(
+            &accounts.pc_token_program,
+            &accounts.pc_token_mint,
+            accounts.owner_pc_token_ata.to_account_info(),
+            accounts.pc_token_vault.to_account_info(),
+            owner.clone(),
+            &[],
+            deduct_pc_amount,
+        )?;
+    }
+    emit!(ModifyPositionEvent {
+        position: accounts.position.key(),
+        owner: owner.key(),
+        liquidity_delta,
+        base_token_amount: deduct_base_amount,
+        pc_token_amount: deduct_pc_amount,
+        base_token_fees: 0,
+        pc_token_fees: 0,
+    });
+    Ok(())
+}
+
+/// Removes `liquidity` from a position and pays it out together with all the
+/// fees the position has earned. Passing zero liquidity only collects fees.
+/// The minimums apply to what the owner receives.
+pub fn _decrease_liquidity(
+    ctx: Context<ModifyLiquidity>,
+    concentrated_pool_index: u64,
+    liquidity: u128,
+    min_base_token_amount: u64,
+    min_pc_token_amount: u64,
+) -> Result<()> {
+    let accounts = &mut *ctx.accounts;
+    PoolStatus::require_withdraw_enabled(
+        accounts.concentrated_pool.status | accounts.amm_config.status,
+    )?;
+    require!(
+        liquidity <= accounts.position.liquidity,
+        AMMError::InvalidAmount
+    );
+    // A position without liquidity has no fees left to settle
+    require!(
+        liquidity > 0 || accounts.position.liquidity > 0,
+        AMMError::InvalidAmount
+    );
+    let liquidity_delta = i128::try_from(liquidity)
+        .map_err(|_| AMMError::MathOverflow)?
+        .checked_neg()
+        .ok_or(AMMError::MathOverflow)?;
+    let position = &accounts.position;
+    let (base_token_amount, pc_token_amount) = accounts.concentrated_pool.amounts_for_liquidity(
+        position.tick_lower,
+        position.tick_upper,
+        liquidity,
+        false,
+    )?;
+
+    accounts.concentrated_pool.modify_position(
+        &mut accounts.position,
+        &accounts.tick_array_lower,
+        &accounts.tick_array_upper,
+        liquidity_delta,
+    )?;
+    let position = &mut accounts.position;
+    let base_token_fees = position.fees_owed_base_token;
+    let pc_token_fees = position.fees_owed_pc_token;
+    position.fees_owed_base_token = 0;
+    position.fees_owed_pc_token = 0;
+
+    let base_token_payout = base_token_amount
+        .checked_add(base_token_fees)
+        .ok_or(AMMError::MathOverflow)?;
+    let pc_token_payout = pc_token_amount
+        .checked_add(pc_token_fees)
+        .ok_or(AMMError::MathOverflow)?;
+    SlippageValidator::validate_withdraw(
+        base_token_payout
+            - ProcessTokenInstructions::transfer_fee(&accounts.base_token_mint, base_token_payout)?,
+        min_base_token_amount,
+    )?;
+    SlippageValidator::validate_withdraw(
+        pc_token_payout
+            - ProcessTokenInstructions::transfer_fee(&accounts.pc_token_mint, pc_token_payout)?,
+        min_pc_token_amount,
+    )?;
+
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        b"concentrated_pool",
+        &concentrated_pool_index.to_le_bytes()[..],
+        &[accounts.concentrated_pool.bump],
+    ]];
+    let authority = accounts.concentrated_pool.to_account_info();
+    if base_token_payout > 0 {
+        ProcessTokenInstructions::transfer(
+            &accounts.base_token_program,
+            &accounts.base_token_mint,
+            accounts.base_token_vault.to_account_info(),
+            accounts.owner_base_token_ata.to_account_info(),
+            authority.clone(),
+            signer_seeds,
+            base_token_payout,
+        )?;
+    }
+    if pc_token_payout > 0 {
+        ProcessTokenInstructions::transfer(
+            &accounts.pc_token_program,
+            &accounts.pc_token_mint,
+            accounts.pc_token_vault.to_account_info(),
+            accounts.owner_pc_token_ata.to_account_info(),
+            authority,
+            signer_seeds,
+            pc_token_payout,
+        )?;
+    }
+    emit!(ModifyPositionEvent {
+        position: accounts.position.key(),
+        owner: accounts.owner.key(),
+        liquidity_delta,
+        base_token_amount: base_token_payout,
+        pc_token_amount: pc_token_payout,
+        base_token_fees,
+        pc_token_fees,
+    });
+    Ok(())
+}
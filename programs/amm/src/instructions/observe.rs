@@ -0,0 +1,34 @@
+use anchor_lang::prelude::*;
+
+use crate::{AMMError, InitalizeLiquidityAccount, ObservationState, Twap};
+
+#[derive(Accounts)]
+#[instruction(amm_pda_index: u64)]
+pub struct Observe<'info> {
+    #[account(
+        seeds=[b"amm_pda", &amm_pda_index.to_le_bytes()],
+        bump = amm_pda.bump
+    )]
+    amm_pda: Account<'info, InitalizeLiquidityAccount>,
+    #[account(
+        seeds = [b"observation", amm_pda.key().as_ref()],
+        bump = observation_state.load()?.bump,
+    )]
+    pub observation_state: AccountLoader<'info, ObservationState>,
+}
+
+/// Returns the pool's TWAPs over the last `seconds_ago` seconds. The start
+/// of the window is interpolated from the observation buffer, so it has to
+/// be within the window the buffer still covers.
+pub fn _observe(ctx: Context<Observe>, _amm_pda_index: u64, seconds_ago: u32) -> Result<Twap> {
+    require!(seconds_ago > 0, AMMError::InvalidTwapWindow);
+    let accounts = &ctx.accounts;
+    let timestamp = Clock::get()?.unix_timestamp;
+    let current = accounts.amm_pda.observe(timestamp)?;
+    let start = ObservationState::lookup(
+        &accounts.observation_state,
+        &accounts.amm_pda,
+        timestamp - i64::from(seconds_ago),
+    )?;
+    current.twap_since(&start)
+}
@@ -0,0 +1,33 @@
+use anchor_lang::prelude::*;
+
+use crate::{InitalizeLiquidityAccount, ObservationState, PriceCumulatives};
+
+#[derive(Accounts)]
+#[instruction(amm_pda_index: u64)]
+pub struct ObserveAt<'info> {
+    #[account(
+        seeds=[b"amm_pda", &amm_pda_index.to_le_bytes()],
+        bump = amm_pda.bump
+    )]
+    amm_pda: Account<'info, InitalizeLiquidityAccount>,
+    #[account(
+        seeds = [b"observation", amm_pda.key().as_ref()],
+        bump = observation_state.load()?.bump,
+    )]
+    pub observation_state: AccountLoader<'info, ObservationState>,
+}
+
+/// Returns the pool's cumulative values at a past `timestamp`, interpolated
+/// from the observation buffer. The timestamp has to be within the window
+/// the buffer still covers.
+pub fn _observe_at(
+    ctx: Context<ObserveAt>,
+    _amm_pda_index: u64,
+    timestamp: i64,
+) -> Result<PriceCumulatives> {
+    ObservationState::lookup(
+        &ctx.accounts.observation_state,
+        &ctx.accounts.amm_pda,
+        timestamp,
+    )
+}
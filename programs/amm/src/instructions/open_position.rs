@@ -0,0 +1,50 @@
+use anchor_lang::prelude::*;
+
+use crate::{ConcentratedPool, Position};
+
+#[derive(Accounts)]
+#[instruction(concentrated_pool_index: u64, tick_lower: i32, tick_upper: i32)]
+pub struct OpenPosition<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        seeds = [b"concentrated_pool", &concentrated_pool_index.to_le_bytes()[..]],
+        bump = concentrated_pool.bump,
+    )]
+    pub concentrated_pool: Account<'info, ConcentratedPool>,
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + Position::MAX_SIZE,
+        seeds = [
+            b"position",
+            concentrated_pool.key().as_ref(),
+            owner.key().as_ref(),
+            &tick_lower.to_le_bytes()[..],
+            &tick_upper.to_le_bytes()[..],
+        ],
+        bump,
+    )]
+    pub position: Account<'info, Position>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Opens an empty position over `[tick_lower, tick_upper)`. Liquidity is
+/// added with `increase_liquidity`.
+pub fn _open_position(
+    ctx: Context<OpenPosition>,
+    _concentrated_pool_index: u64,
+    tick_lower: i32,
+    tick_upper: i32,
+) -> Result<()> {
+    ctx.accounts
+        .concentrated_pool
+        .validate_tick_range(tick_lower, tick_upper)?;
+    let position = &mut ctx.accounts.position;
+    position.concentrated_pool = ctx.accounts.concentrated_pool.key();
+    position.owner = ctx.accounts.owner.key();
+    position.tick_lower = tick_lower;
+    position.tick_upper = tick_upper;
+    position.bump = ctx.bumps.position;
+    Ok(())
+}
@@ -0,0 +1,45 @@
+use anchor_lang::prelude::*;
+
+use crate::{AMMError, AmmConfig, CurveType, InitalizeLiquidityAccount, RampAmpEvent};
+
+#[derive(Accounts)]
+#[instruction(amm_pda_index: u64)]
+pub struct RampAmp<'info> {
+    pub admin: Signer<'info>,
+    #[account(
+        seeds = [b"amm_config"],
+        bump = amm_config.bump,
+        has_one = admin @ AMMError::Unauthorized,
+    )]
+    pub amm_config: Account<'info, AmmConfig>,
+    #[account(
+        mut,
+        seeds=[b"amm_pda", &amm_pda_index.to_le_bytes()],
+        bump = amm_pda.bump,
+        constraint = amm_pda.curve_type == CurveType::StableSwap @ AMMError::InvalidCurveType,
+    )]
+    amm_pda: Account<'info, InitalizeLiquidityAccount>,
+}
+
+pub fn _ramp_amp(
+    ctx: Context<RampAmp>,
+    _amm_pda_index: u64,
+    target_amp: u64,
+    ramp_stop_timestamp: i64,
+) -> Result<()> {
+    let amm_pda = &mut ctx.accounts.amm_pda;
+    amm_pda.amp.ramp(
+        target_amp,
+        Clock::get()?.unix_timestamp,
+        ramp_stop_timestamp,
+    )?;
+    emit!(RampAmpEvent::new(amm_pda.key(), &amm_pda.amp));
+    Ok(())
+}
+
+pub fn _stop_ramp_amp(ctx: Context<RampAmp>, _amm_pda_index: u64) -> Result<()> {
+    let amm_pda = &mut ctx.accounts.amm_pda;
+    amm_pda.amp.stop(Clock::get()?.unix_timestamp);
+    emit!(RampAmpEvent::new(amm_pda.key(), &amm_pda.amp));
+    Ok(())
+}
@@ -0,0 +1,38 @@
+use anchor_lang::prelude::*;
+
+use crate::{AMMError, AmmConfig, ConcentratedPool, PoolStatus, SetPoolStatusEvent};
+
+#[derive(Accounts)]
+#[instruction(concentrated_pool_index: u64)]
+pub struct SetConcentratedPoolStatus<'info> {
+    pub admin: Signer<'info>,
+    #[account(
+        seeds = [b"amm_config"],
+        bump = amm_config.bump,
+        has_one = admin @ AMMError::Unauthorized,
+    )]
+    pub amm_config: Account<'info, AmmConfig>,
+    #[account(
+        mut,
+        seeds = [b"concentrated_pool", &concentrated_pool_index.to_le_bytes()[..]],
+        bump = concentrated_pool.bump,
+    )]
+    pub concentrated_pool: Account<'info, ConcentratedPool>,
+}
+
+/// Replaces the concentrated pool's `PoolStatus` bits
+pub fn _set_concentrated_pool_status(
+    ctx: Context<SetConcentratedPoolStatus>,
+    _concentrated_pool_index: u64,
+    status: u8,
+) -> Result<()> {
+    PoolStatus::validate(status)?;
+    let concentrated_pool = &mut ctx.accounts.concentrated_pool;
+    concentrated_pool.status = status;
+
+    emit!(SetPoolStatusEvent {
+        amm_pda: concentrated_pool.key(),
+        status,
+    });
+    Ok(())
+}
@@ -0,0 +1,42 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    AMMError, AmmConfig, DynamicFee, DynamicFeeParams, InitalizeLiquidityAccount,
+    SetDynamicFeeEvent,
+};
+
+#[derive(Accounts)]
+#[instruction(amm_pda_index: u64)]
+pub struct SetDynamicFee<'info> {
+    pub admin: Signer<'info>,
+    #[account(
+        seeds = [b"amm_config"],
+        bump = amm_config.bump,
+        has_one = admin @ AMMError::Unauthorized,
+    )]
+    pub amm_config: Account<'info, AmmConfig>,
+    #[account(
+        mut,
+        seeds=[b"amm_pda", &amm_pda_index.to_le_bytes()],
+        bump = amm_pda.bump
+    )]
+    amm_pda: Account<'info, InitalizeLiquidityAccount>,
+}
+
+/// Turns the pool's variable fee on with `params`, resetting its volatility,
+/// or off for `None`
+pub fn _set_dynamic_fee(
+    ctx: Context<SetDynamicFee>,
+    _amm_pda_index: u64,
+    params: Option<DynamicFeeParams>,
+) -> Result<()> {
+    let amm_pda = &mut ctx.accounts.amm_pda;
+    amm_pda.dynamic_fee = DynamicFee::new(params)?;
+
+    emit!(SetDynamicFeeEvent {
+        amm_pda: amm_pda.key(),
+        enabled: amm_pda.dynamic_fee.enabled,
+        params: amm_pda.dynamic_fee.params,
+    });
+    Ok(())
+}
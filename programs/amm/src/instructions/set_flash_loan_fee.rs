@@ -0,0 +1,30 @@
+use anchor_lang::prelude::*;
+
+use crate::{AMMError, AmmConfig, SetFlashLoanFeeEvent};
+
+#[derive(Accounts)]
+pub struct SetFlashLoanFee<'info> {
+    pub admin: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"amm_config"],
+        bump = amm_config.bump,
+        has_one = admin @ AMMError::Unauthorized,
+    )]
+    pub amm_config: Account<'info, AmmConfig>,
+}
+
+pub fn _set_flash_loan_fee(
+    ctx: Context<SetFlashLoanFee>,
+    flash_loan_fee_numerator: u64,
+    flash_loan_fee_denominator: u64,
+) -> Result<()> {
+    ctx.accounts
+        .amm_config
+        .set_flash_loan_fee(flash_loan_fee_numerator, flash_loan_fee_denominator)?;
+    emit!(SetFlashLoanFeeEvent {
+        flash_loan_fee_numerator,
+        flash_loan_fee_denominator,
+    });
+    Ok(())
+}
@@ -0,0 +1,24 @@
+use anchor_lang::prelude::*;
+
+use crate::{AMMError, AmmConfig, PoolStatus, SetGlobalPauseEvent};
+
+#[derive(Accounts)]
+pub struct SetGlobalPause<'info> {
+    pub admin: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"amm_config"],
+        bump = amm_config.bump,
+        has_one = admin @ AMMError::Unauthorized,
+    )]
+    pub amm_config: Account<'info, AmmConfig>,
+}
+
+/// Replaces the `PoolStatus` bits applied to every pool at once. Pools keep
+/// their own status, so clearing the global bits restores it.
+pub fn _set_global_pause(ctx: Context<SetGlobalPause>, status: u8) -> Result<()> {
+    PoolStatus::validate(status)?;
+    ctx.accounts.amm_config.status = status;
+    emit!(SetGlobalPauseEvent { status });
+    Ok(())
+}
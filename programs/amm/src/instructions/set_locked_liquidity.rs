@@ -0,0 +1,28 @@
+use anchor_lang::prelude::*;
+
+use crate::{AMMError, AmmConfig, SetLockedLiquidityEvent};
+
+#[derive(Accounts)]
+pub struct SetLockedLiquidity<'info> {
+    pub admin: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"amm_config"],
+        bump = amm_config.bump,
+        has_one = admin @ AMMError::Unauthorized,
+    )]
+    pub amm_config: Account<'info, AmmConfig>,
+}
+
+/// Sets the LP tokens locked in pools created from now on. Existing pools
+/// keep the amount they were created with.
+pub fn _set_locked_liquidity(
+    ctx: Context<SetLockedLiquidity>,
+    locked_liquidity: u64,
+) -> Result<()> {
+    ctx.accounts
+        .amm_config
+        .set_locked_liquidity(locked_liquidity)?;
+    emit!(SetLockedLiquidityEvent { locked_liquidity });
+    Ok(())
+}
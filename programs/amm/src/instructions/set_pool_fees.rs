@@ -0,0 +1,41 @@
+use anchor_lang::prelude::*;
+
+use crate::{AMMError, AmmConfig, InitalizeLiquidityAccount, SetPoolFeesEvent};
+
+#[derive(Accounts)]
+#[instruction(amm_pda_index: u64)]
+pub struct SetPoolFees<'info> {
+    pub admin: Signer<'info>,
+    #[account(
+        seeds = [b"amm_config"],
+        bump = amm_config.bump,
+        has_one = admin @ AMMError::Unauthorized,
+    )]
+    pub amm_config: Account<'info, AmmConfig>,
+    #[account(
+        mut,
+        seeds=[b"amm_pda", &amm_pda_index.to_le_bytes()],
+        bump = amm_pda.bump
+    )]
+    amm_pda: Account<'info, InitalizeLiquidityAccount>,
+}
+
+pub fn _set_pool_fees(
+    ctx: Context<SetPoolFees>,
+    _amm_pda_index: u64,
+    fee_tier_index: u16,
+) -> Result<()> {
+    let fees = ctx.accounts.amm_config.fee_tier(fee_tier_index)?;
+    let amm_pda = &mut ctx.accounts.amm_pda;
+    amm_pda.set_fees(fee_tier_index, fees.clone())?;
+
+    emit!(SetPoolFeesEvent {
+        amm_pda: amm_pda.key(),
+        fee_tier_index,
+        swap_fee_numerator: fees.swap_fee_numerator,
+        swap_fee_denominator: fees.swap_fee_denominator,
+        protocol_fee_numerator: fees.protocol_fee_numerator,
+        protocol_fee_denominator: fees.protocol_fee_denominator,
+    });
+    Ok(())
+}
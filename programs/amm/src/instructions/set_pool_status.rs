@@ -0,0 +1,38 @@
+use anchor_lang::prelude::*;
+
+use crate::{AMMError, AmmConfig, InitalizeLiquidityAccount, PoolStatus, SetPoolStatusEvent};
+
+#[derive(Accounts)]
+#[instruction(amm_pda_index: u64)]
+pub struct SetPoolStatus<'info> {
+    pub admin: Signer<'info>,
+    #[account(
+        seeds = [b"amm_config"],
+        bump = amm_config.bump,
+        has_one = admin @ AMMError::Unauthorized,
+    )]
+    pub amm_config: Account<'info, AmmConfig>,
+    #[account(
+        mut,
+        seeds=[b"amm_pda", &amm_pda_index.to_le_bytes()],
+        bump = amm_pda.bump
+    )]
+    amm_pda: Account<'info, InitalizeLiquidityAccount>,
+}
+
+/// Replaces the pool's `PoolStatus` bits, e.g. `PoolStatus::WITHDRAW_ONLY`
+pub fn _set_pool_status(
+    ctx: Context<SetPoolStatus>,
+    _amm_pda_index: u64,
+    status: u8,
+) -> Result<()> {
+    PoolStatus::validate(status)?;
+    let amm_pda = &mut ctx.accounts.amm_pda;
+    amm_pda.status = status;
+
+    emit!(SetPoolStatusEvent {
+        amm_pda: amm_pda.key(),
+        status,
+    });
+    Ok(())
+}
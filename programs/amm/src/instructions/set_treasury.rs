@@ -0,0 +1,21 @@
+use anchor_lang::prelude::*;
+
+use crate::{AMMError, AmmConfig, SetTreasuryEvent};
+
+#[derive(Accounts)]
+pub struct SetTreasury<'info> {
+    pub admin: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"amm_config"],
+        bump = amm_config.bump,
+        has_one = admin @ AMMError::Unauthorized,
+    )]
+    pub amm_config: Account<'info, AmmConfig>,
+}
+
+pub fn _set_treasury(ctx: Context<SetTreasury>, treasury: Pubkey) -> Result<()> {
+    ctx.accounts.amm_config.treasury = treasury;
+    emit!(SetTreasuryEvent { treasury });
+    Ok(())
+}
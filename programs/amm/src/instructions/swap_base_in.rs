@@ -1,18 +1,20 @@
-use anchor_lang::{
-    prelude::*,
-    solana_program::program_pack::{Pack, Sealed},
-};
-use anchor_spl::token::{self, spl_token, Mint, Token, TokenAccount, Transfer};
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
 
 use crate::{
-    program::Amm, AMMCalculator, AMMError, Converter, InitalizeLiquidityAccount,
-    ProcessTokenInstructions, SwapDirection, SwapEvent,
+    AMMError, AmmConfig, InitalizeLiquidityAccount, ObservationState, PoolStatus,
+    ProcessTokenInstructions, SlippageValidator, SwapDirection, SwapEvent,
 };
 
 #[derive(Accounts)]
 #[instruction(amm_pda_index: u64)]
 pub struct SwapBaseIn<'info> {
     pub user: Signer<'info>,
+    #[account(
+        seeds = [b"amm_config"],
+        bump = amm_config.bump,
+    )]
+    pub amm_config: Account<'info, AmmConfig>,
     #[account(
         mut,
         seeds=[b"amm_pda", &amm_pda_index.to_le_bytes()],
@@ -21,27 +23,38 @@ pub struct SwapBaseIn<'info> {
     amm_pda: Account<'info, InitalizeLiquidityAccount>,
     #[account(
         mut,
-        seeds=[b"base_token_vault", base_token_mint.key().as_ref()],
+        seeds = [b"observation", amm_pda.key().as_ref()],
+        bump = observation_state.load()?.bump,
+    )]
+    pub observation_state: AccountLoader<'info, ObservationState>,
+    #[account(
+        mut,
+        seeds=[b"base_token_vault", base_token_mint.key().as_ref(), amm_pda.key().as_ref()],
         bump=amm_pda.base_token_vault_bump,
         token::mint = base_token_mint,
         token::authority = amm_pda,
+        token::token_program = base_token_program,
     )]
-    pub base_token_vault: Account<'info, TokenAccount>,
+    pub base_token_vault: Box<InterfaceAccount<'info, TokenAccount>>,
     #[account(
         mut,
-        seeds=[b"pc_token_vault", pc_token_mint.key().as_ref()],
+        seeds=[b"pc_token_vault", pc_token_mint.key().as_ref(), amm_pda.key().as_ref()],
         bump=amm_pda.pc_token_vault_bump,
         token::mint = pc_token_mint,
         token::authority = amm_pda,
+        token::token_program = pc_token_program,
     )]
-    pub pc_token_vault: Account<'info, TokenAccount>,
+    pub pc_token_vault: Box<InterfaceAccount<'info, TokenAccount>>,
     #[account(mut)]
-    pub user_source_ata: Account<'info, TokenAccount>,
+    pub user_source_ata: Box<InterfaceAccount<'info, TokenAccount>>,
     #[account(mut)]
-    pub user_destination_ata: Account<'info, TokenAccount>,
-    pub base_token_mint: Account<'info, Mint>,
-    pub pc_token_mint: Account<'info, Mint>,
-    pub token_program: Program<'info, Token>,
+    pub user_destination_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mint::token_program = base_token_program)]
+    pub base_token_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = pc_token_program)]
+    pub pc_token_mint: Box<InterfaceAccount<'info, Mint>>,
+    pub base_token_program: Interface<'info, TokenInterface>,
+    pub pc_token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
@@ -51,21 +64,19 @@ pub fn _swap_base_in(
     amount_in: u64,
     min_amount_out: u64,
 ) -> Result<()> {
-    // TODO: Figure out how to implement slippage then use min_amount_out to validate
     require!(amount_in > 0, AMMError::InvalidAmount);
     require!(min_amount_out > 0, AMMError::InvalidAmount);
-    let accounts = ctx.accounts;
+    let accounts = &ctx.accounts;
     let amm_pda = &accounts.amm_pda;
+    amm_pda.flash_loan.require_inactive()?;
+    amm_pda.require_open()?;
+    PoolStatus::require_swap_enabled(amm_pda.status | accounts.amm_config.status)?;
     let base_token_vault = &accounts.base_token_vault;
     let pc_token_vault = &accounts.pc_token_vault;
     let user_source_account_info = accounts.user_source_ata.to_account_info();
     let user_destination_account_info = accounts.user_destination_ata.to_account_info();
-    let user_source =
-        ProcessTokenInstructions::unpack_token_accounts(&user_source_account_info, &spl_token::ID)?;
-    let user_destination = ProcessTokenInstructions::unpack_token_accounts(
-        &user_destination_account_info,
-        &spl_token::ID,
-    )?;
+    let user_source = &accounts.user_source_ata;
+    let user_destination = &accounts.user_destination_ata;
 
     let swap_direction;
     if user_source.mint == base_token_vault.mint && user_destination.mint == pc_token_vault.mint {
@@ -78,75 +89,81 @@ pub fn _swap_base_in(
         return Err(AMMError::InvalidUserToken.into());
     }
 
-    let amount_in_u128 = Converter::to_u128(amount_in)?;
-    let swap_fee_numerator_u128 = Converter::to_u128(amm_pda.fees.swap_fee_numerator)?;
-    let swap_fee_denominator_u128 = Converter::to_u128(amm_pda.fees.swap_fee_denominator)?;
-    let multiplied_numerator = amount_in_u128
-        .checked_mul(swap_fee_numerator_u128)
-        .ok_or(AMMError::MathOverflow)?;
-    let swap_fee_u128 = multiplied_numerator
-        .checked_div(swap_fee_denominator_u128)
-        .ok_or(AMMError::MathOverflow)?;
-    let swap_fee = Converter::to_u64(swap_fee_u128)?;
-    let swap_in_after_deduct_fee = amount_in - swap_fee;
+    let base_side = (
+        base_token_vault,
+        &accounts.base_token_mint,
+        &accounts.base_token_program,
+    );
+    let pc_side = (
+        pc_token_vault,
+        &accounts.pc_token_mint,
+        &accounts.pc_token_program,
+    );
+    let (
+        (source_token_vault, source_token_mint, source_token_program),
+        (destination_token_vault, destination_token_mint, destination_token_program),
+    ) = match swap_direction {
+        SwapDirection::Coin2Pc => (base_side, pc_side),
+        SwapDirection::Pc2Coin => (pc_side, base_side),
+    };
 
-    let swap_amount_out = Converter::to_u64(AMMCalculator::swap_token_base_amount_in(
-        swap_in_after_deduct_fee.into(),
-        amm_pda.base_token_amount.into(),
-        amm_pda.pc_token_amount.into(),
-        swap_direction,
-    ))?;
+    // Transfer-fee mints withhold part of each transfer, so the curve only
+    // sees what reaches the vault and the user is held to what they receive
+    let amount_in_received =
+        amount_in - ProcessTokenInstructions::transfer_fee(source_token_mint, amount_in)?;
+    let fees = amm_pda.swap_fees()?;
+    let swap_fee = fees.swap_fee(amount_in_received)?;
+    let protocol_fee = fees.protocol_fee(swap_fee)?;
+    let swap_in_after_deduct_fee = amount_in_received - swap_fee;
 
-    let token_program = accounts.token_program.to_account_info();
-    let user_account_info = accounts.user.to_account_info();
-    let base_token_vault_account_info;
-    let pc_token_vault_account_info;
+    let swap_amount_out = amm_pda.swap_amount_out(swap_in_after_deduct_fee, swap_direction)?;
+    let swap_amount_out_received = swap_amount_out
+        - ProcessTokenInstructions::transfer_fee(destination_token_mint, swap_amount_out)?;
+    SlippageValidator::validate_min_amount_out(swap_amount_out_received, min_amount_out)?;
 
-    match swap_direction {
-        SwapDirection::Coin2Pc => {
-            base_token_vault_account_info = base_token_vault.to_account_info();
-            pc_token_vault_account_info = pc_token_vault.to_account_info();
-        }
-        SwapDirection::Pc2Coin => {
-            base_token_vault_account_info = pc_token_vault.to_account_info();
-            pc_token_vault_account_info = base_token_vault.to_account_info();
-        }
-    }
-    // Transfer source token to base token vault
-    let cpi_context = CpiContext::new(
-        token_program.clone(),
-        Transfer {
-            from: user_source_account_info.clone(),
-            to: base_token_vault_account_info,
-            authority: user_account_info.clone(),
-        },
-    );
-    token::transfer(cpi_context, amount_in)?;
+    let user_account_info = accounts.user.to_account_info();
+    // Transfer source token to source token vault
+    ProcessTokenInstructions::transfer(
+        source_token_program,
+        source_token_mint,
+        user_source_account_info,
+        source_token_vault.to_account_info(),
+        user_account_info,
+        &[],
+        amount_in,
+    )?;
 
     let signer_seeds: &[&[&[u8]]] = &[&[
         b"amm_pda",
         &amm_pda_index.to_le_bytes()[..],
         &[amm_pda.bump],
     ]];
-    // Transfer destination token from pc token vault to user
-    let cpi_context = CpiContext::new_with_signer(
-        token_program,
-        Transfer {
-            from: pc_token_vault_account_info,
-            to: user_destination_account_info,
-            authority: amm_pda.to_account_info(),
-        },
+    // Transfer destination token from destination token vault to user
+    ProcessTokenInstructions::transfer(
+        destination_token_program,
+        destination_token_mint,
+        destination_token_vault.to_account_info(),
+        user_destination_account_info,
+        amm_pda.to_account_info(),
         signer_seeds,
-    );
-    token::transfer(cpi_context, swap_amount_out)?;
+        swap_amount_out,
+    )?;
 
     // Emit event for swap
     emit!(SwapEvent {
-        amount_in: amount_in,
+        amount_in: amount_in_received,
         direction: swap_direction as u8,
         user_source: user_source.mint,
         user_destination: user_destination.mint,
-        swap_amount_out: swap_amount_out
+        swap_amount_out: swap_amount_out_received,
+        swap_fee,
     });
+    ObservationState::record(&ctx.accounts.observation_state, &ctx.accounts.amm_pda)?;
+    ctx.accounts.amm_pda.apply_swap(
+        amount_in_received,
+        swap_amount_out,
+        protocol_fee,
+        swap_direction,
+    )?;
     Ok(())
 }
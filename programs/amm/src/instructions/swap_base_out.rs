@@ -0,0 +1,177 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{
+    AMMError, AmmConfig, InitalizeLiquidityAccount, ObservationState, PoolStatus,
+    ProcessTokenInstructions, SlippageValidator, SwapBaseOutEvent, SwapDirection,
+};
+
+#[derive(Accounts)]
+#[instruction(amm_pda_index: u64)]
+pub struct SwapBaseOut<'info> {
+    pub user: Signer<'info>,
+    #[account(
+        seeds = [b"amm_config"],
+        bump = amm_config.bump,
+    )]
+    pub amm_config: Account<'info, AmmConfig>,
+    #[account(
+        mut,
+        seeds=[b"amm_pda", &amm_pda_index.to_le_bytes()],
+        bump = amm_pda.bump
+    )]
+    amm_pda: Account<'info, InitalizeLiquidityAccount>,
+    #[account(
+        mut,
+        seeds = [b"observation", amm_pda.key().as_ref()],
+        bump = observation_state.load()?.bump,
+    )]
+    pub observation_state: AccountLoader<'info, ObservationState>,
+    #[account(
+        mut,
+        seeds=[b"base_token_vault", base_token_mint.key().as_ref(), amm_pda.key().as_ref()],
+        bump=amm_pda.base_token_vault_bump,
+        token::mint = base_token_mint,
+        token::authority = amm_pda,
+        token::token_program = base_token_program,
+    )]
+    pub base_token_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds=[b"pc_token_vault", pc_token_mint.key().as_ref(), amm_pda.key().as_ref()],
+        bump=amm_pda.pc_token_vault_bump,
+        token::mint = pc_token_mint,
+        token::authority = amm_pda,
+        token::token_program = pc_token_program,
+    )]
+    pub pc_token_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut)]
+    pub user_source_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut)]
+    pub user_destination_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mint::token_program = base_token_program)]
+    pub base_token_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = pc_token_program)]
+    pub pc_token_mint: Box<InterfaceAccount<'info, Mint>>,
+    pub base_token_program: Interface<'info, TokenInterface>,
+    pub pc_token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn _swap_base_out(
+    ctx: Context<SwapBaseOut>,
+    amm_pda_index: u64,
+    amount_out: u64,
+    max_amount_in: u64,
+) -> Result<()> {
+    require!(amount_out > 0, AMMError::InvalidAmount);
+    require!(max_amount_in > 0, AMMError::InvalidAmount);
+    let accounts = &ctx.accounts;
+    let amm_pda = &accounts.amm_pda;
+    amm_pda.flash_loan.require_inactive()?;
+    amm_pda.require_open()?;
+    PoolStatus::require_swap_enabled(amm_pda.status | accounts.amm_config.status)?;
+    let base_token_vault = &accounts.base_token_vault;
+    let pc_token_vault = &accounts.pc_token_vault;
+    let user_source_account_info = accounts.user_source_ata.to_account_info();
+    let user_destination_account_info = accounts.user_destination_ata.to_account_info();
+    let user_source = &accounts.user_source_ata;
+    let user_destination = &accounts.user_destination_ata;
+
+    let swap_direction;
+    if user_source.mint == base_token_vault.mint && user_destination.mint == pc_token_vault.mint {
+        swap_direction = SwapDirection::Coin2Pc;
+    } else if user_source.mint == pc_token_vault.mint
+        && user_destination.mint == base_token_vault.mint
+    {
+        swap_direction = SwapDirection::Pc2Coin;
+    } else {
+        return Err(AMMError::InvalidUserToken.into());
+    }
+
+    let base_side = (
+        base_token_vault,
+        &accounts.base_token_mint,
+        &accounts.base_token_program,
+    );
+    let pc_side = (
+        pc_token_vault,
+        &accounts.pc_token_mint,
+        &accounts.pc_token_program,
+    );
+    let (
+        (source_token_vault, source_token_mint, source_token_program),
+        (destination_token_vault, destination_token_mint, destination_token_program),
+    ) = match swap_direction {
+        SwapDirection::Coin2Pc => (base_side, pc_side),
+        SwapDirection::Pc2Coin => (pc_side, base_side),
+    };
+
+    // The vault has to send enough for amount_out to survive the destination
+    // mint's transfer fee
+    let amount_out_sent = amount_out
+        .checked_add(ProcessTokenInstructions::transfer_inverse_fee(
+            destination_token_mint,
+            amount_out,
+        )?)
+        .ok_or(AMMError::MathOverflow)?;
+    // Input the vault has to receive, swap fee included, to send amount_out_sent
+    let fees = amm_pda.swap_fees()?;
+    let amount_in_received = amm_pda.swap_amount_in(amount_out_sent, swap_direction, &fees)?;
+    // Input the user has to send for amount_in_received to survive the source
+    // mint's transfer fee
+    let amount_in = amount_in_received
+        .checked_add(ProcessTokenInstructions::transfer_inverse_fee(
+            source_token_mint,
+            amount_in_received,
+        )?)
+        .ok_or(AMMError::MathOverflow)?;
+    SlippageValidator::validate_max_amount_in(amount_in, max_amount_in)?;
+    let swap_fee = fees.swap_fee(amount_in_received)?;
+    let protocol_fee = fees.protocol_fee(swap_fee)?;
+
+    let user_account_info = accounts.user.to_account_info();
+    // Transfer source token to source token vault
+    ProcessTokenInstructions::transfer(
+        source_token_program,
+        source_token_mint,
+        user_source_account_info,
+        source_token_vault.to_account_info(),
+        user_account_info,
+        &[],
+        amount_in,
+    )?;
+
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        b"amm_pda",
+        &amm_pda_index.to_le_bytes()[..],
+        &[amm_pda.bump],
+    ]];
+    // Transfer destination token from destination token vault to user
+    ProcessTokenInstructions::transfer(
+        destination_token_program,
+        destination_token_mint,
+        destination_token_vault.to_account_info(),
+        user_destination_account_info,
+        amm_pda.to_account_info(),
+        signer_seeds,
+        amount_out_sent,
+    )?;
+
+    emit!(SwapBaseOutEvent {
+        amount_in: amount_in_received,
+        direction: swap_direction as u8,
+        user_source: user_source.mint,
+        user_destination: user_destination.mint,
+        amount_out,
+        swap_fee,
+    });
+    ObservationState::record(&ctx.accounts.observation_state, &ctx.accounts.amm_pda)?;
+    ctx.accounts.amm_pda.apply_swap(
+        amount_in_received,
+        amount_out_sent,
+        protocol_fee,
+        swap_direction,
+    )?;
+    Ok(())
+}
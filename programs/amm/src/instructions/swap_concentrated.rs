@@ -0,0 +1,167 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{
+    AMMError, AmmConfig, ConcentratedPool, PoolStatus, ProcessTokenInstructions, SlippageValidator,
+    SwapDirection, SwapEvent, TickArray,
+};
+
+/// Exact-input swap against a concentrated pool. The tick arrays the price
+/// moves through are passed writable in `remaining_accounts`, starting with
+/// the one holding the current tick. A swap that reaches its price limit
+/// fills partially and only takes the input it used.
+#[derive(Accounts)]
+#[instruction(concentrated_pool_index: u64)]
+pub struct SwapConcentrated<'info> {
+    pub user: Signer<'info>,
+    #[account(
+        seeds = [b"amm_config"],
+        bump = amm_config.bump,
+    )]
+    pub amm_config: Account<'info, AmmConfig>,
+    #[account(
+        mut,
+        seeds = [b"concentrated_pool", &concentrated_pool_index.to_le_bytes()[..]],
+        bump = concentrated_pool.bump,
+    )]
+    pub concentrated_pool: Account<'info, ConcentratedPool>,
+    #[account(
+        mut,
+        seeds=[b"base_token_vault", base_token_mint.key().as_ref(), concentrated_pool.key().as_ref()],
+        bump=concentrated_pool.base_token_vault_bump,
+        token::mint = base_token_mint,
+        token::authority = concentrated_pool,
+        token::token_program = base_token_program,
+    )]
+    pub base_token_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds=[b"pc_token_vault", pc_token_mint.key().as_ref(), concentrated_pool.key().as_ref()],
+        bump=concentrated_pool.pc_token_vault_bump,
+        token::mint = pc_token_mint,
+        token::authority = concentrated_pool,
+        token::token_program = pc_token_program,
+    )]
+    pub pc_token_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut)]
+    pub user_source_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut)]
+    pub user_destination_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mint::token_program = base_token_program)]
+    pub base_token_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = pc_token_program)]
+    pub pc_token_mint: Box<InterfaceAccount<'info, Mint>>,
+    pub base_token_program: Interface<'info, TokenInterface>,
+    pub pc_token_program: Interface<'info, TokenInterface>,
+}
+
+pub fn _swap_concentrated<'info>(
+    ctx: Context<'_, '_, 'info, 'info, SwapConcentrated<'info>>,
+    concentrated_pool_index: u64,
+    amount_in: u64,
+    min_amount_out: u64,
+    sqrt_price_limit_q64: Option<u128>,
+) -> Result<()> {
+    require!(amount_in > 0, AMMError::InvalidAmount);
+    require!(min_amount_out > 0, AMMError::InvalidAmount);
+    PoolStatus::require_swap_enabled(
+        ctx.accounts.concentrated_pool.status | ctx.accounts.amm_config.status,
+    )?;
+    let tick_arrays = ctx
+        .remaining_accounts
+        .iter()
+        .map(AccountLoader::<TickArray>::try_from)
+        .collect::<Result<Vec<_>>>()?;
+
+    let accounts = &mut *ctx.accounts;
+    let base_token_vault = &accounts.base_token_vault;
+    let pc_token_vault = &accounts.pc_token_vault;
+    let source_mint = accounts.user_source_ata.mint;
+    let destination_mint = accounts.user_destination_ata.mint;
+    let swap_direction =
+        if source_mint == base_token_vault.mint && destination_mint == pc_token_vault.mint {
+            SwapDirection::Coin2Pc
+        } else if source_mint == pc_token_vault.mint && destination_mint == base_token_vault.mint {
+            SwapDirection::Pc2Coin
+        } else {
+            return Err(AMMError::InvalidUserToken.into());
+        };
+
+    let base_side = (
+        base_token_vault,
+        &accounts.base_token_mint,
+        &accounts.base_token_program,
+    );
+    let pc_side = (
+        pc_token_vault,
+        &accounts.pc_token_mint,
+        &accounts.pc_token_program,
+    );
+    let (
+        (source_token_vault, source_token_mint, source_token_program),
+        (destination_token_vault, destination_token_mint, destination_token_program),
+    ) = match swap_direction {
+        SwapDirection::Coin2Pc => (base_side, pc_side),
+        SwapDirection::Pc2Coin => (pc_side, base_side),
+    };
+
+    let amount_in_received =
+        amount_in - ProcessTokenInstructions::transfer_fee(source_token_mint, amount_in)?;
+    let concentrated_pool_key = accounts.concentrated_pool.key();
+    let swap = accounts.concentrated_pool.swap(
+        concentrated_pool_key,
+        &tick_arrays,
+        amount_in_received,
+        swap_direction,
+        sqrt_price_limit_q64,
+    )?;
+    // A partial fill only charges what has to be sent for the used input to
+    // reach the vault
+    let amount_in = if swap.amount_in < amount_in_received {
+        swap.amount_in
+            + ProcessTokenInstructions::transfer_inverse_fee(source_token_mint, swap.amount_in)?
+    } else {
+        amount_in
+    };
+    let swap_amount_out = swap.amount_out;
+    let swap_amount_out_received = swap_amount_out
+        - ProcessTokenInstructions::transfer_fee(destination_token_mint, swap_amount_out)?;
+    SlippageValidator::validate_min_amount_out(swap_amount_out_received, min_amount_out)?;
+
+    // Transfer source token to source token vault
+    ProcessTokenInstructions::transfer(
+        source_token_program,
+        source_token_mint,
+        accounts.user_source_ata.to_account_info(),
+        source_token_vault.to_account_info(),
+        accounts.user.to_account_info(),
+        &[],
+        amount_in,
+    )?;
+
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        b"concentrated_pool",
+        &concentrated_pool_index.to_le_bytes()[..],
+        &[accounts.concentrated_pool.bump],
+    ]];
+    // Transfer destination token from destination token vault to user
+    ProcessTokenInstructions::transfer(
+        destination_token_program,
+        destination_token_mint,
+        destination_token_vault.to_account_info(),
+        accounts.user_destination_ata.to_account_info(),
+        accounts.concentrated_pool.to_account_info(),
+        signer_seeds,
+        swap_amount_out,
+    )?;
+
+    emit!(SwapEvent {
+        amount_in: swap.amount_in,
+        direction: swap_direction as u8,
+        user_source: source_mint,
+        user_destination: destination_mint,
+        swap_amount_out: swap_amount_out_received,
+        swap_fee: swap.fee_amount,
+    });
+    Ok(())
+}
@@ -0,0 +1,287 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{
+    AMMError, AmmConfig, InitalizeLiquidityAccount, ObservationState, PoolStatus,
+    ProcessTokenInstructions, SlippageValidator, SwapDirection, SwapEvent, SwapRouteEvent,
+};
+
+/// Accounts each hop passes through `remaining_accounts`, in order: pool,
+/// observation buffer, source vault, destination vault, source mint,
+/// destination mint, source token program, destination token program
+pub const ROUTE_HOP_ACCOUNTS: usize = 8;
+pub const MAX_ROUTE_HOPS: usize = 4;
+
+#[derive(Accounts)]
+pub struct SwapRoute<'info> {
+    pub user: Signer<'info>,
+    #[account(
+        seeds = [b"amm_config"],
+        bump = amm_config.bump,
+    )]
+    pub amm_config: Account<'info, AmmConfig>,
+    #[account(mut)]
+    pub user_source_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut)]
+    pub user_destination_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+}
+
+/// One pool of a route, checked against the pool's own PDAs since the
+/// accounts come in unchecked through `remaining_accounts`.
+struct RouteHop<'info> {
+    amm_pda_index: u64,
+    amm_pda: Account<'info, InitalizeLiquidityAccount>,
+    observation_state: AccountLoader<'info, ObservationState>,
+    swap_direction: SwapDirection,
+    source_token_vault: &'info AccountInfo<'info>,
+    destination_token_vault: &'info AccountInfo<'info>,
+    source_token_mint: Box<InterfaceAccount<'info, Mint>>,
+    destination_token_mint: Box<InterfaceAccount<'info, Mint>>,
+    source_token_program: Interface<'info, TokenInterface>,
+    destination_token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> RouteHop<'info> {
+    fn load(
+        amm_pda_index: u64,
+        accounts: &'info [AccountInfo<'info>],
+        global_status: u8,
+    ) -> Result<Self> {
+        let amm_pda = Account::<InitalizeLiquidityAccount>::try_from(&accounts[0])?;
+        let expected_amm_pda = Pubkey::create_program_address(
+            &[b"amm_pda", &amm_pda_index.to_le_bytes(), &[amm_pda.bump]],
+            &crate::ID,
+        )
+        .map_err(|_| AMMError::InvalidRoute)?;
+        require_keys_eq!(amm_pda.key(), expected_amm_pda, AMMError::InvalidRoute);
+        amm_pda.flash_loan.require_inactive()?;
+        amm_pda.require_open()?;
+        PoolStatus::require_swap_enabled(amm_pda.status | global_status)?;
+
+        let observation_state = AccountLoader::<ObservationState>::try_from(&accounts[1])?;
+        require_keys_eq!(
+            observation_state.load()?.amm_pda,
+            amm_pda.key(),
+            AMMError::InvalidRoute
+        );
+
+        let source_token_mint = Box::new(InterfaceAccount::<Mint>::try_from(&accounts[4])?);
+        let destination_token_mint = Box::new(InterfaceAccount::<Mint>::try_from(&accounts[5])?);
+        let swap_direction = if source_token_mint.key() == amm_pda.base_token
+            && destination_token_mint.key() == amm_pda.pc_token
+        {
+            SwapDirection::Coin2Pc
+        } else if source_token_mint.key() == amm_pda.pc_token
+            && destination_token_mint.key() == amm_pda.base_token
+        {
+            SwapDirection::Pc2Coin
+        } else {
+            return Err(AMMError::InvalidRoute.into());
+        };
+
+        let base_token_vault = Pubkey::create_program_address(
+            &[
+                b"base_token_vault",
+                amm_pda.base_token.as_ref(),
+                amm_pda.key().as_ref(),
+                &[amm_pda.base_token_vault_bump],
+            ],
+            &crate::ID,
+        )
+        .map_err(|_| AMMError::InvalidRoute)?;
+        let pc_token_vault = Pubkey::create_program_address(
+            &[
+                b"pc_token_vault",
+                amm_pda.pc_token.as_ref(),
+                amm_pda.key().as_ref(),
+                &[amm_pda.pc_token_vault_bump],
+            ],
+            &crate::ID,
+        )
+        .map_err(|_| AMMError::InvalidRoute)?;
+        let (expected_source_vault, expected_destination_vault) = match swap_direction {
+            SwapDirection::Coin2Pc => (base_token_vault, pc_token_vault),
+            SwapDirection::Pc2Coin => (pc_token_vault, base_token_vault),
+        };
+        require_keys_eq!(
+            accounts[2].key(),
+            expected_source_vault,
+            AMMError::InvalidRoute
+        );
+        require_keys_eq!(
+            accounts[3].key(),
+            expected_destination_vault,
+            AMMError::InvalidRoute
+        );
+
+        let source_token_program = Interface::<TokenInterface>::try_from(&accounts[6])?;
+        let destination_token_program = Interface::<TokenInterface>::try_from(&accounts[7])?;
+        require_keys_eq!(
+            *source_token_mint.to_account_info().owner,
+            source_token_program.key(),
+            AMMError::InvalidRoute
+        );
+        require_keys_eq!(
+            *destination_token_mint.to_account_info().owner,
+            destination_token_program.key(),
+            AMMError::InvalidRoute
+        );
+
+        Ok(Self {
+            amm_pda_index,
+            amm_pda,
+            observation_state,
+            swap_direction,
+            source_token_vault: &accounts[2],
+            destination_token_vault: &accounts[3],
+            source_token_mint,
+            destination_token_mint,
+            source_token_program,
+            destination_token_program,
+        })
+    }
+}
+
+pub fn _swap_route<'info>(
+    ctx: Context<'_, '_, 'info, 'info, SwapRoute<'info>>,
+    amount_in: u64,
+    min_amount_out: u64,
+    amm_pda_indexes: Vec<u64>,
+) -> Result<()> {
+    require!(amount_in > 0, AMMError::InvalidAmount);
+    require!(min_amount_out > 0, AMMError::InvalidAmount);
+    require!(
+        !amm_pda_indexes.is_empty() && amm_pda_indexes.len() <= MAX_ROUTE_HOPS,
+        AMMError::InvalidRoute
+    );
+    require!(
+        ctx.remaining_accounts.len() == amm_pda_indexes.len() * ROUTE_HOP_ACCOUNTS,
+        AMMError::InvalidRoute
+    );
+    // Pool state is written back after each hop, so a pool visited twice
+    // would be loaded stale the second time
+    for (position, amm_pda_index) in amm_pda_indexes.iter().enumerate() {
+        require!(
+            !amm_pda_indexes[..position].contains(amm_pda_index),
+            AMMError::InvalidRoute
+        );
+    }
+
+    let mut hops = Vec::with_capacity(amm_pda_indexes.len());
+    for (amm_pda_index, hop_accounts) in amm_pda_indexes
+        .iter()
+        .zip(ctx.remaining_accounts.chunks(ROUTE_HOP_ACCOUNTS))
+    {
+        hops.push(RouteHop::load(
+            *amm_pda_index,
+            hop_accounts,
+            ctx.accounts.amm_config.status,
+        )?);
+    }
+
+    let accounts = &ctx.accounts;
+    let user_source_account_info = accounts.user_source_ata.to_account_info();
+    let user_destination_account_info = accounts.user_destination_ata.to_account_info();
+    let source_mint = accounts.user_source_ata.mint;
+    let destination_mint = accounts.user_destination_ata.mint;
+    require_keys_eq!(
+        hops[0].source_token_mint.key(),
+        source_mint,
+        AMMError::InvalidUserToken
+    );
+    require_keys_eq!(
+        hops[hops.len() - 1].destination_token_mint.key(),
+        destination_mint,
+        AMMError::InvalidUserToken
+    );
+    for pair in hops.windows(2) {
+        require_keys_eq!(
+            pair[0].destination_token_mint.key(),
+            pair[1].source_token_mint.key(),
+            AMMError::InvalidRoute
+        );
+    }
+
+    // Transfer source token to the first pool's source token vault
+    ProcessTokenInstructions::transfer(
+        &hops[0].source_token_program,
+        &hops[0].source_token_mint,
+        user_source_account_info,
+        hops[0].source_token_vault.clone(),
+        accounts.user.to_account_info(),
+        &[],
+        amount_in,
+    )?;
+
+    // Each hop's output goes straight into the next pool's vault, so the
+    // next hop only sees what is left after the mint's transfer fee
+    let mut hop_amount_in =
+        amount_in - ProcessTokenInstructions::transfer_fee(&hops[0].source_token_mint, amount_in)?;
+    for position in 0..hops.len() {
+        let hop = &hops[position];
+        let amm_pda = &hop.amm_pda;
+        let fees = amm_pda.swap_fees()?;
+        let swap_fee = fees.swap_fee(hop_amount_in)?;
+        let protocol_fee = fees.protocol_fee(swap_fee)?;
+        let swap_in_after_deduct_fee = hop_amount_in - swap_fee;
+
+        let swap_amount_out =
+            amm_pda.swap_amount_out(swap_in_after_deduct_fee, hop.swap_direction)?;
+        let swap_amount_out_received = swap_amount_out
+            - ProcessTokenInstructions::transfer_fee(&hop.destination_token_mint, swap_amount_out)?;
+
+        let recipient = match hops.get(position + 1) {
+            Some(next_hop) => next_hop.source_token_vault.clone(),
+            None => user_destination_account_info.clone(),
+        };
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"amm_pda",
+            &hop.amm_pda_index.to_le_bytes()[..],
+            &[amm_pda.bump],
+        ]];
+        // Transfer destination token to the next pool or to the user
+        ProcessTokenInstructions::transfer(
+            &hop.destination_token_program,
+            &hop.destination_token_mint,
+            hop.destination_token_vault.clone(),
+            recipient,
+            amm_pda.to_account_info(),
+            signer_seeds,
+            swap_amount_out,
+        )?;
+
+        emit!(SwapEvent {
+            amount_in: hop_amount_in,
+            direction: hop.swap_direction as u8,
+            user_source: hop.source_token_mint.key(),
+            user_destination: hop.destination_token_mint.key(),
+            swap_amount_out: swap_amount_out_received,
+            swap_fee,
+        });
+
+        let hop = &mut hops[position];
+        ObservationState::record(&hop.observation_state, &hop.amm_pda)?;
+        hop.amm_pda.apply_swap(
+            hop_amount_in,
+            swap_amount_out,
+            protocol_fee,
+            hop.swap_direction,
+        )?;
+        hop.amm_pda.exit(&crate::ID)?;
+
+        hop_amount_in = swap_amount_out_received;
+    }
+
+    let amount_out = hop_amount_in;
+    SlippageValidator::validate_min_amount_out(amount_out, min_amount_out)?;
+
+    emit!(SwapRouteEvent {
+        user: accounts.user.key(),
+        source_mint,
+        destination_mint,
+        amount_in,
+        amount_out,
+        hops: hops.len() as u8,
+    });
+    Ok(())
+}
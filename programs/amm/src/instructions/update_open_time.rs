@@ -0,0 +1,32 @@
+use anchor_lang::prelude::*;
+
+use crate::{AMMError, InitalizeLiquidityAccount, UpdateOpenTimeEvent};
+
+#[derive(Accounts)]
+#[instruction(amm_pda_index: u64)]
+pub struct UpdateOpenTime<'info> {
+    /// Creator of the pool
+    pub liquidity_provider: Signer<'info>,
+    #[account(
+        mut,
+        seeds=[b"amm_pda", &amm_pda_index.to_le_bytes()],
+        bump = amm_pda.bump,
+        has_one = liquidity_provider @ AMMError::Unauthorized,
+    )]
+    amm_pda: Account<'info, InitalizeLiquidityAccount>,
+}
+
+pub fn _update_open_time(
+    ctx: Context<UpdateOpenTime>,
+    _amm_pda_index: u64,
+    open_time: i64,
+) -> Result<()> {
+    let amm_pda = &mut ctx.accounts.amm_pda;
+    amm_pda.set_open_time(open_time)?;
+
+    emit!(UpdateOpenTimeEvent {
+        amm_pda: amm_pda.key(),
+        open_time: amm_pda.open_time,
+    });
+    Ok(())
+}
@@ -1,13 +1,22 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, burn, Burn, Mint, Token, TokenAccount, Transfer};
+use anchor_spl::token::Token;
+use anchor_spl::token_interface::{burn, Burn, Mint, TokenAccount, TokenInterface};
 
-use crate::{AMMError, InitalizeLiquidityAccount, TokenShareCalculator, WithdrawEvent};
+use crate::{
+    AMMError, AmmConfig, InitalizeLiquidityAccount, ObservationState, PoolStatus,
+    ProcessTokenInstructions, SlippageValidator, TokenShareCalculator, WithdrawEvent,
+};
 
 #[derive(Accounts)]
 #[instruction(_lp_token_mint_decimal: u8, amm_pda_index: u64)]
 pub struct Withdraw<'info> {
     #[account(mut)]
     user: Signer<'info>,
+    #[account(
+        seeds = [b"amm_config"],
+        bump = amm_config.bump,
+    )]
+    pub amm_config: Account<'info, AmmConfig>,
     #[account(
         mut,
         seeds=[b"amm_pda", &amm_pda_index.to_le_bytes()],
@@ -16,20 +25,28 @@ pub struct Withdraw<'info> {
     amm_pda: Account<'info, InitalizeLiquidityAccount>,
     #[account(
         mut,
-        seeds=[b"base_token_vault", base_token_mint.key().as_ref()],
+        seeds = [b"observation", amm_pda.key().as_ref()],
+        bump = observation_state.load()?.bump,
+    )]
+    pub observation_state: AccountLoader<'info, ObservationState>,
+    #[account(
+        mut,
+        seeds=[b"base_token_vault", base_token_mint.key().as_ref(), amm_pda.key().as_ref()],
         bump=amm_pda.base_token_vault_bump,
         token::mint = base_token_mint,
         token::authority = amm_pda,
+        token::token_program = base_token_program,
     )]
-    pub base_token_vault: Account<'info, TokenAccount>,
+    pub base_token_vault: Box<InterfaceAccount<'info, TokenAccount>>,
     #[account(
         mut,
-        seeds=[b"pc_token_vault", pc_token_mint.key().as_ref()],
+        seeds=[b"pc_token_vault", pc_token_mint.key().as_ref(), amm_pda.key().as_ref()],
         bump=amm_pda.pc_token_vault_bump,
         token::mint = pc_token_mint,
         token::authority = amm_pda,
+        token::token_program = pc_token_program,
     )]
-    pub pc_token_vault: Account<'info, TokenAccount>,
+    pub pc_token_vault: Box<InterfaceAccount<'info, TokenAccount>>,
     #[account(
         mut,
         mint::decimals = _lp_token_mint_decimal,
@@ -38,23 +55,25 @@ pub struct Withdraw<'info> {
         seeds=[b"lp_mint", base_token_mint.key().as_ref(), pc_token_mint.key().as_ref(), amm_pda.key().as_ref()],
         bump=amm_pda.lp_token_mint_bump,
     )]
-    pub lp_token_mint: Account<'info, Mint>,
+    pub lp_token_mint: Box<InterfaceAccount<'info, Mint>>,
     #[account(
         mut,
-        seeds=[b"lp_token_ata", user.key().as_ref(), amm_pda.key().as_ref()],
-        bump,
-        token::mint = lp_token_mint,
-        token::authority = user,
-
+        associated_token::mint = lp_token_mint,
+        associated_token::authority = user,
     )]
-    pub liquidity_provider_lp_token_ata: Account<'info, TokenAccount>,
+    pub liquidity_provider_lp_token_ata: Box<InterfaceAccount<'info, TokenAccount>>,
     #[account(mut)]
-    pub liquidity_provider_base_token_ata: Account<'info, TokenAccount>,
+    pub liquidity_provider_base_token_ata: Box<InterfaceAccount<'info, TokenAccount>>,
     #[account(mut)]
-    pub liquidity_provider_pc_token_ata: Account<'info, TokenAccount>,
-    pub base_token_mint: Account<'info, Mint>,
-    pub pc_token_mint: Account<'info, Mint>,
+    pub liquidity_provider_pc_token_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mint::token_program = base_token_program)]
+    pub base_token_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = pc_token_program)]
+    pub pc_token_mint: Box<InterfaceAccount<'info, Mint>>,
+    /// Program of the LP mint, which is always created with SPL Token
     pub token_program: Program<'info, Token>,
+    pub base_token_program: Interface<'info, TokenInterface>,
+    pub pc_token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
@@ -63,10 +82,14 @@ pub fn _withdraw(
     _lp_token_mint_decimal: u8,
     amm_pda_index: u64,
     max_lp_token_amount: u64,
+    min_base_token_amount: u64,
+    min_pc_token_amount: u64,
 ) -> Result<()> {
     let accounts = &ctx.accounts;
     let user = &accounts.user;
     let amm_pda = &accounts.amm_pda;
+    amm_pda.flash_loan.require_inactive()?;
+    PoolStatus::require_withdraw_enabled(amm_pda.status | accounts.amm_config.status)?;
     let liquidity_provider_lp_token_ata = &accounts.liquidity_provider_lp_token_ata;
     let lp_token_mint = &accounts.lp_token_mint;
     let base_token_vault = &accounts.base_token_vault;
@@ -78,7 +101,7 @@ pub fn _withdraw(
         accounts.liquidity_provider_pc_token_ata.to_account_info();
 
     require!(
-        max_lp_token_amount < liquidity_provider_lp_token_ata.amount,
+        max_lp_token_amount <= liquidity_provider_lp_token_ata.amount,
         AMMError::InsufficientFund
     );
 
@@ -87,22 +110,32 @@ pub fn _withdraw(
         AMMError::NotEnoughTokenSupply
     );
 
+    // Shares are proportional on every curve type, since scaling both
+    // reserves by the same factor scales `sqrt(x * y)`, StableSwap's `D` and
+    // the weighted invariant `x^w_x * y^w_y` alike
     let token_share_calculator = TokenShareCalculator {
         lp_token_input: max_lp_token_amount,
         lp_total_token: lp_token_mint.supply,
     };
 
-    let base_token_share = token_share_calculator.exchange_pool_to_token(base_token_vault.amount);
-    let pc_token_share = token_share_calculator.exchange_pool_to_token(pc_token_vault.amount);
+    let base_token_share = token_share_calculator.exchange_pool_to_token(amm_pda.base_token_amount);
+    let pc_token_share = token_share_calculator.exchange_pool_to_token(amm_pda.pc_token_amount);
 
     require!(
-        base_token_share < base_token_vault.amount,
+        base_token_share < amm_pda.base_token_amount,
         AMMError::InsufficientPoolFund
     );
     require!(
-        pc_token_share < pc_token_vault.amount,
+        pc_token_share < amm_pda.pc_token_amount,
         AMMError::InsufficientPoolFund
     );
+    // Slippage bounds apply to what the user receives after any transfer fee
+    let base_token_received = base_token_share
+        - ProcessTokenInstructions::transfer_fee(&accounts.base_token_mint, base_token_share)?;
+    let pc_token_received = pc_token_share
+        - ProcessTokenInstructions::transfer_fee(&accounts.pc_token_mint, pc_token_share)?;
+    SlippageValidator::validate_withdraw(base_token_received, min_base_token_amount)?;
+    SlippageValidator::validate_withdraw(pc_token_received, min_pc_token_amount)?;
 
     // Burn lp tokens belonging to the user
     let cpi_accounts = Burn {
@@ -120,38 +153,40 @@ pub fn _withdraw(
         &[amm_pda.bump],
     ]];
 
-    let cpi_ctx = CpiContext::new_with_signer(token_program.clone(), cpi_accounts, signer_seeds);
+    let cpi_ctx = CpiContext::new_with_signer(token_program, cpi_accounts, signer_seeds);
     burn(cpi_ctx, max_lp_token_amount)?;
 
     // Transfer base tokens share to user
-    let cpi_context = CpiContext::new_with_signer(
-        token_program.clone(),
-        Transfer {
-            from: base_token_vault.to_account_info(),
-            to: liquidity_provider_base_token_ata_account_info,
-            authority: amm_pda.to_account_info(),
-        },
+    ProcessTokenInstructions::transfer(
+        &accounts.base_token_program,
+        &accounts.base_token_mint,
+        base_token_vault.to_account_info(),
+        liquidity_provider_base_token_ata_account_info,
+        amm_pda.to_account_info(),
         signer_seeds,
-    );
-    token::transfer(cpi_context, base_token_share)?;
+        base_token_share,
+    )?;
 
     // Transfer pc tokens share to user
-    let cpi_context = CpiContext::new_with_signer(
-        token_program,
-        Transfer {
-            from: pc_token_vault.to_account_info(),
-            to: liquidity_provider_pc_token_ata_account_info,
-            authority: amm_pda.to_account_info(),
-        },
+    ProcessTokenInstructions::transfer(
+        &accounts.pc_token_program,
+        &accounts.pc_token_mint,
+        pc_token_vault.to_account_info(),
+        liquidity_provider_pc_token_ata_account_info,
+        amm_pda.to_account_info(),
         signer_seeds,
-    );
-    token::transfer(cpi_context, pc_token_share)?;
+        pc_token_share,
+    )?;
 
     emit!(WithdrawEvent {
         user: user.key(),
         lp_amount: max_lp_token_amount,
-        base_token_amount: base_token_share,
-        pc_token_amount: pc_token_share
+        base_token_amount: base_token_received,
+        pc_token_amount: pc_token_received
     });
+    ObservationState::record(&ctx.accounts.observation_state, &ctx.accounts.amm_pda)?;
+    ctx.accounts
+        .amm_pda
+        .remove_liquidity(base_token_share, pc_token_share)?;
     Ok(())
 }
@@ -0,0 +1,210 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::Token;
+use anchor_spl::token_interface::{burn, Burn, Mint, TokenAccount, TokenInterface};
+
+use crate::{
+    AMMError, AmmConfig, InitalizeLiquidityAccount, ObservationState, PoolStatus,
+    ProcessTokenInstructions, SlippageValidator, SwapDirection, TokenShareCalculator,
+    WithdrawSingleSideEvent,
+};
+
+#[derive(Accounts)]
+#[instruction(amm_pda_index: u64)]
+pub struct WithdrawSingleSide<'info> {
+    #[account(mut)]
+    user: Signer<'info>,
+    #[account(
+        seeds = [b"amm_config"],
+        bump = amm_config.bump,
+    )]
+    pub amm_config: Account<'info, AmmConfig>,
+    #[account(
+        mut,
+        seeds=[b"amm_pda", &amm_pda_index.to_le_bytes()],
+        bump = amm_pda.bump
+    )]
+    amm_pda: Account<'info, InitalizeLiquidityAccount>,
+    #[account(
+        mut,
+        seeds = [b"observation", amm_pda.key().as_ref()],
+        bump = observation_state.load()?.bump,
+    )]
+    pub observation_state: AccountLoader<'info, ObservationState>,
+    #[account(
+        mut,
+        seeds=[b"base_token_vault", base_token_mint.key().as_ref(), amm_pda.key().as_ref()],
+        bump=amm_pda.base_token_vault_bump,
+        token::mint = base_token_mint,
+        token::authority = amm_pda,
+        token::token_program = base_token_program,
+    )]
+    pub base_token_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds=[b"pc_token_vault", pc_token_mint.key().as_ref(), amm_pda.key().as_ref()],
+        bump=amm_pda.pc_token_vault_bump,
+        token::mint = pc_token_mint,
+        token::authority = amm_pda,
+        token::token_program = pc_token_program,
+    )]
+    pub pc_token_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        mint::authority= amm_pda,
+        seeds=[b"lp_mint", base_token_mint.key().as_ref(), pc_token_mint.key().as_ref(), amm_pda.key().as_ref()],
+        bump=amm_pda.lp_token_mint_bump,
+    )]
+    pub lp_token_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        associated_token::mint = lp_token_mint,
+        associated_token::authority = user,
+    )]
+    pub liquidity_provider_lp_token_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    /// Receives the whole withdrawal in the chosen token
+    #[account(mut)]
+    pub user_destination_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mint::token_program = base_token_program)]
+    pub base_token_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = pc_token_program)]
+    pub pc_token_mint: Box<InterfaceAccount<'info, Mint>>,
+    /// Program of the LP mint, which is always created with SPL Token
+    pub token_program: Program<'info, Token>,
+    pub base_token_program: Interface<'info, TokenInterface>,
+    pub pc_token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Burns `lp_token_amount` and pays the whole share out in one token, base
+/// when `base_side` is 0 and pc otherwise. The share of the other token is
+/// swapped back into the pool along its curve, paying the usual swap fee.
+pub fn _withdraw_single_side(
+    ctx: Context<WithdrawSingleSide>,
+    amm_pda_index: u64,
+    lp_token_amount: u64,
+    base_side: u8,
+    min_amount_out: u64,
+) -> Result<()> {
+    let accounts = &ctx.accounts;
+    let amm_pda = &accounts.amm_pda;
+    amm_pda.flash_loan.require_inactive()?;
+    PoolStatus::require_withdraw_enabled(amm_pda.status | accounts.amm_config.status)?;
+    PoolStatus::require_swap_enabled(amm_pda.status | accounts.amm_config.status)?;
+    let lp_token_mint = &accounts.lp_token_mint;
+
+    require!(
+        lp_token_amount <= accounts.liquidity_provider_lp_token_ata.amount,
+        AMMError::InsufficientFund
+    );
+    require!(
+        lp_token_amount < lp_token_mint.supply,
+        AMMError::NotEnoughTokenSupply
+    );
+
+    let token_share_calculator = TokenShareCalculator {
+        lp_token_input: lp_token_amount,
+        lp_total_token: lp_token_mint.supply,
+    };
+    let base_token_share = token_share_calculator.exchange_pool_to_token(amm_pda.base_token_amount);
+    let pc_token_share = token_share_calculator.exchange_pool_to_token(amm_pda.pc_token_amount);
+    require!(
+        base_token_share < amm_pda.base_token_amount,
+        AMMError::InsufficientPoolFund
+    );
+    require!(
+        pc_token_share < amm_pda.pc_token_amount,
+        AMMError::InsufficientPoolFund
+    );
+
+    // The swap runs from the unwanted side into the paid out one
+    let (swap_direction, swap_amount_in, share_out) = if base_side == 0 {
+        (SwapDirection::Pc2Coin, pc_token_share, base_token_share)
+    } else {
+        (SwapDirection::Coin2Pc, base_token_share, pc_token_share)
+    };
+
+    ObservationState::record(&ctx.accounts.observation_state, &ctx.accounts.amm_pda)?;
+    // The swap prices against the reserves left once the share is taken out
+    let amm_pda = &mut ctx.accounts.amm_pda;
+    amm_pda.remove_liquidity(base_token_share, pc_token_share)?;
+    let fees = amm_pda.swap_fees()?;
+    let swap_fee = fees.swap_fee(swap_amount_in)?;
+    let protocol_fee = fees.protocol_fee(swap_fee)?;
+    let swap_amount_out = amm_pda.swap_amount_out(swap_amount_in - swap_fee, swap_direction)?;
+    amm_pda.apply_swap(
+        swap_amount_in,
+        swap_amount_out,
+        protocol_fee,
+        swap_direction,
+    )?;
+
+    let accounts = &ctx.accounts;
+    let amm_pda = &accounts.amm_pda;
+    let lp_token_mint = &accounts.lp_token_mint;
+    let (destination_token_vault, destination_token_mint, destination_token_program) =
+        match swap_direction {
+            SwapDirection::Pc2Coin => (
+                &accounts.base_token_vault,
+                &accounts.base_token_mint,
+                &accounts.base_token_program,
+            ),
+            SwapDirection::Coin2Pc => (
+                &accounts.pc_token_vault,
+                &accounts.pc_token_mint,
+                &accounts.pc_token_program,
+            ),
+        };
+    require_keys_eq!(
+        accounts.user_destination_ata.mint,
+        destination_token_mint.key(),
+        AMMError::InvalidUserToken
+    );
+    let amount_out = share_out
+        .checked_add(swap_amount_out)
+        .ok_or(AMMError::MathOverflow)?;
+    // Slippage bounds apply to what the user receives after any transfer fee
+    let amount_out_received =
+        amount_out - ProcessTokenInstructions::transfer_fee(destination_token_mint, amount_out)?;
+    SlippageValidator::validate_withdraw(amount_out_received, min_amount_out)?;
+
+    // Burn lp tokens belonging to the user
+    let cpi_accounts = Burn {
+        mint: lp_token_mint.to_account_info(),
+        from: accounts.liquidity_provider_lp_token_ata.to_account_info(),
+        authority: accounts.user.to_account_info(),
+    };
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        b"amm_pda",
+        &amm_pda_index.to_le_bytes()[..],
+        &[amm_pda.bump],
+    ]];
+    let cpi_ctx = CpiContext::new_with_signer(
+        accounts.token_program.to_account_info(),
+        cpi_accounts,
+        signer_seeds,
+    );
+    burn(cpi_ctx, lp_token_amount)?;
+
+    // Transfer the share and the swap output to user
+    ProcessTokenInstructions::transfer(
+        destination_token_program,
+        destination_token_mint,
+        destination_token_vault.to_account_info(),
+        accounts.user_destination_ata.to_account_info(),
+        amm_pda.to_account_info(),
+        signer_seeds,
+        amount_out,
+    )?;
+
+    emit!(WithdrawSingleSideEvent {
+        user: accounts.user.key(),
+        lp_amount: lp_token_amount,
+        destination_mint: destination_token_mint.key(),
+        share_amount: share_out,
+        swap_amount_in,
+        swap_fee,
+        swap_amount_out,
+        amount_out: amount_out_received,
+    });
+    Ok(())
+}
@@ -1,6 +1,10 @@
+// `#[program]` generates the IDL instruction handlers in a `__private`
+// module beside `amm`, and they call the deprecated `AccountInfo::realloc`
+#![allow(deprecated)]
 pub mod constants;
 pub mod error;
 pub mod events;
+pub mod fees;
 pub mod instructions;
 pub mod math;
 pub mod state;
@@ -9,6 +13,7 @@ use anchor_lang::prelude::*;
 pub use constants::*;
 pub use error::*;
 pub use events::*;
+pub use fees::*;
 pub use instructions::*;
 pub use math::*;
 pub use state::*;
@@ -19,6 +24,98 @@ declare_id!("Hr9FAeTLTe8ESL831KZjMAreV21Gno4Pv8HTwHRjA8PK");
 pub mod amm {
     use super::*;
 
+    pub fn initialize_amm_config(ctx: Context<InitializeAmmConfig>) -> Result<()> {
+        _initialize_amm_config(ctx)
+    }
+
+    pub fn create_fee_tier(ctx: Context<ManageFeeTier>, fees: Fees) -> Result<()> {
+        _create_fee_tier(ctx, fees)
+    }
+
+    pub fn update_fee_tier(
+        ctx: Context<ManageFeeTier>,
+        fee_tier_index: u16,
+        fees: Fees,
+    ) -> Result<()> {
+        _update_fee_tier(ctx, fee_tier_index, fees)
+    }
+
+    pub fn set_treasury(ctx: Context<SetTreasury>, treasury: Pubkey) -> Result<()> {
+        _set_treasury(ctx, treasury)
+    }
+
+    pub fn set_flash_loan_fee(
+        ctx: Context<SetFlashLoanFee>,
+        flash_loan_fee_numerator: u64,
+        flash_loan_fee_denominator: u64,
+    ) -> Result<()> {
+        _set_flash_loan_fee(ctx, flash_loan_fee_numerator, flash_loan_fee_denominator)
+    }
+
+    pub fn set_locked_liquidity(
+        ctx: Context<SetLockedLiquidity>,
+        locked_liquidity: u64,
+    ) -> Result<()> {
+        _set_locked_liquidity(ctx, locked_liquidity)
+    }
+
+    pub fn set_dynamic_fee(
+        ctx: Context<SetDynamicFee>,
+        amm_pda_index: u64,
+        params: Option<DynamicFeeParams>,
+    ) -> Result<()> {
+        _set_dynamic_fee(ctx, amm_pda_index, params)
+    }
+
+    pub fn set_pool_status(
+        ctx: Context<SetPoolStatus>,
+        amm_pda_index: u64,
+        status: u8,
+    ) -> Result<()> {
+        _set_pool_status(ctx, amm_pda_index, status)
+    }
+
+    pub fn set_concentrated_pool_status(
+        ctx: Context<SetConcentratedPoolStatus>,
+        concentrated_pool_index: u64,
+        status: u8,
+    ) -> Result<()> {
+        _set_concentrated_pool_status(ctx, concentrated_pool_index, status)
+    }
+
+    pub fn set_global_pause(ctx: Context<SetGlobalPause>, status: u8) -> Result<()> {
+        _set_global_pause(ctx, status)
+    }
+
+    pub fn collect_protocol_fees(
+        ctx: Context<CollectProtocolFees>,
+        amm_pda_index: u64,
+    ) -> Result<()> {
+        _collect_protocol_fees(ctx, amm_pda_index)
+    }
+
+    pub fn set_pool_fees(
+        ctx: Context<SetPoolFees>,
+        amm_pda_index: u64,
+        fee_tier_index: u16,
+    ) -> Result<()> {
+        _set_pool_fees(ctx, amm_pda_index, fee_tier_index)
+    }
+
+    pub fn ramp_amp(
+        ctx: Context<RampAmp>,
+        amm_pda_index: u64,
+        target_amp: u64,
+        ramp_stop_timestamp: i64,
+    ) -> Result<()> {
+        _ramp_amp(ctx, amm_pda_index, target_amp, ramp_stop_timestamp)
+    }
+
+    pub fn stop_ramp_amp(ctx: Context<RampAmp>, amm_pda_index: u64) -> Result<()> {
+        _stop_ramp_amp(ctx, amm_pda_index)
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn initialize_liquidity(
         ctx: Context<InitializeLiquidity>,
         lp_coin_mint_decimal: u8,
@@ -27,6 +124,12 @@ pub mod amm {
         pc_coin: Pubkey,
         base_coin_amount: u64,
         pc_coin_amount: u64,
+        fee_tier_index: u16,
+        curve_type: CurveType,
+        amp: u64,
+        weights: Weights,
+        open_time: i64,
+        launch_fee: Option<LaunchFee>,
     ) -> Result<()> {
         _initialize_liquidity_pool(
             ctx,
@@ -36,9 +139,23 @@ pub mod amm {
             pc_coin,
             base_coin_amount,
             pc_coin_amount,
+            fee_tier_index,
+            curve_type,
+            amp,
+            weights,
+            open_time,
+            launch_fee,
         )
     }
 
+    pub fn update_open_time(
+        ctx: Context<UpdateOpenTime>,
+        amm_pda_index: u64,
+        open_time: i64,
+    ) -> Result<()> {
+        _update_open_time(ctx, amm_pda_index, open_time)
+    }
+
     pub fn swap_base_in(
         ctx: Context<SwapBaseIn>,
         amm_pda_index: u64,
@@ -48,6 +165,25 @@ pub mod amm {
         _swap_base_in(ctx, amm_pda_index, amount_in, min_amount_out)
     }
 
+    pub fn swap_base_out(
+        ctx: Context<SwapBaseOut>,
+        amm_pda_index: u64,
+        amount_out: u64,
+        max_amount_in: u64,
+    ) -> Result<()> {
+        _swap_base_out(ctx, amm_pda_index, amount_out, max_amount_in)
+    }
+
+    pub fn swap_route<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SwapRoute<'info>>,
+        amount_in: u64,
+        min_amount_out: u64,
+        amm_pda_indexes: Vec<u64>,
+    ) -> Result<()> {
+        _swap_route(ctx, amount_in, min_amount_out, amm_pda_indexes)
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn deposit(
         ctx: Context<Deposit>,
         _lp_token_mint_decimal: u8,
@@ -57,6 +193,7 @@ pub mod amm {
         max_base_coin_amount: u64,
         max_pc_coin_amount: u64,
         base_side: u8,
+        min_lp_token_amount: u64,
     ) -> Result<()> {
         _deposit(
             ctx,
@@ -66,6 +203,23 @@ pub mod amm {
             max_base_coin_amount,
             max_pc_coin_amount,
             base_side,
+            min_lp_token_amount,
+        )
+    }
+
+    pub fn deposit_single_side(
+        ctx: Context<DepositSingleSide>,
+        amm_pda_index: u64,
+        amount_in: u64,
+        base_side: u8,
+        min_lp_token_amount: u64,
+    ) -> Result<()> {
+        _deposit_single_side(
+            ctx,
+            amm_pda_index,
+            amount_in,
+            base_side,
+            min_lp_token_amount,
         )
     }
 
@@ -74,14 +228,167 @@ pub mod amm {
         _lp_token_mint_decimal: u8,
         amm_pda_index: u64,
         max_lp_token_amount: u64,
+        min_base_token_amount: u64,
+        min_pc_token_amount: u64,
     ) -> Result<()> {
         _withdraw(
             ctx,
             _lp_token_mint_decimal,
             amm_pda_index,
             max_lp_token_amount,
+            min_base_token_amount,
+            min_pc_token_amount,
+        )
+    }
+
+    pub fn withdraw_single_side(
+        ctx: Context<WithdrawSingleSide>,
+        amm_pda_index: u64,
+        lp_token_amount: u64,
+        base_side: u8,
+        min_amount_out: u64,
+    ) -> Result<()> {
+        _withdraw_single_side(
+            ctx,
+            amm_pda_index,
+            lp_token_amount,
+            base_side,
+            min_amount_out,
+        )
+    }
+
+    pub fn close_pool(ctx: Context<ClosePool>, amm_pda_index: u64) -> Result<()> {
+        _close_pool(ctx, amm_pda_index)
+    }
+
+    pub fn initialize_concentrated_pool(
+        ctx: Context<InitializeConcentratedPool>,
+        concentrated_pool_index: u64,
+        tick_spacing: u16,
+        initial_sqrt_price_q64: u128,
+        fee_tier_index: u16,
+    ) -> Result<()> {
+        _initialize_concentrated_pool(
+            ctx,
+            concentrated_pool_index,
+            tick_spacing,
+            initial_sqrt_price_q64,
+            fee_tier_index,
+        )
+    }
+
+    pub fn initialize_tick_array(
+        ctx: Context<InitializeTickArray>,
+        concentrated_pool_index: u64,
+        start_tick_index: i32,
+    ) -> Result<()> {
+        _initialize_tick_array(ctx, concentrated_pool_index, start_tick_index)
+    }
+
+    pub fn open_position(
+        ctx: Context<OpenPosition>,
+        concentrated_pool_index: u64,
+        tick_lower: i32,
+        tick_upper: i32,
+    ) -> Result<()> {
+        _open_position(ctx, concentrated_pool_index, tick_lower, tick_upper)
+    }
+
+    pub fn increase_liquidity(
+        ctx: Context<ModifyLiquidity>,
+        concentrated_pool_index: u64,
+        liquidity: u128,
+        max_base_token_amount: u64,
+        max_pc_token_amount: u64,
+    ) -> Result<()> {
+        _increase_liquidity(
+            ctx,
+            concentrated_pool_index,
+            liquidity,
+            max_base_token_amount,
+            max_pc_token_amount,
         )
     }
+
+    pub fn decrease_liquidity(
+        ctx: Context<ModifyLiquidity>,
+        concentrated_pool_index: u64,
+        liquidity: u128,
+        min_base_token_amount: u64,
+        min_pc_token_amount: u64,
+    ) -> Result<()> {
+        _decrease_liquidity(
+            ctx,
+            concentrated_pool_index,
+            liquidity,
+            min_base_token_amount,
+            min_pc_token_amount,
+        )
+    }
+
+    pub fn close_position(ctx: Context<ClosePosition>, concentrated_pool_index: u64) -> Result<()> {
+        _close_position(ctx, concentrated_pool_index)
+    }
+
+    pub fn swap_concentrated<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SwapConcentrated<'info>>,
+        concentrated_pool_index: u64,
+        amount_in: u64,
+        min_amount_out: u64,
+        sqrt_price_limit_q64: Option<u128>,
+    ) -> Result<()> {
+        _swap_concentrated(
+            ctx,
+            concentrated_pool_index,
+            amount_in,
+            min_amount_out,
+            sqrt_price_limit_q64,
+        )
+    }
+
+    pub fn collect_concentrated_protocol_fees(
+        ctx: Context<CollectConcentratedProtocolFees>,
+        concentrated_pool_index: u64,
+    ) -> Result<()> {
+        _collect_concentrated_protocol_fees(ctx, concentrated_pool_index)
+    }
+
+    pub fn flash_loan_begin(
+        ctx: Context<FlashLoanBegin>,
+        amm_pda_index: u64,
+        base_token_amount: u64,
+        pc_token_amount: u64,
+    ) -> Result<()> {
+        _flash_loan_begin(ctx, amm_pda_index, base_token_amount, pc_token_amount)
+    }
+
+    pub fn flash_loan_end(ctx: Context<FlashLoanEnd>, amm_pda_index: u64) -> Result<()> {
+        _flash_loan_end(ctx, amm_pda_index)
+    }
+
+    pub fn observe(ctx: Context<Observe>, amm_pda_index: u64, seconds_ago: u32) -> Result<Twap> {
+        _observe(ctx, amm_pda_index, seconds_ago)
+    }
+
+    pub fn observe_at(
+        ctx: Context<ObserveAt>,
+        amm_pda_index: u64,
+        timestamp: i64,
+    ) -> Result<PriceCumulatives> {
+        _observe_at(ctx, amm_pda_index, timestamp)
+    }
+
+    pub fn grow_observations(
+        ctx: Context<GrowObservations>,
+        amm_pda_index: u64,
+        capacity: u16,
+    ) -> Result<()> {
+        _grow_observations(ctx, amm_pda_index, capacity)
+    }
+
+    pub fn migrate_pool(ctx: Context<MigratePool>, amm_pda_index: u64) -> Result<()> {
+        _migrate_pool(ctx, amm_pda_index)
+    }
 }
 
 #[derive(Accounts)]
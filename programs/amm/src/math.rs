@@ -1,10 +1,19 @@
 use std::cmp::min;
 
-use crate::{error::AMMError, SwapDirection};
+use crate::{error::AMMError, Fees, SwapDirection};
+
+#[allow(clippy::assign_op_pattern, clippy::manual_div_ceil)]
+mod u256 {
+    uint::construct_uint! {
+        pub struct U256(4);
+    }
+}
+pub use u256::U256;
 
 pub struct Converter {}
 
 impl Converter {
+    #[allow(clippy::unnecessary_fallible_conversions)]
     pub fn to_u128(val: u64) -> Result<u128, AMMError> {
         val.try_into().map_err(|_| AMMError::ConversionFailedToU128)
     }
@@ -23,7 +32,6 @@ impl AMMCalculator {
         total_pc: u128,
         swap_direction: SwapDirection,
     ) -> u128 {
-        let amount_out;
         match swap_direction {
             SwapDirection::Coin2Pc => {
                 // (x + dx) * (y + dy) = x * y
@@ -33,7 +41,7 @@ impl AMMCalculator {
                 // amount_out =  pc * amount_in / (coin + amount_in)
                 let numerator = total_pc.checked_mul(amount_in).unwrap();
                 let denominator = total_coin.checked_add(amount_in).unwrap();
-                amount_out = numerator.checked_div(denominator).unwrap();
+                numerator.checked_div(denominator).unwrap()
             }
             SwapDirection::Pc2Coin => {
                 // (x + dx) * (y + dy) = x * y
@@ -43,10 +51,737 @@ impl AMMCalculator {
                 //  amount_out = coin * amount_in / (pc + amount_in)
                 let numerator = total_coin.checked_mul(amount_in).unwrap();
                 let denominator = total_pc.checked_add(amount_in).unwrap();
-                amount_out = numerator.checked_div(denominator).unwrap();
+                numerator.checked_div(denominator).unwrap()
+            }
+        }
+    }
+
+    pub fn swap_token_base_amount_out(
+        amount_out: u128,
+        total_coin: u128,
+        total_pc: u128,
+        fees: &Fees,
+        swap_direction: SwapDirection,
+    ) -> Result<u128, AMMError> {
+        let (total_in, total_out) = match swap_direction {
+            SwapDirection::Coin2Pc => (total_coin, total_pc),
+            SwapDirection::Pc2Coin => (total_pc, total_coin),
+        };
+        if amount_out >= total_out {
+            return Err(AMMError::InsufficientPoolFund);
+        }
+        // (x + dx) * (y - dy) = x * y
+        // (total_in + amount_in) * (total_out - amount_out) = total_in * total_out
+        // amount_in = total_in * amount_out / (total_out - amount_out)
+        // Rounded up so the pool never gives out more than the curve allows
+        let amount_in_after_fee = Self::checked_ceil_div(
+            total_in
+                .checked_mul(amount_out)
+                .ok_or(AMMError::MathOverflow)?,
+            total_out - amount_out,
+        )?;
+        Self::amount_in_before_fee(amount_in_after_fee, fees)
+    }
+
+    /// Smallest input that is still worth `amount_in_after_fee` once the swap
+    /// fee is taken out of it.
+    pub fn amount_in_before_fee(amount_in_after_fee: u128, fees: &Fees) -> Result<u128, AMMError> {
+        // The fee is rounded down when it is charged, so the smallest amount_in satisfying
+        // amount_in - amount_in * fee_numerator / fee_denominator >= amount_in_after_fee
+        // amount_in = (amount_in_after_fee - 1) * fee_denominator / (fee_denominator - fee_numerator) + 1
+        let fee_numerator = Converter::to_u128(fees.swap_fee_numerator)?;
+        let fee_denominator = Converter::to_u128(fees.swap_fee_denominator)?;
+        let fee_complement = fee_denominator
+            .checked_sub(fee_numerator)
+            .ok_or(AMMError::MathOverflow)?;
+        amount_in_after_fee
+            .checked_sub(1)
+            .ok_or(AMMError::MathOverflow)?
+            .checked_mul(fee_denominator)
+            .ok_or(AMMError::MathOverflow)?
+            .checked_div(fee_complement)
+            .ok_or(AMMError::MathOverflow)?
+            .checked_add(1)
+            .ok_or(AMMError::MathOverflow)
+    }
+
+    /// Part of a single-sided deposit of `amount_in` to swap first, so that
+    /// the rest matches the reserve ratio after the swap. For a swap fee of
+    /// `n / d` and input reserve `R`, solving
+    /// `(A - s) / (R + s) = (1 - n / d) * s / R` gives
+    /// s = (sqrt(R^2 * (2d - n)^2 + 4d * (d - n) * A * R) - R * (2d - n)) / (2 * (d - n))
+    /// Rounded down, and any leftover is refunded by the caller.
+    pub fn zap_swap_amount(amount_in: u128, total_in: u128, fees: &Fees) -> Result<u128, AMMError> {
+        let fee_numerator = U256::from(fees.swap_fee_numerator);
+        let fee_denominator = U256::from(fees.swap_fee_denominator);
+        let fee_complement = fee_denominator
+            .checked_sub(fee_numerator)
+            .filter(|complement| !complement.is_zero())
+            .ok_or(AMMError::MathOverflow)?;
+        let checked_mul = |a: U256, b: U256| a.checked_mul(b).ok_or(AMMError::MathOverflow);
+
+        let b = checked_mul(U256::from(total_in), fee_denominator + fee_complement)?;
+        let four_ac = checked_mul(
+            checked_mul(U256::from(4) * fee_denominator, fee_complement)?,
+            checked_mul(U256::from(amount_in), U256::from(total_in))?,
+        )?;
+        let discriminant = checked_mul(b, b)?
+            .checked_add(four_ac)
+            .ok_or(AMMError::MathOverflow)?;
+        let swap_amount = (discriminant.integer_sqrt() - b) / (fee_complement * 2);
+        // The root is rounded down, but stay within the deposit regardless
+        Ok(swap_amount.as_u128().min(amount_in))
+    }
+
+    pub fn invariant(total_coin: u64, total_pc: u64) -> Result<u128, AMMError> {
+        Converter::to_u128(total_coin)?
+            .checked_mul(Converter::to_u128(total_pc)?)
+            .ok_or(AMMError::MathOverflow)
+    }
+
+    pub fn checked_ceil_div(numerator: u128, denominator: u128) -> Result<u128, AMMError> {
+        if denominator == 0 {
+            return Err(AMMError::MathOverflow);
+        }
+        Ok(numerator.div_ceil(denominator))
+    }
+}
+
+/// Curve-style StableSwap invariant for two coins,
+/// `A * n^n * (x + y) + D = A * n^n * D + D^(n + 1) / (n^n * x * y)`,
+/// solved with Newton's method in 256-bit intermediates.
+pub struct StableSwapCalculator {}
+
+impl StableSwapCalculator {
+    const N_COINS: u64 = 2;
+    const MAX_ITERATIONS: usize = 255;
+
+    pub fn compute_d(amp: u64, total_coin: u128, total_pc: u128) -> Result<u128, AMMError> {
+        if total_coin == 0 || total_pc == 0 {
+            return Err(AMMError::InsufficientPoolFund);
+        }
+        let ann = U256::from(amp) * Self::N_COINS * Self::N_COINS;
+        let (x, y) = (U256::from(total_coin), U256::from(total_pc));
+        let sum = x + y;
+        let mut d = sum;
+        for _ in 0..Self::MAX_ITERATIONS {
+            // D^(n + 1) / (n^n * x * y), one coin at a time to keep it small
+            let d_p = d * d / (x * Self::N_COINS) * d / (y * Self::N_COINS);
+            let previous_d = d;
+            d = (ann * sum + d_p * Self::N_COINS) * d / ((ann - 1) * d + d_p * (Self::N_COINS + 1));
+            if Self::converged(d, previous_d) {
+                return Self::to_u128(d);
+            }
+        }
+        Err(AMMError::CurveDidNotConverge)
+    }
+
+    /// Balance of one coin that keeps the invariant at `d` when the other
+    /// coin's balance is `total_other`.
+    pub fn compute_y(amp: u64, total_other: u128, d: u128) -> Result<u128, AMMError> {
+        if total_other == 0 {
+            return Err(AMMError::InsufficientPoolFund);
+        }
+        let ann = U256::from(amp) * Self::N_COINS * Self::N_COINS;
+        let (x, d) = (U256::from(total_other), U256::from(d));
+        // y^2 + (x + D / Ann - D) * y = D^(n + 1) / (n^n * x * Ann)
+        let c = d * d / (x * Self::N_COINS) * d / (ann * Self::N_COINS);
+        let b = x + d / ann;
+        let mut y = d;
+        for _ in 0..Self::MAX_ITERATIONS {
+            let previous_y = y;
+            y = (y * y + c)
+                / (y * Self::N_COINS + b)
+                    .checked_sub(d)
+                    .ok_or(AMMError::MathOverflow)?;
+            if Self::converged(y, previous_y) {
+                return Self::to_u128(y);
+            }
+        }
+        Err(AMMError::CurveDidNotConverge)
+    }
+
+    pub fn swap_token_base_amount_in(
+        amp: u64,
+        amount_in: u128,
+        total_coin: u128,
+        total_pc: u128,
+        swap_direction: SwapDirection,
+    ) -> Result<u128, AMMError> {
+        let (total_in, total_out) = match swap_direction {
+            SwapDirection::Coin2Pc => (total_coin, total_pc),
+            SwapDirection::Pc2Coin => (total_pc, total_coin),
+        };
+        let d = Self::compute_d(amp, total_coin, total_pc)?;
+        let new_total_out = Self::compute_y(
+            amp,
+            total_in
+                .checked_add(amount_in)
+                .ok_or(AMMError::MathOverflow)?,
+            d,
+        )?;
+        // One unit less than the solver's answer so its rounding never
+        // favours the trader
+        Ok(total_out.saturating_sub(new_total_out).saturating_sub(1))
+    }
+
+    pub fn swap_token_base_amount_out(
+        amp: u64,
+        amount_out: u128,
+        total_coin: u128,
+        total_pc: u128,
+        fees: &Fees,
+        swap_direction: SwapDirection,
+    ) -> Result<u128, AMMError> {
+        let (total_in, total_out) = match swap_direction {
+            SwapDirection::Coin2Pc => (total_coin, total_pc),
+            SwapDirection::Pc2Coin => (total_pc, total_coin),
+        };
+        if amount_out >= total_out {
+            return Err(AMMError::InsufficientPoolFund);
+        }
+        let d = Self::compute_d(amp, total_coin, total_pc)?;
+        let new_total_in = Self::compute_y(amp, total_out - amount_out, d)?;
+        let amount_in_after_fee = new_total_in
+            .saturating_sub(total_in)
+            .checked_add(1)
+            .ok_or(AMMError::MathOverflow)?;
+        AMMCalculator::amount_in_before_fee(amount_in_after_fee, fees)
+    }
+
+    fn converged(value: U256, previous_value: U256) -> bool {
+        if value > previous_value {
+            value - previous_value <= U256::one()
+        } else {
+            previous_value - value <= U256::one()
+        }
+    }
+
+    fn to_u128(value: U256) -> Result<u128, AMMError> {
+        if value > U256::from(u128::MAX) {
+            return Err(AMMError::MathOverflow);
+        }
+        Ok(value.as_u128())
+    }
+}
+
+/// 18-decimal fixed point logarithms and powers for weighted pools.
+///
+/// Error bounds, checked against an arbitrary-precision reference in the
+/// tests:
+/// - `ln` is within `MAX_LN_ERROR` of the exact value.
+/// - `exp` is within `MAX_EXP_RELATIVE_ERROR` of the exact value, plus one
+///   unit from the final shift.
+/// - `pow` with an exponent up to `MAX_POW_EXPONENT`, on the bases that
+///   trades within `WeightedCalculator::MAX_TRADE_RATIO` produce, is within
+///   `MAX_POW_RELATIVE_ERROR` of the exact value, plus one unit.
+///
+/// `pow_up` and `pow_down` widen the result by that bound so callers can
+/// round in the pool's favour.
+pub struct FixedPoint {}
+
+impl FixedPoint {
+    pub const ONE: u128 = 1_000_000_000_000_000_000;
+    /// ln(2), rounded to the nearest unit
+    const LN_2: i128 = 693_147_180_559_945_309;
+    pub const MAX_LN_ERROR: u128 = 100;
+    pub const MAX_EXP_RELATIVE_ERROR: u128 = 1_000;
+    pub const MAX_POW_EXPONENT: u128 = 100 * Self::ONE;
+    pub const MAX_POW_RELATIVE_ERROR: u128 = 100_000;
+
+    /// Natural logarithm of `x / ONE`, scaled by `ONE`.
+    pub fn ln(x: u128) -> Result<i128, AMMError> {
+        if x == 0 {
+            return Err(AMMError::MathOverflow);
+        }
+        // x = m * 2^k with m in [ONE, 2 * ONE), so ln(x) = k * ln(2) + ln(m)
+        let (m, k) = if x >= Self::ONE {
+            let k = (x / Self::ONE).ilog2();
+            (x >> k, i128::from(k))
+        } else {
+            let k = (Self::ONE - 1) / x;
+            let k = k.ilog2() + 1;
+            (x << k, -i128::from(k))
+        };
+        let (m, k) = if m >= 2 * Self::ONE {
+            (m >> 1, k + 1)
+        } else {
+            (m, k)
+        };
+        // ln(m) = 2 * atanh(z) = 2 * (z + z^3 / 3 + z^5 / 5 + ...) with
+        // z = (m - 1) / (m + 1) in [0, 1/3)
+        let z = (m - Self::ONE) * Self::ONE / (m + Self::ONE);
+        let z_squared = z * z / Self::ONE;
+        let mut term = z;
+        let mut series = 0;
+        let mut denominator = 1;
+        while term > 0 {
+            series += term / denominator;
+            term = term * z_squared / Self::ONE;
+            denominator += 2;
+        }
+        Ok(k * Self::LN_2 + 2 * series as i128)
+    }
+
+    /// `e^(x / ONE)`, scaled by `ONE`.
+    pub fn exp(x: i128) -> Result<u128, AMMError> {
+        // x = k * ln(2) + r with |r| <= ln(2) / 2, so e^x = 2^k * e^r
+        let half_ln_2 = Self::LN_2 / 2;
+        let k = if x >= 0 {
+            (x + half_ln_2) / Self::LN_2
+        } else {
+            (x - half_ln_2) / Self::LN_2
+        };
+        let r = x - k * Self::LN_2;
+        let one = Self::ONE as i128;
+        let mut term = one;
+        let mut series = one;
+        let mut n = 1;
+        while term != 0 {
+            term = term * r / (n * one);
+            series += term;
+            n += 1;
+        }
+        let series = series as u128;
+        if k >= 0 {
+            let k = u32::try_from(k).map_err(|_| AMMError::MathOverflow)?;
+            if k >= series.leading_zeros() {
+                return Err(AMMError::MathOverflow);
+            }
+            Ok(series << k)
+        } else {
+            Ok(series
+                .checked_shr(u32::try_from(-k).unwrap_or(u32::MAX))
+                .unwrap_or(0))
+        }
+    }
+
+    /// `(base / ONE)^(exponent / ONE)`, scaled by `ONE`.
+    pub fn pow(base: u128, exponent: u128) -> Result<u128, AMMError> {
+        if exponent > Self::MAX_POW_EXPONENT {
+            return Err(AMMError::MathOverflow);
+        }
+        if exponent == 0 {
+            return Ok(Self::ONE);
+        }
+        let exponent = exponent as i128;
+        let ln_base = Self::ln(base)?;
+        let product = ln_base
+            .checked_mul(exponent)
+            .ok_or(AMMError::MathOverflow)?
+            / Self::ONE as i128;
+        Self::exp(product)
+    }
+
+    /// Upper bound of `pow`
+    pub fn pow_up(base: u128, exponent: u128) -> Result<u128, AMMError> {
+        let power = Self::pow(base, exponent)?;
+        power
+            .checked_add(Self::pow_error(power)?)
+            .ok_or(AMMError::MathOverflow)
+    }
+
+    /// Lower bound of `pow`
+    pub fn pow_down(base: u128, exponent: u128) -> Result<u128, AMMError> {
+        let power = Self::pow(base, exponent)?;
+        Ok(power.saturating_sub(Self::pow_error(power)?))
+    }
+
+    fn pow_error(power: u128) -> Result<u128, AMMError> {
+        Ok(power
+            .checked_mul(Self::MAX_POW_RELATIVE_ERROR)
+            .ok_or(AMMError::MathOverflow)?
+            / Self::ONE
+            + 1)
+    }
+}
+
+/// Balancer-style weighted product invariant for two coins,
+/// `x^w_x * y^w_y = k`.
+pub struct WeightedCalculator {}
+
+impl WeightedCalculator {
+    /// Trades are capped at this share of the reserve they touch, which keeps
+    /// the powers inside `FixedPoint`'s bounds
+    pub const MAX_TRADE_RATIO: u128 = 3 * FixedPoint::ONE / 10;
+
+    pub fn swap_token_base_amount_in(
+        amount_in: u128,
+        total_in: u128,
+        total_out: u128,
+        weight_in: u64,
+        weight_out: u64,
+    ) -> Result<u128, AMMError> {
+        Self::check_trade_size(amount_in, total_in)?;
+        // amount_out = total_out * (1 - (total_in / (total_in + amount_in))^(w_in / w_out))
+        // The base and the exponent are rounded so the power comes out
+        // larger, which rounds amount_out down
+        let base = AMMCalculator::checked_ceil_div(
+            total_in
+                .checked_mul(FixedPoint::ONE)
+                .ok_or(AMMError::MathOverflow)?,
+            total_in
+                .checked_add(amount_in)
+                .ok_or(AMMError::MathOverflow)?,
+        )?;
+        let exponent = u128::from(weight_in) * FixedPoint::ONE / u128::from(weight_out);
+        let power = FixedPoint::pow_up(base, exponent)?.min(FixedPoint::ONE);
+        total_out
+            .checked_mul(FixedPoint::ONE - power)
+            .ok_or(AMMError::MathOverflow)
+            .map(|amount| amount / FixedPoint::ONE)
+    }
+
+    pub fn swap_token_base_amount_out(
+        amount_out: u128,
+        total_in: u128,
+        total_out: u128,
+        weight_in: u64,
+        weight_out: u64,
+        fees: &Fees,
+    ) -> Result<u128, AMMError> {
+        Self::check_trade_size(amount_out, total_out)?;
+        // Solve for a little more than amount_out, so that swapping the
+        // input back through `swap_token_base_amount_in`, which rounds
+        // against the trader as well, still gives at least amount_out
+        let amount_out = amount_out
+            .checked_add(Self::amount_out_rounding_error(total_out)?)
+            .ok_or(AMMError::MathOverflow)?;
+        // amount_in = total_in * ((total_out / (total_out - amount_out))^(w_out / w_in) - 1)
+        // Everything rounds up so the pool never gives out more than the
+        // curve allows
+        let base = AMMCalculator::checked_ceil_div(
+            total_out
+                .checked_mul(FixedPoint::ONE)
+                .ok_or(AMMError::MathOverflow)?,
+            total_out - amount_out,
+        )?;
+        let exponent = AMMCalculator::checked_ceil_div(
+            u128::from(weight_out) * FixedPoint::ONE,
+            u128::from(weight_in),
+        )?;
+        let power = FixedPoint::pow_up(base, exponent)?;
+        let amount_in_after_fee = AMMCalculator::checked_ceil_div(
+            total_in
+                .checked_mul(power - FixedPoint::ONE)
+                .ok_or(AMMError::MathOverflow)?,
+            FixedPoint::ONE,
+        )?;
+        AMMCalculator::amount_in_before_fee(amount_in_after_fee, fees)
+    }
+
+    /// Weighted geometric mean of the reserves, `(x^w_x * y^w_y)^(1 / (w_x + w_y))`,
+    /// scaled by `FixedPoint::ONE`. Grows linearly with the pool.
+    pub fn invariant(
+        total_coin: u128,
+        total_pc: u128,
+        weight_coin: u64,
+        weight_pc: u64,
+    ) -> Result<u128, AMMError> {
+        let ln_coin = FixedPoint::ln(
+            total_coin
+                .checked_mul(FixedPoint::ONE)
+                .ok_or(AMMError::MathOverflow)?,
+        )?;
+        let ln_pc = FixedPoint::ln(
+            total_pc
+                .checked_mul(FixedPoint::ONE)
+                .ok_or(AMMError::MathOverflow)?,
+        )?;
+        let (weight_coin, weight_pc) = (i128::from(weight_coin), i128::from(weight_pc));
+        let weighted_ln = ln_coin
+            .checked_mul(weight_coin)
+            .and_then(|ln_coin| ln_coin.checked_add(ln_pc.checked_mul(weight_pc)?))
+            .ok_or(AMMError::MathOverflow)?
+            .checked_div(weight_coin + weight_pc)
+            .ok_or(AMMError::MathOverflow)?;
+        FixedPoint::exp(weighted_ln)
+    }
+
+    /// Largest drop in `invariant` that fixed-point error alone can cause
+    pub fn invariant_error(invariant: u128) -> u128 {
+        invariant * FixedPoint::MAX_POW_RELATIVE_ERROR / FixedPoint::ONE + 1
+    }
+
+    /// Most `swap_token_base_amount_in` can fall short of the exact curve,
+    /// from the power's error and the rounding of its base
+    fn amount_out_rounding_error(total_out: u128) -> Result<u128, AMMError> {
+        let relative_error =
+            2 * FixedPoint::MAX_POW_RELATIVE_ERROR + FixedPoint::MAX_POW_EXPONENT / FixedPoint::ONE;
+        Ok(total_out
+            .checked_mul(relative_error)
+            .ok_or(AMMError::MathOverflow)?
+            / FixedPoint::ONE
+            + 1)
+    }
+
+    fn check_trade_size(amount: u128, total: u128) -> Result<(), AMMError> {
+        let max_amount = total
+            .checked_mul(Self::MAX_TRADE_RATIO)
+            .ok_or(AMMError::MathOverflow)?
+            / FixedPoint::ONE;
+        if amount > max_amount {
+            return Err(AMMError::TradeTooLarge);
+        }
+        Ok(())
+    }
+}
+
+/// Ticks of concentrated pools. Tick `i` is the price `1.0001^i` in pc
+/// units per base unit, and prices are stored as Q64.64 square roots.
+pub struct TickMath {}
+
+impl TickMath {
+    /// Keeps square-root prices between about 1.5e-5 and 65535
+    pub const MIN_TICK: i32 = -221_818;
+    pub const MAX_TICK: i32 = 221_818;
+    /// ln(1.0001) / 2, scaled by `FixedPoint::ONE`
+    const HALF_LN_TICK_BASE: i128 = 49_997_500_166_654;
+
+    pub fn sqrt_price_at_tick(tick: i32) -> Result<u128, AMMError> {
+        if !(Self::MIN_TICK..=Self::MAX_TICK).contains(&tick) {
+            return Err(AMMError::InvalidTickRange);
+        }
+        let sqrt_price = FixedPoint::exp(i128::from(tick) * Self::HALF_LN_TICK_BASE)?;
+        Ok(((U256::from(sqrt_price) << 64) / U256::from(FixedPoint::ONE)).as_u128())
+    }
+
+    /// Greatest tick whose square-root price is at most `sqrt_price_q64`.
+    pub fn tick_at_sqrt_price(sqrt_price_q64: u128) -> Result<i32, AMMError> {
+        if sqrt_price_q64 < Self::sqrt_price_at_tick(Self::MIN_TICK)?
+            || sqrt_price_q64 > Self::sqrt_price_at_tick(Self::MAX_TICK)?
+        {
+            return Err(AMMError::InvalidSqrtPrice);
+        }
+        let sqrt_price =
+            ((U256::from(sqrt_price_q64) * U256::from(FixedPoint::ONE)) >> 64).as_u128();
+        let estimate = FixedPoint::ln(sqrt_price)? / Self::HALF_LN_TICK_BASE;
+        // The estimate can be off by one either way, so settle it against
+        // the exact tick prices
+        let mut tick = estimate.clamp(Self::MIN_TICK.into(), Self::MAX_TICK.into()) as i32;
+        while tick < Self::MAX_TICK && Self::sqrt_price_at_tick(tick + 1)? <= sqrt_price_q64 {
+            tick += 1;
+        }
+        while tick > Self::MIN_TICK && Self::sqrt_price_at_tick(tick)? > sqrt_price_q64 {
+            tick -= 1;
+        }
+        Ok(tick)
+    }
+}
+
+/// Result of swapping within a single tick range
+pub struct SwapStep {
+    pub sqrt_price_q64: u128,
+    pub amount_in: u128,
+    pub amount_out: u128,
+    pub fee_amount: u128,
+}
+
+/// Uniswap v3 style liquidity math on Q64.64 square-root prices, with the
+/// base token as token 0.
+pub struct ConcentratedLiquidityCalculator {}
+
+impl ConcentratedLiquidityCalculator {
+    /// Base tokens held by `liquidity` between two prices,
+    /// `L * (sqrt_b - sqrt_a) / (sqrt_a * sqrt_b)`
+    pub fn base_token_delta(
+        sqrt_price_a_q64: u128,
+        sqrt_price_b_q64: u128,
+        liquidity: u128,
+        round_up: bool,
+    ) -> Result<u128, AMMError> {
+        let (lower, upper) = Self::sorted(sqrt_price_a_q64, sqrt_price_b_q64);
+        if lower == 0 {
+            return Err(AMMError::InvalidSqrtPrice);
+        }
+        let numerator = (U256::from(liquidity) << 64) * U256::from(upper - lower);
+        let amount = if round_up {
+            Self::ceil_div(
+                Self::ceil_div(numerator, U256::from(upper)),
+                U256::from(lower),
+            )
+        } else {
+            numerator / U256::from(upper) / U256::from(lower)
+        };
+        Self::to_u128(amount)
+    }
+
+    /// Pc tokens held by `liquidity` between two prices,
+    /// `L * (sqrt_b - sqrt_a)`
+    pub fn pc_token_delta(
+        sqrt_price_a_q64: u128,
+        sqrt_price_b_q64: u128,
+        liquidity: u128,
+        round_up: bool,
+    ) -> Result<u128, AMMError> {
+        let (lower, upper) = Self::sorted(sqrt_price_a_q64, sqrt_price_b_q64);
+        let product = U256::from(liquidity) * U256::from(upper - lower);
+        let amount = if round_up {
+            Self::ceil_div(product, U256::one() << 64)
+        } else {
+            product >> 64
+        };
+        Self::to_u128(amount)
+    }
+
+    /// Price after `amount` base tokens come in, `L * sqrt_p / (L + amount * sqrt_p)`,
+    /// rounded up so the pool never moves further than paid for
+    pub fn next_sqrt_price_from_base_in(
+        sqrt_price_q64: u128,
+        liquidity: u128,
+        amount: u128,
+    ) -> Result<u128, AMMError> {
+        if amount == 0 {
+            return Ok(sqrt_price_q64);
+        }
+        let numerator = U256::from(liquidity) << 64;
+        let denominator = numerator + U256::from(amount) * U256::from(sqrt_price_q64);
+        Self::to_u128(Self::ceil_div(
+            numerator * U256::from(sqrt_price_q64),
+            denominator,
+        ))
+    }
+
+    /// Price after `amount` pc tokens come in, `sqrt_p + amount / L`, rounded
+    /// down
+    pub fn next_sqrt_price_from_pc_in(
+        sqrt_price_q64: u128,
+        liquidity: u128,
+        amount: u128,
+    ) -> Result<u128, AMMError> {
+        if liquidity == 0 {
+            return Err(AMMError::InsufficientPoolFund);
+        }
+        let delta = Self::to_u128((U256::from(amount) << 64) / U256::from(liquidity))?;
+        sqrt_price_q64
+            .checked_add(delta)
+            .ok_or(AMMError::MathOverflow)
+    }
+
+    /// Swaps `amount_remaining`, swap fee included, from `sqrt_price_q64`
+    /// towards `target_sqrt_price_q64` without passing it. Selling base moves
+    /// the price down, selling pc moves it up.
+    pub fn swap_step(
+        sqrt_price_q64: u128,
+        target_sqrt_price_q64: u128,
+        liquidity: u128,
+        amount_remaining: u128,
+        fees: &Fees,
+    ) -> Result<SwapStep, AMMError> {
+        let base_to_pc = target_sqrt_price_q64 <= sqrt_price_q64;
+        let fee_numerator = u128::from(fees.swap_fee_numerator);
+        let fee_denominator = u128::from(fees.swap_fee_denominator);
+        let amount_remaining_less_fee = amount_remaining
+            .checked_mul(fee_denominator - fee_numerator)
+            .ok_or(AMMError::MathOverflow)?
+            / fee_denominator;
+
+        let input_delta = |from: u128, to: u128, round_up: bool| {
+            if base_to_pc {
+                Self::base_token_delta(from, to, liquidity, round_up)
+            } else {
+                Self::pc_token_delta(from, to, liquidity, round_up)
             }
+        };
+        let max_amount_in = input_delta(sqrt_price_q64, target_sqrt_price_q64, true)?;
+        let next_sqrt_price_q64 = if amount_remaining_less_fee >= max_amount_in {
+            target_sqrt_price_q64
+        } else if base_to_pc {
+            Self::next_sqrt_price_from_base_in(
+                sqrt_price_q64,
+                liquidity,
+                amount_remaining_less_fee,
+            )?
+        } else {
+            Self::next_sqrt_price_from_pc_in(sqrt_price_q64, liquidity, amount_remaining_less_fee)?
+        };
+        let reached_target = next_sqrt_price_q64 == target_sqrt_price_q64;
+
+        let amount_in = if reached_target {
+            max_amount_in
+        } else {
+            input_delta(sqrt_price_q64, next_sqrt_price_q64, true)?
+        };
+        let amount_out = if base_to_pc {
+            Self::pc_token_delta(next_sqrt_price_q64, sqrt_price_q64, liquidity, false)?
+        } else {
+            Self::base_token_delta(sqrt_price_q64, next_sqrt_price_q64, liquidity, false)?
+        };
+        // A step that stops short of the target used up the whole input, so
+        // whatever the price move didn't need is fee
+        let fee_amount = if reached_target {
+            AMMCalculator::checked_ceil_div(
+                amount_in
+                    .checked_mul(fee_numerator)
+                    .ok_or(AMMError::MathOverflow)?,
+                fee_denominator - fee_numerator,
+            )?
+        } else {
+            amount_remaining - amount_in
+        };
+        Ok(SwapStep {
+            sqrt_price_q64: next_sqrt_price_q64,
+            amount_in,
+            amount_out,
+            fee_amount,
+        })
+    }
+
+    fn sorted(a: u128, b: u128) -> (u128, u128) {
+        if a <= b {
+            (a, b)
+        } else {
+            (b, a)
         }
-        amount_out
+    }
+
+    fn ceil_div(numerator: U256, denominator: U256) -> U256 {
+        let quotient = numerator / denominator;
+        if quotient * denominator == numerator {
+            quotient
+        } else {
+            quotient + 1
+        }
+    }
+
+    fn to_u128(value: U256) -> Result<u128, AMMError> {
+        if value > U256::from(u128::MAX) {
+            return Err(AMMError::MathOverflow);
+        }
+        Ok(value.as_u128())
+    }
+}
+
+/// Q64.64 fixed point prices for the TWAP oracle. Prices are in raw token
+/// units, so they keep full precision whatever the mints' decimals are.
+pub struct PriceCalculator {}
+
+impl PriceCalculator {
+    /// Price of one unit of the denominator token in units of the numerator
+    /// token, as Q64.64.
+    pub fn price_q64(numerator_reserve: u64, denominator_reserve: u64) -> Result<u128, AMMError> {
+        (Converter::to_u128(numerator_reserve)? << 64)
+            .checked_div(Converter::to_u128(denominator_reserve)?)
+            .ok_or(AMMError::MathOverflow)
+    }
+
+    /// Adds `price_q64 * elapsed` to a cumulative price. Like Uniswap v2 the
+    /// accumulator wraps on overflow, which `twap_q64` undoes.
+    pub fn accumulate(cumulative: u128, price_q64: u128, elapsed: u64) -> u128 {
+        cumulative.wrapping_add(price_q64.wrapping_mul(elapsed.into()))
+    }
+
+    /// Time-weighted average Q64.64 price between two cumulative prices
+    /// taken `elapsed` seconds apart.
+    pub fn twap_q64(
+        start_cumulative: u128,
+        end_cumulative: u128,
+        elapsed: u64,
+    ) -> Result<u128, AMMError> {
+        end_cumulative
+            .wrapping_sub(start_cumulative)
+            .checked_div(elapsed.into())
+            .ok_or(AMMError::MathOverflow)
     }
 }
 
@@ -127,7 +862,7 @@ pub struct TokenShareCalculator {
 
 impl TokenShareCalculator {
     pub fn exchange_pool_to_token(&self, total_pool_token: u64) -> u64 {
-        let token_share = Converter::to_u64(
+        Converter::to_u64(
             Converter::to_u128(self.lp_token_input)
                 .unwrap()
                 .checked_mul(total_pool_token.into())
@@ -135,7 +870,528 @@ impl TokenShareCalculator {
                 .checked_div(self.lp_total_token.into())
                 .unwrap(),
         )
+        .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn default_fees() -> Fees {
+        Fees {
+            swap_fee_numerator: 25,
+            swap_fee_denominator: 10000,
+            protocol_fee_numerator: 0,
+            protocol_fee_denominator: 10000,
+        }
+    }
+
+    fn amount_out_for_gross_in(amount_in: u128, total_coin: u128, total_pc: u128) -> u128 {
+        let fee = amount_in * 25 / 10000;
+        AMMCalculator::swap_token_base_amount_in(
+            amount_in - fee,
+            total_coin,
+            total_pc,
+            SwapDirection::Coin2Pc,
+        )
+    }
+
+    #[test]
+    fn swap_base_out_is_smallest_input_covering_amount_out() {
+        let (total_coin, total_pc) = (2_000_000_000u128, 1_000_000_000u128);
+        for amount_out in [1u128, 7, 90_702_432, 500_000_000, 999_999_999] {
+            let amount_in = AMMCalculator::swap_token_base_amount_out(
+                amount_out,
+                total_coin,
+                total_pc,
+                &default_fees(),
+                SwapDirection::Coin2Pc,
+            )
+            .unwrap();
+            assert!(amount_out_for_gross_in(amount_in, total_coin, total_pc) >= amount_out);
+            assert!(amount_out_for_gross_in(amount_in - 1, total_coin, total_pc) < amount_out);
+        }
+    }
+
+    #[test]
+    fn swap_base_out_rejects_draining_the_pool() {
+        let result = AMMCalculator::swap_token_base_amount_out(
+            1_000,
+            5_000,
+            1_000,
+            &default_fees(),
+            SwapDirection::Coin2Pc,
+        );
+        assert!(matches!(result, Err(AMMError::InsufficientPoolFund)));
+    }
+
+    #[test]
+    fn zap_leaves_the_rest_in_the_post_swap_ratio() {
+        let (total_coin, total_pc) = (2_000_000_000u128, 1_000_000_000u128);
+        let amount_in = 100_000_000u128;
+        let swap_amount =
+            AMMCalculator::zap_swap_amount(amount_in, total_coin, &default_fees()).unwrap();
+        // Just under half, since the swap itself moves the price
+        assert!(swap_amount > 48_000_000 && swap_amount < 50_000_000);
+        let amount_out = amount_out_for_gross_in(swap_amount, total_coin, total_pc);
+        let remaining = amount_in - swap_amount;
+        // remaining / (total_coin + swap_amount) == amount_out / (total_pc - amount_out),
+        // up to rounding by one unit of either side
+        let lhs = remaining * (total_pc - amount_out);
+        let rhs = amount_out * (total_coin + swap_amount);
+        assert!(lhs.abs_diff(rhs) <= total_coin + total_pc);
+
+        // Without a fee, the split is the plain constant product one
+        let no_fee = Fees {
+            swap_fee_numerator: 0,
+            ..default_fees()
+        };
+        let swap_amount = AMMCalculator::zap_swap_amount(amount_in, total_coin, &no_fee).unwrap();
+        // sqrt(R^2 + A * R) - R for R = 2e9, A = 1e8
+        assert_eq!(swap_amount, 49_390_153);
+        assert_eq!(
+            AMMCalculator::zap_swap_amount(0, total_coin, &default_fees()).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn stable_swap_d_is_sum_for_balanced_pools() {
+        for amp in [1, 100, 1_000_000] {
+            let d = StableSwapCalculator::compute_d(amp, 1_000_000_000, 1_000_000_000).unwrap();
+            assert_eq!(d, 2_000_000_000);
+        }
+        let max = u128::from(u64::MAX);
+        assert!(StableSwapCalculator::compute_d(1_000_000, max, max / 3).is_ok());
+        assert!(StableSwapCalculator::compute_d(100, 0, 1).is_err());
+    }
+
+    #[test]
+    fn stable_swap_trades_near_par_and_keeps_d() {
+        // Balanced, so the stable curve is flatter than x * y everywhere
+        let (total_coin, total_pc) = (1_000_000_000u128, 1_000_000_000u128);
+        for amount_in in [1u128, 1_000, 10_000_000, 500_000_000] {
+            let amount_out = StableSwapCalculator::swap_token_base_amount_in(
+                100,
+                amount_in,
+                total_coin,
+                total_pc,
+                SwapDirection::Coin2Pc,
+            )
+            .unwrap();
+            let constant_product = AMMCalculator::swap_token_base_amount_in(
+                amount_in,
+                total_coin,
+                total_pc,
+                SwapDirection::Coin2Pc,
+            );
+            assert!(amount_out >= constant_product.saturating_sub(1));
+            assert!(amount_out <= amount_in);
+            let d_before = StableSwapCalculator::compute_d(100, total_coin, total_pc).unwrap();
+            let d_after =
+                StableSwapCalculator::compute_d(100, total_coin + amount_in, total_pc - amount_out)
+                    .unwrap();
+            assert!(d_after >= d_before);
+        }
+        // Close to par a large trade loses little to slippage
+        let amount_out = StableSwapCalculator::swap_token_base_amount_in(
+            100,
+            10_000_000,
+            1_000_000_000,
+            1_000_000_000,
+            SwapDirection::Pc2Coin,
+        )
+        .unwrap();
+        assert!(amount_out > 9_990_000 && amount_out < 10_000_000);
+    }
+
+    #[test]
+    fn stable_swap_base_out_covers_amount_out() {
+        let (total_coin, total_pc) = (3_000_000_000u128, 2_000_000_000u128);
+        for amount_out in [1u128, 7, 90_702_432, 1_500_000_000] {
+            let amount_in = StableSwapCalculator::swap_token_base_amount_out(
+                100,
+                amount_out,
+                total_coin,
+                total_pc,
+                &default_fees(),
+                SwapDirection::Coin2Pc,
+            )
+            .unwrap();
+            let fee = amount_in * 25 / 10000;
+            let received = StableSwapCalculator::swap_token_base_amount_in(
+                100,
+                amount_in - fee,
+                total_coin,
+                total_pc,
+                SwapDirection::Coin2Pc,
+            )
+            .unwrap();
+            assert!(received >= amount_out);
+        }
+        let result = StableSwapCalculator::swap_token_base_amount_out(
+            100,
+            2_000_000_000,
+            total_coin,
+            total_pc,
+            &default_fees(),
+            SwapDirection::Coin2Pc,
+        );
+        assert!(matches!(result, Err(AMMError::InsufficientPoolFund)));
+    }
+
+    /// Arbitrary-precision reference for `FixedPoint`, working on the same
+    /// `ONE`-scaled integers
+    mod reference {
+        use dashu_float::DBig;
+
+        fn from_fixed(x: i128) -> DBig {
+            (DBig::from(x) / DBig::from(super::FixedPoint::ONE))
+                .with_precision(80)
+                .value()
+        }
+
+        fn to_fixed(x: DBig) -> i128 {
+            let scaled = x * DBig::from(super::FixedPoint::ONE);
+            i128::try_from(scaled.round().to_int().value()).unwrap()
+        }
+
+        pub fn ln(x: u128) -> i128 {
+            to_fixed(from_fixed(x as i128).ln())
+        }
+
+        pub fn exp(x: i128) -> i128 {
+            to_fixed(from_fixed(x).exp())
+        }
+
+        pub fn pow(base: u128, exponent: u128) -> i128 {
+            to_fixed((from_fixed(base as i128).ln() * from_fixed(exponent as i128)).exp())
+        }
+    }
+
+    fn relative_bound(exact: i128, relative_error: u128) -> u128 {
+        let exact = exact.unsigned_abs();
+        exact / FixedPoint::ONE * relative_error
+            + exact % FixedPoint::ONE * relative_error / FixedPoint::ONE
+            + 1
+    }
+
+    proptest! {
+        #[test]
+        fn fixed_point_ln_matches_reference(x in 1u128..1_000_000_000_000_000_000_000_000_000_000_000_000) {
+            let error = FixedPoint::ln(x).unwrap().abs_diff(reference::ln(x));
+            prop_assert!(error <= FixedPoint::MAX_LN_ERROR, "ln({}) is off by {}", x, error);
+        }
+
+        #[test]
+        fn fixed_point_exp_matches_reference(x in -40_000_000_000_000_000_000i128..40_000_000_000_000_000_000) {
+            let exact = reference::exp(x);
+            let error = (FixedPoint::exp(x).unwrap() as i128).abs_diff(exact);
+            prop_assert!(
+                error <= relative_bound(exact, FixedPoint::MAX_EXP_RELATIVE_ERROR),
+                "exp({}) is off by {}", x, error
+            );
+        }
+
+        #[test]
+        fn fixed_point_pow_matches_reference(
+            // Bases that weighted trades of up to 30% of a reserve produce
+            base in 700_000_000_000_000_000u128..1_430_000_000_000_000_000,
+            exponent in 10_000_000_000_000_000u128..99_000_000_000_000_000_000,
+        ) {
+            let exact = reference::pow(base, exponent);
+            let power = FixedPoint::pow(base, exponent).unwrap();
+            let error = (power as i128).abs_diff(exact);
+            prop_assert!(
+                error <= relative_bound(exact, FixedPoint::MAX_POW_RELATIVE_ERROR),
+                "pow({}, {}) is off by {}", base, exponent, error
+            );
+            prop_assert!(FixedPoint::pow_down(base, exponent).unwrap() as i128 <= exact);
+            prop_assert!(FixedPoint::pow_up(base, exponent).unwrap() as i128 >= exact);
+        }
+
+        #[test]
+        fn weighted_swap_base_out_round_trips(
+            total_in in 1_000u64..u64::MAX / 2,
+            total_out in 1_000u64..u64::MAX / 2,
+            trade_share in 1u128..300,
+            weight_in in 1u64..100,
+        ) {
+            let weight_out = 100 - weight_in;
+            let (total_in, total_out) = (u128::from(total_in), u128::from(total_out));
+            let amount_out = (total_out * trade_share / 1000).max(1);
+            let no_fees = Fees {
+                swap_fee_numerator: 0,
+                swap_fee_denominator: 1,
+                ..default_fees()
+            };
+            let Ok(amount_in) = WeightedCalculator::swap_token_base_amount_out(
+                amount_out, total_in, total_out, weight_in, weight_out, &no_fees,
+            ) else {
+                return Ok(());
+            };
+            let Ok(received) = WeightedCalculator::swap_token_base_amount_in(
+                amount_in, total_in, total_out, weight_in, weight_out,
+            ) else {
+                return Ok(());
+            };
+            prop_assert!(received >= amount_out, "{} < {}", received, amount_out);
+        }
+
+        #[test]
+        fn weighted_swap_never_beats_the_exact_curve(
+            total_in in 1_000u64..u64::MAX / 2,
+            total_out in 1_000u64..u64::MAX / 2,
+            trade_share in 1u128..300,
+            weight_in in 1u64..100,
+        ) {
+            let (total_in, total_out) = (u128::from(total_in), u128::from(total_out));
+            let amount_in = total_in * trade_share / 1_000;
+            let weight_out = 100 - weight_in;
+            prop_assume!(weight_out > 0);
+            let amount_out = WeightedCalculator::swap_token_base_amount_in(
+                amount_in, total_in, total_out, weight_in, weight_out,
+            ).unwrap();
+            // Exact output from the reference power of the same base
+            let base = total_in * FixedPoint::ONE / (total_in + amount_in);
+            let exponent = u128::from(weight_in) * FixedPoint::ONE / u128::from(weight_out);
+            let exact_power = reference::pow(base, exponent) as u128;
+            let exact_out = total_out * (FixedPoint::ONE - exact_power.min(FixedPoint::ONE)) / FixedPoint::ONE;
+            prop_assert!(amount_out <= exact_out);
+            // Rounding in the pool's favour costs at most the pow bound
+            let slack = total_out * (relative_bound(exact_power as i128, FixedPoint::MAX_POW_RELATIVE_ERROR) * 2 + 1) / FixedPoint::ONE + 1;
+            prop_assert!(exact_out - amount_out <= slack, "lost {} > {}", exact_out - amount_out, slack);
+        }
+    }
+
+    #[test]
+    fn weighted_swap_prices_by_weight() {
+        // 80/20 pool priced at par, (1e9 / 20) / (4e9 / 80) = 1
+        let amount_out = WeightedCalculator::swap_token_base_amount_in(
+            1_000_000,
+            4_000_000_000,
+            1_000_000_000,
+            80,
+            20,
+        )
+        .unwrap();
+        assert!(amount_out < 1_000_000 && amount_out > 999_000);
+        // Equal weights match the constant product curve
+        let weighted = WeightedCalculator::swap_token_base_amount_in(
+            10_000_000,
+            2_000_000_000,
+            1_000_000_000,
+            50,
+            50,
+        )
         .unwrap();
-        token_share
+        let constant_product = AMMCalculator::swap_token_base_amount_in(
+            10_000_000,
+            2_000_000_000,
+            1_000_000_000,
+            SwapDirection::Coin2Pc,
+        );
+        assert!(weighted <= constant_product && weighted + 2 >= constant_product);
+        let result = WeightedCalculator::swap_token_base_amount_in(
+            300_000_001,
+            1_000_000_000,
+            1_000,
+            50,
+            50,
+        );
+        assert!(matches!(result, Err(AMMError::TradeTooLarge)));
+    }
+
+    #[test]
+    fn weighted_invariant_rejects_overflowing_weights() {
+        assert!(WeightedCalculator::invariant(4_000_000_000, 1_000_000_000, 80, 20).is_ok());
+        let result =
+            WeightedCalculator::invariant(4_000_000_000, 1_000_000_000, u64::MAX, u64::MAX);
+        assert!(matches!(result, Err(AMMError::MathOverflow)));
+    }
+
+    #[test]
+    fn weighted_swap_base_out_covers_amount_out() {
+        let (total_in, total_out) = (4_000_000_000u128, 1_000_000_000u128);
+        for amount_out in [1u128, 7, 90_702_432, 300_000_000] {
+            let amount_in = WeightedCalculator::swap_token_base_amount_out(
+                amount_out,
+                total_in,
+                total_out,
+                80,
+                20,
+                &default_fees(),
+            )
+            .unwrap();
+            let fee = amount_in * 25 / 10000;
+            let received = WeightedCalculator::swap_token_base_amount_in(
+                amount_in - fee,
+                total_in,
+                total_out,
+                80,
+                20,
+            )
+            .unwrap();
+            assert!(received >= amount_out, "{} < {}", received, amount_out);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn tick_at_sqrt_price_inverts_sqrt_price_at_tick(tick in TickMath::MIN_TICK..TickMath::MAX_TICK) {
+            let sqrt_price = TickMath::sqrt_price_at_tick(tick).unwrap();
+            let next_sqrt_price = TickMath::sqrt_price_at_tick(tick + 1).unwrap();
+            prop_assert!(sqrt_price < next_sqrt_price);
+            prop_assert_eq!(TickMath::tick_at_sqrt_price(sqrt_price).unwrap(), tick);
+            prop_assert_eq!(TickMath::tick_at_sqrt_price(next_sqrt_price - 1).unwrap(), tick);
+        }
+    }
+
+    #[test]
+    fn sqrt_price_at_tick_matches_reference() {
+        assert_eq!(TickMath::sqrt_price_at_tick(0).unwrap(), 1 << 64);
+        for tick in [
+            TickMath::MIN_TICK,
+            -100_000,
+            -1,
+            1,
+            60,
+            100_000,
+            TickMath::MAX_TICK,
+        ] {
+            let exact = reference::exp(i128::from(tick) * TickMath::HALF_LN_TICK_BASE);
+            let exact_q64 = (U256::from(exact as u128) << 64) / U256::from(FixedPoint::ONE);
+            let sqrt_price = U256::from(TickMath::sqrt_price_at_tick(tick).unwrap());
+            let error = if sqrt_price > exact_q64 {
+                sqrt_price - exact_q64
+            } else {
+                exact_q64 - sqrt_price
+            };
+            // Within exp's error, scaled from `ONE` to Q64.64
+            let bound = relative_bound(exact, FixedPoint::MAX_EXP_RELATIVE_ERROR) + 1;
+            let bound = (U256::from(bound) << 64) / U256::from(FixedPoint::ONE) + 1;
+            assert!(error <= bound, "tick {} is off by {}", tick, error);
+        }
+        assert!(TickMath::sqrt_price_at_tick(TickMath::MAX_TICK + 1).is_err());
+        assert!(TickMath::sqrt_price_at_tick(TickMath::MIN_TICK - 1).is_err());
+        assert!(TickMath::tick_at_sqrt_price(0).is_err());
+        assert!(TickMath::tick_at_sqrt_price(u128::MAX).is_err());
+    }
+
+    #[test]
+    fn liquidity_deltas_round_in_the_pools_favour() {
+        // 1e9 liquidity over ticks [-600, 600) holds 1e9 * (1 - 1.0001^-300)
+        // base tokens above a price of 1 and the same pc tokens below it
+        let lower = TickMath::sqrt_price_at_tick(-600).unwrap();
+        let upper = TickMath::sqrt_price_at_tick(600).unwrap();
+        let price = 1 << 64;
+        let base_down =
+            ConcentratedLiquidityCalculator::base_token_delta(price, upper, 1_000_000_000, false)
+                .unwrap();
+        let base_up =
+            ConcentratedLiquidityCalculator::base_token_delta(upper, price, 1_000_000_000, true)
+                .unwrap();
+        let pc_down =
+            ConcentratedLiquidityCalculator::pc_token_delta(lower, price, 1_000_000_000, false)
+                .unwrap();
+        let pc_up =
+            ConcentratedLiquidityCalculator::pc_token_delta(lower, price, 1_000_000_000, true)
+                .unwrap();
+        assert_eq!(base_up, base_down + 1);
+        assert_eq!(pc_up, pc_down + 1);
+        assert_eq!(base_down, 29_553_010);
+        assert_eq!(pc_down, 29_553_010);
+        assert!(ConcentratedLiquidityCalculator::base_token_delta(0, price, 1, false).is_err());
+    }
+
+    #[test]
+    fn swap_step_stops_at_the_target_or_spends_the_input() {
+        let fees = default_fees();
+        let price = 1u128 << 64;
+        let target = TickMath::sqrt_price_at_tick(-600).unwrap();
+        let liquidity = 1_000_000_000;
+
+        // Small trade stays inside the range and spends everything
+        let step =
+            ConcentratedLiquidityCalculator::swap_step(price, target, liquidity, 1_000_000, &fees)
+                .unwrap();
+        assert!(step.sqrt_price_q64 > target && step.sqrt_price_q64 < price);
+        assert_eq!(step.amount_in + step.fee_amount, 1_000_000);
+        assert_eq!(step.fee_amount, 2_500);
+        // Around price 1, the trade moves the price by about 0.1%
+        assert!(step.amount_out < step.amount_in && step.amount_out > 996_000);
+
+        // Large trade stops at the target with input to spare
+        let step = ConcentratedLiquidityCalculator::swap_step(
+            price,
+            target,
+            liquidity,
+            100_000_000,
+            &fees,
+        )
+        .unwrap();
+        assert_eq!(step.sqrt_price_q64, target);
+        let max_in =
+            ConcentratedLiquidityCalculator::base_token_delta(target, price, liquidity, true)
+                .unwrap();
+        assert_eq!(step.amount_in, max_in);
+        assert!(step.amount_in + step.fee_amount < 100_000_000);
+        assert_eq!(
+            step.amount_out,
+            ConcentratedLiquidityCalculator::pc_token_delta(target, price, liquidity, false)
+                .unwrap()
+        );
+
+        // Buying base moves the price up
+        let target = TickMath::sqrt_price_at_tick(600).unwrap();
+        let step =
+            ConcentratedLiquidityCalculator::swap_step(price, target, liquidity, 1_000_000, &fees)
+                .unwrap();
+        assert!(step.sqrt_price_q64 > price && step.sqrt_price_q64 < target);
+        assert_eq!(step.amount_in + step.fee_amount, 1_000_000);
+
+        // Without liquidity the price jumps straight to the target for free
+        let step =
+            ConcentratedLiquidityCalculator::swap_step(price, target, 0, 1_000, &fees).unwrap();
+        assert_eq!(step.sqrt_price_q64, target);
+        assert_eq!(
+            (step.amount_in, step.amount_out, step.fee_amount),
+            (0, 0, 0)
+        );
+    }
+
+    #[test]
+    fn price_q64_keeps_precision_for_small_prices() {
+        // 1 pc unit per 3 base units, well below one raw unit
+        let price = PriceCalculator::price_q64(1_000_000, 3_000_000).unwrap();
+        assert_eq!(price, (1u128 << 64) / 3);
+        assert_eq!(
+            PriceCalculator::price_q64(u64::MAX, 1).unwrap(),
+            u128::from(u64::MAX) << 64
+        );
+        assert!(PriceCalculator::price_q64(1, 0).is_err());
+    }
+
+    #[test]
+    fn twap_weights_prices_by_time() {
+        let first = PriceCalculator::price_q64(2, 1).unwrap();
+        let second = PriceCalculator::price_q64(5, 1).unwrap();
+        let cumulative = PriceCalculator::accumulate(0, first, 30);
+        let cumulative = PriceCalculator::accumulate(cumulative, second, 10);
+        let twap = PriceCalculator::twap_q64(0, cumulative, 40).unwrap();
+        // (2 * 30 + 5 * 10) / 40 = 2.75
+        assert_eq!(twap, (11u128 << 64) / 4);
+    }
+
+    #[test]
+    fn twap_survives_accumulator_wrapping() {
+        let price = PriceCalculator::price_q64(1_000, 1).unwrap();
+        let start = u128::MAX - price;
+        let end = PriceCalculator::accumulate(start, price, 3);
+        assert!(end < start);
+        assert_eq!(PriceCalculator::twap_q64(start, end, 3).unwrap(), price);
+        assert!(PriceCalculator::twap_q64(start, end, 0).is_err());
     }
 }
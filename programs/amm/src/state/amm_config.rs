@@ -0,0 +1,154 @@
+use anchor_lang::prelude::*;
+
+use crate::{AMMCalculator, AMMError, Converter, Fees};
+
+pub const MAX_FEE_TIERS: usize = 8;
+/// LP tokens locked in every new pool, whatever the LP mint's decimals
+pub const DEFAULT_LOCKED_LIQUIDITY: u64 = 1_000;
+
+/// Program-wide settings, stored in the single `[b"amm_config"]` PDA.
+#[account]
+pub struct AmmConfig {
+    pub admin: Pubkey,
+    pub bump: u8,
+    /// Only signer allowed to collect protocol fees from the pools
+    pub treasury: Pubkey,
+    /// Fee on flash loans as a share of the amount borrowed, paid to the LPs
+    pub flash_loan_fee_numerator: u64,
+    pub flash_loan_fee_denominator: u64,
+    /// `PoolStatus` bits applied to every pool, including concentrated ones
+    pub status: u8,
+    /// LP tokens minted to each new pool's lock account and never redeemable
+    pub locked_liquidity: u64,
+    pub fee_tiers: Vec<Fees>,
+}
+
+impl AmmConfig {
+    pub const MAX_SIZE: usize = 32 + 1 + 32 + 8 + 8 + 1 + 8 + 4 + MAX_FEE_TIERS * Fees::SIZE;
+
+    pub fn fee_tier(&self, fee_tier_index: u16) -> Result<Fees> {
+        self.fee_tiers
+            .get(usize::from(fee_tier_index))
+            .cloned()
+            .ok_or(AMMError::InvalidFeeTier.into())
+    }
+
+    pub fn add_fee_tier(&mut self, fees: Fees) -> Result<u16> {
+        fees.validate()?;
+        require!(
+            self.fee_tiers.len() < MAX_FEE_TIERS,
+            AMMError::FeeTierLimitReached
+        );
+        self.fee_tiers.push(fees);
+        Ok((self.fee_tiers.len() - 1) as u16)
+    }
+
+    pub fn set_flash_loan_fee(&mut self, numerator: u64, denominator: u64) -> Result<()> {
+        require!(
+            denominator > 0 && numerator < denominator,
+            AMMError::InvalidFee
+        );
+        self.flash_loan_fee_numerator = numerator;
+        self.flash_loan_fee_denominator = denominator;
+        Ok(())
+    }
+
+    pub fn set_locked_liquidity(&mut self, locked_liquidity: u64) -> Result<()> {
+        require!(locked_liquidity > 0, AMMError::InvalidLockedLiquidity);
+        self.locked_liquidity = locked_liquidity;
+        Ok(())
+    }
+
+    /// Flash loan fee on `amount`, rounded up
+    pub fn flash_loan_fee(&self, amount: u64) -> Result<u64> {
+        let fee = AMMCalculator::checked_ceil_div(
+            u128::from(amount)
+                .checked_mul(self.flash_loan_fee_numerator.into())
+                .ok_or(AMMError::MathOverflow)?,
+            self.flash_loan_fee_denominator.into(),
+        )?;
+        Ok(Converter::to_u64(fee)?)
+    }
+
+    pub fn update_fee_tier(&mut self, fee_tier_index: u16, fees: Fees) -> Result<()> {
+        fees.validate()?;
+        let fee_tier = self
+            .fee_tiers
+            .get_mut(usize::from(fee_tier_index))
+            .ok_or(AMMError::InvalidFeeTier)?;
+        *fee_tier = fees;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fees(swap_fee_numerator: u64, swap_fee_denominator: u64) -> Fees {
+        Fees {
+            swap_fee_numerator,
+            swap_fee_denominator,
+            protocol_fee_numerator: 0,
+            protocol_fee_denominator: 1,
+        }
+    }
+
+    fn config() -> AmmConfig {
+        AmmConfig {
+            admin: Pubkey::default(),
+            bump: 0,
+            treasury: Pubkey::default(),
+            flash_loan_fee_numerator: 0,
+            flash_loan_fee_denominator: 1,
+            status: 0,
+            locked_liquidity: DEFAULT_LOCKED_LIQUIDITY,
+            fee_tiers: vec![],
+        }
+    }
+
+    #[test]
+    fn fee_tiers_require_numerator_below_denominator() {
+        let mut amm_config = config();
+        assert_eq!(amm_config.add_fee_tier(fees(25, 10000)).unwrap(), 0);
+        assert!(amm_config.add_fee_tier(fees(10000, 10000)).is_err());
+        assert!(amm_config.add_fee_tier(fees(0, 0)).is_err());
+        assert!(amm_config.update_fee_tier(0, fees(30, 10000)).is_ok());
+        assert!(amm_config.update_fee_tier(0, fees(30, 30)).is_err());
+        assert!(amm_config.update_fee_tier(1, fees(30, 10000)).is_err());
+        assert_eq!(amm_config.fee_tier(0).unwrap().swap_fee_numerator, 30);
+    }
+
+    #[test]
+    fn fee_tiers_are_capped() {
+        let mut amm_config = config();
+        for _ in 0..MAX_FEE_TIERS {
+            amm_config.add_fee_tier(fees(1, 100)).unwrap();
+        }
+        let err = amm_config.add_fee_tier(fees(1, 100)).unwrap_err();
+        assert_eq!(err, AMMError::FeeTierLimitReached.into());
+    }
+
+    #[test]
+    fn flash_loan_fees_round_up() {
+        let mut amm_config = config();
+        assert_eq!(amm_config.flash_loan_fee(1_000_000).unwrap(), 0);
+        amm_config.set_flash_loan_fee(9, 10000).unwrap();
+        assert_eq!(amm_config.flash_loan_fee(1_000_000).unwrap(), 900);
+        assert_eq!(amm_config.flash_loan_fee(1).unwrap(), 1);
+        assert_eq!(amm_config.flash_loan_fee(0).unwrap(), 0);
+        let err = amm_config.set_flash_loan_fee(1, 1).unwrap_err();
+        assert_eq!(err, AMMError::InvalidFee.into());
+        assert!(amm_config.set_flash_loan_fee(0, 0).is_err());
+    }
+
+    #[test]
+    fn locked_liquidity_has_to_be_positive() {
+        let mut amm_config = config();
+        amm_config.set_locked_liquidity(1).unwrap();
+        assert_eq!(amm_config.locked_liquidity, 1);
+        let err = amm_config.set_locked_liquidity(0).unwrap_err();
+        assert_eq!(err, AMMError::InvalidLockedLiquidity.into());
+        assert_eq!(amm_config.locked_liquidity, 1);
+    }
+}
@@ -0,0 +1,727 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    AMMError, ConcentratedLiquidityCalculator, Converter, Fees, SwapDirection, TickMath, U256,
+};
+
+/// Ticks stored per `TickArray`
+pub const TICK_ARRAY_SIZE: usize = 64;
+
+/// Pool whose liquidity comes from positions over tick ranges, PDA
+/// `[b"concentrated_pool", concentrated_pool_index]`. Its vaults use the same
+/// seeds as the vaults of `InitalizeLiquidityAccount` pools.
+#[account]
+pub struct ConcentratedPool {
+    pub base_token: Pubkey,
+    pub pc_token: Pubkey,
+    pub creator: Pubkey,
+    pub bump: u8,
+    pub base_token_vault_bump: u8,
+    pub pc_token_vault_bump: u8,
+    pub tick_spacing: u16,
+    pub fee_tier_index: u16,
+    pub fees: Fees,
+    pub sqrt_price_q64: u128,
+    /// Greatest tick at or below the current price
+    pub tick_current: i32,
+    /// Liquidity of the positions whose range covers the current price
+    pub liquidity: u128,
+    /// Swap fees earned per unit of liquidity over the pool's lifetime,
+    /// Q64.64 and wrapping
+    pub fee_growth_global_base_q64: u128,
+    pub fee_growth_global_pc_q64: u128,
+    /// Protocol share of the swap fees, sitting in the vaults until the
+    /// treasury collects it
+    pub protocol_fees_base_token: u64,
+    pub protocol_fees_pc_token: u64,
+    /// `PoolStatus` bits
+    pub status: u8,
+}
+
+/// Result of `ConcentratedPool::swap`
+pub struct ConcentratedSwap {
+    /// Input the swap used, less than offered when it stopped at the price
+    /// limit
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub fee_amount: u64,
+}
+
+impl ConcentratedPool {
+    pub const MAX_SIZE: usize =
+        32 + 32 + 32 + 1 + 1 + 1 + 2 + 2 + Fees::SIZE + 16 + 4 + 16 + 16 + 16 + 8 + 8 + 1;
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn initialize(
+        &mut self,
+        base_token: Pubkey,
+        pc_token: Pubkey,
+        creator: Pubkey,
+        bump: u8,
+        base_token_vault_bump: u8,
+        pc_token_vault_bump: u8,
+        tick_spacing: u16,
+        fee_tier_index: u16,
+        fees: Fees,
+        sqrt_price_q64: u128,
+    ) -> Result<()> {
+        require!(tick_spacing > 0, AMMError::InvalidTickRange);
+        let tick_current = TickMath::tick_at_sqrt_price(sqrt_price_q64)?;
+        *self = Self {
+            base_token,
+            pc_token,
+            creator,
+            bump,
+            base_token_vault_bump,
+            pc_token_vault_bump,
+            tick_spacing,
+            fee_tier_index,
+            fees,
+            sqrt_price_q64,
+            tick_current,
+            liquidity: 0,
+            fee_growth_global_base_q64: 0,
+            fee_growth_global_pc_q64: 0,
+            protocol_fees_base_token: 0,
+            protocol_fees_pc_token: 0,
+            status: 0,
+        };
+        Ok(())
+    }
+
+    pub fn collect_protocol_fees(&mut self) -> (u64, u64) {
+        let protocol_fees = (self.protocol_fees_base_token, self.protocol_fees_pc_token);
+        self.protocol_fees_base_token = 0;
+        self.protocol_fees_pc_token = 0;
+        protocol_fees
+    }
+
+    pub fn fee_growth_global(&self) -> (u128, u128) {
+        (
+            self.fee_growth_global_base_q64,
+            self.fee_growth_global_pc_q64,
+        )
+    }
+
+    /// First tick of the tick array holding `tick_index`
+    pub fn tick_array_start(&self, tick_index: i32) -> i32 {
+        let ticks_per_array = i32::from(self.tick_spacing) * TICK_ARRAY_SIZE as i32;
+        tick_index.div_euclid(ticks_per_array) * ticks_per_array
+    }
+
+    pub fn validate_tick_range(&self, tick_lower: i32, tick_upper: i32) -> Result<()> {
+        let tick_spacing = i32::from(self.tick_spacing);
+        require!(
+            tick_lower < tick_upper
+                && tick_lower >= TickMath::MIN_TICK
+                && tick_upper <= TickMath::MAX_TICK
+                && tick_lower % tick_spacing == 0
+                && tick_upper % tick_spacing == 0,
+            AMMError::InvalidTickRange
+        );
+        Ok(())
+    }
+
+    /// Fees earned per unit of liquidity inside `[tick_lower, tick_upper)`
+    pub fn fee_growth_inside(
+        &self,
+        tick_lower: i32,
+        lower: &Tick,
+        tick_upper: i32,
+        upper: &Tick,
+    ) -> (u128, u128) {
+        let inside = |global: u128, lower_outside: u128, upper_outside: u128| {
+            let below = if self.tick_current >= tick_lower {
+                lower_outside
+            } else {
+                global.wrapping_sub(lower_outside)
+            };
+            let above = if self.tick_current < tick_upper {
+                upper_outside
+            } else {
+                global.wrapping_sub(upper_outside)
+            };
+            global.wrapping_sub(below).wrapping_sub(above)
+        };
+        (
+            inside(
+                self.fee_growth_global_base_q64,
+                lower.fee_growth_outside_base_q64,
+                upper.fee_growth_outside_base_q64,
+            ),
+            inside(
+                self.fee_growth_global_pc_q64,
+                lower.fee_growth_outside_pc_q64,
+                upper.fee_growth_outside_pc_q64,
+            ),
+        )
+    }
+
+    /// Tokens backing `liquidity` over `[tick_lower, tick_upper)` at the
+    /// current price. Deposits round up and withdrawals round down.
+    pub fn amounts_for_liquidity(
+        &self,
+        tick_lower: i32,
+        tick_upper: i32,
+        liquidity: u128,
+        round_up: bool,
+    ) -> Result<(u64, u64)> {
+        let sqrt_price_lower_q64 = TickMath::sqrt_price_at_tick(tick_lower)?;
+        let sqrt_price_upper_q64 = TickMath::sqrt_price_at_tick(tick_upper)?;
+        let (base_token_amount, pc_token_amount) = if self.tick_current < tick_lower {
+            (
+                ConcentratedLiquidityCalculator::base_token_delta(
+                    sqrt_price_lower_q64,
+                    sqrt_price_upper_q64,
+                    liquidity,
+                    round_up,
+                )?,
+                0,
+            )
+        } else if self.tick_current < tick_upper {
+            (
+                ConcentratedLiquidityCalculator::base_token_delta(
+                    self.sqrt_price_q64,
+                    sqrt_price_upper_q64,
+                    liquidity,
+                    round_up,
+                )?,
+                ConcentratedLiquidityCalculator::pc_token_delta(
+                    sqrt_price_lower_q64,
+                    self.sqrt_price_q64,
+                    liquidity,
+                    round_up,
+                )?,
+            )
+        } else {
+            (
+                0,
+                ConcentratedLiquidityCalculator::pc_token_delta(
+                    sqrt_price_lower_q64,
+                    sqrt_price_upper_q64,
+                    liquidity,
+                    round_up,
+                )?,
+            )
+        };
+        Ok((
+            Converter::to_u64(base_token_amount)?,
+            Converter::to_u64(pc_token_amount)?,
+        ))
+    }
+
+    /// Adds or removes `liquidity_delta` for `position` at both of its ticks
+    /// and in the active liquidity, then settles the fees it earned so far.
+    /// Both ends may live in the same tick array, which is then passed twice.
+    pub fn modify_position(
+        &mut self,
+        position: &mut Position,
+        tick_array_lower: &AccountLoader<TickArray>,
+        tick_array_upper: &AccountLoader<TickArray>,
+        liquidity_delta: i128,
+    ) -> Result<()> {
+        let (tick_lower, tick_upper) = (position.tick_lower, position.tick_upper);
+        let (tick_current, tick_spacing) = (self.tick_current, self.tick_spacing);
+        let fee_growth_global = self.fee_growth_global();
+        let update = |tick_array: &mut TickArray, tick_index: i32, upper: bool| -> Result<Tick> {
+            let tick = tick_array.tick_mut(tick_index, tick_spacing)?;
+            tick.update(
+                tick_index,
+                tick_current,
+                liquidity_delta,
+                fee_growth_global,
+                upper,
+            )?;
+            Ok(*tick)
+        };
+        let (lower, upper) = if tick_array_lower.key() == tick_array_upper.key() {
+            let mut tick_array = tick_array_lower.load_mut()?;
+            (
+                update(&mut tick_array, tick_lower, false)?,
+                update(&mut tick_array, tick_upper, true)?,
+            )
+        } else {
+            (
+                update(&mut *tick_array_lower.load_mut()?, tick_lower, false)?,
+                update(&mut *tick_array_upper.load_mut()?, tick_upper, true)?,
+            )
+        };
+
+        let fee_growth_inside = self.fee_growth_inside(tick_lower, &lower, tick_upper, &upper);
+        position.update(liquidity_delta, fee_growth_inside)?;
+
+        if (tick_lower..tick_upper).contains(&self.tick_current) {
+            self.liquidity = self
+                .liquidity
+                .checked_add_signed(liquidity_delta)
+                .ok_or(AMMError::MathOverflow)?;
+        }
+        Ok(())
+    }
+
+    /// Swaps `amount_in` through the pool, crossing into the next tick range
+    /// whenever one runs dry, until the input is spent or the price reaches
+    /// `sqrt_price_limit_q64`. Without a limit the swap can run to the end
+    /// of the tick range. `tick_arrays` have to follow the price in the swap
+    /// direction starting from the array holding the current tick.
+    pub fn swap(
+        &mut self,
+        concentrated_pool: Pubkey,
+        tick_arrays: &[AccountLoader<TickArray>],
+        amount_in: u64,
+        swap_direction: SwapDirection,
+        sqrt_price_limit_q64: Option<u128>,
+    ) -> Result<ConcentratedSwap> {
+        let base_to_pc = matches!(swap_direction, SwapDirection::Coin2Pc);
+        let min_sqrt_price_q64 = TickMath::sqrt_price_at_tick(TickMath::MIN_TICK)?;
+        let max_sqrt_price_q64 = TickMath::sqrt_price_at_tick(TickMath::MAX_TICK)?;
+        let sqrt_price_limit_q64 = if base_to_pc {
+            let limit = sqrt_price_limit_q64.unwrap_or(min_sqrt_price_q64);
+            require!(
+                limit < self.sqrt_price_q64 && limit >= min_sqrt_price_q64,
+                AMMError::InvalidSqrtPriceLimit
+            );
+            limit
+        } else {
+            let limit = sqrt_price_limit_q64.unwrap_or(max_sqrt_price_q64);
+            require!(
+                limit > self.sqrt_price_q64 && limit <= max_sqrt_price_q64,
+                AMMError::InvalidSqrtPriceLimit
+            );
+            limit
+        };
+        let ticks_per_array = i32::from(self.tick_spacing) * TICK_ARRAY_SIZE as i32;
+        let mut tick_arrays = tick_arrays.iter();
+        let mut tick_array = tick_arrays.next().ok_or(AMMError::InvalidTickArray)?;
+        let mut amount_remaining = u128::from(amount_in);
+        let mut amount_out = 0u128;
+        let mut fee_amount = 0u128;
+
+        while amount_remaining > 0 && self.sqrt_price_q64 != sqrt_price_limit_q64 {
+            let mut ticks = tick_array.load_mut()?;
+            require_keys_eq!(
+                ticks.concentrated_pool,
+                concentrated_pool,
+                AMMError::InvalidTickArray
+            );
+            let start_tick_index = ticks.start_tick_index;
+            let end_tick_index = start_tick_index + ticks_per_array;
+            // Arrays behind the search start are done with, arrays ahead of it
+            // would skip the ticks in between. Moving up, the search starts
+            // one tick spacing above the current tick.
+            let search_start = if base_to_pc {
+                self.tick_current
+            } else {
+                self.tick_current + i32::from(self.tick_spacing)
+            };
+            let (passed, ahead) = if base_to_pc {
+                (
+                    search_start < start_tick_index,
+                    search_start >= end_tick_index,
+                )
+            } else {
+                (
+                    search_start >= end_tick_index,
+                    search_start < start_tick_index,
+                )
+            };
+            require!(!ahead, AMMError::InvalidTickArray);
+            if passed {
+                drop(ticks);
+                tick_array = tick_arrays.next().ok_or(AMMError::InvalidTickArray)?;
+                continue;
+            }
+
+            let (next_tick, initialized) =
+                ticks.next_tick(self.tick_current, self.tick_spacing, base_to_pc);
+            let next_tick = next_tick.clamp(TickMath::MIN_TICK, TickMath::MAX_TICK);
+            let next_tick_sqrt_price_q64 = TickMath::sqrt_price_at_tick(next_tick)?;
+            let target_sqrt_price_q64 = if base_to_pc {
+                next_tick_sqrt_price_q64.max(sqrt_price_limit_q64)
+            } else {
+                next_tick_sqrt_price_q64.min(sqrt_price_limit_q64)
+            };
+            // Nothing trades where no position is active, so the price moves
+            // straight on without taking any input or fees
+            if self.liquidity == 0 {
+                self.sqrt_price_q64 = target_sqrt_price_q64;
+            } else {
+                let step = ConcentratedLiquidityCalculator::swap_step(
+                    self.sqrt_price_q64,
+                    target_sqrt_price_q64,
+                    self.liquidity,
+                    amount_remaining,
+                    &self.fees,
+                )?;
+                amount_remaining -= step.amount_in + step.fee_amount;
+                amount_out += step.amount_out;
+                fee_amount += step.fee_amount;
+                self.add_fees(Converter::to_u64(step.fee_amount)?, base_to_pc)?;
+                self.sqrt_price_q64 = step.sqrt_price_q64;
+            }
+
+            if self.sqrt_price_q64 != next_tick_sqrt_price_q64 {
+                self.tick_current = TickMath::tick_at_sqrt_price(self.sqrt_price_q64)?;
+                continue;
+            }
+            if initialized {
+                let liquidity_net = ticks
+                    .tick_mut(next_tick, self.tick_spacing)?
+                    .cross(self.fee_growth_global());
+                // Moving down enters the range below the tick, so the
+                // liquidity that starts there leaves again
+                let liquidity_delta = if base_to_pc {
+                    liquidity_net.checked_neg().ok_or(AMMError::MathOverflow)?
+                } else {
+                    liquidity_net
+                };
+                self.liquidity = self
+                    .liquidity
+                    .checked_add_signed(liquidity_delta)
+                    .ok_or(AMMError::MathOverflow)?;
+            }
+            self.tick_current = if base_to_pc { next_tick - 1 } else { next_tick };
+        }
+        Ok(ConcentratedSwap {
+            amount_in: Converter::to_u64(u128::from(amount_in) - amount_remaining)?,
+            amount_out: Converter::to_u64(amount_out)?,
+            fee_amount: Converter::to_u64(fee_amount)?,
+        })
+    }
+
+    /// Splits a swap step's fee, paid in the input token, into the protocol
+    /// share and the fee growth of the active liquidity
+    fn add_fees(&mut self, fee_amount: u64, base_to_pc: bool) -> Result<()> {
+        let protocol_fee = self.fees.protocol_fee(fee_amount)?;
+        let fee_growth = (u128::from(fee_amount - protocol_fee) << 64)
+            .checked_div(self.liquidity)
+            .ok_or(AMMError::MathOverflow)?;
+        let (fee_growth_global, protocol_fees) = if base_to_pc {
+            (
+                &mut self.fee_growth_global_base_q64,
+                &mut self.protocol_fees_base_token,
+            )
+        } else {
+            (
+                &mut self.fee_growth_global_pc_q64,
+                &mut self.protocol_fees_pc_token,
+            )
+        };
+        *fee_growth_global = fee_growth_global.wrapping_add(fee_growth);
+        *protocol_fees = protocol_fees
+            .checked_add(protocol_fee)
+            .ok_or(AMMError::MathOverflow)?;
+        Ok(())
+    }
+}
+
+/// Liquidity boundary of one or more positions
+#[zero_copy]
+#[repr(C, packed)]
+#[derive(Default)]
+pub struct Tick {
+    /// Liquidity added to the active liquidity when the price crosses this
+    /// tick going up
+    pub liquidity_net: i128,
+    /// Liquidity of all positions using this tick as a boundary
+    pub liquidity_gross: u128,
+    /// Fee growth on the other side of this tick from the current price
+    pub fee_growth_outside_base_q64: u128,
+    pub fee_growth_outside_pc_q64: u128,
+}
+
+impl Tick {
+    pub const SIZE: usize = 16 + 16 + 16 + 16;
+
+    pub fn initialized(&self) -> bool {
+        self.liquidity_gross > 0
+    }
+
+    /// Adds `liquidity_delta` of a position that starts or, for `upper`,
+    /// ends at this tick. A tick that comes into use assumes all earlier fee
+    /// growth happened below it.
+    pub fn update(
+        &mut self,
+        tick_index: i32,
+        tick_current: i32,
+        liquidity_delta: i128,
+        fee_growth_global: (u128, u128),
+        upper: bool,
+    ) -> Result<()> {
+        if !self.initialized() {
+            let (base_outside, pc_outside) = if tick_index <= tick_current {
+                fee_growth_global
+            } else {
+                (0, 0)
+            };
+            self.fee_growth_outside_base_q64 = base_outside;
+            self.fee_growth_outside_pc_q64 = pc_outside;
+        }
+        self.liquidity_gross = self
+            .liquidity_gross
+            .checked_add_signed(liquidity_delta)
+            .ok_or(AMMError::MathOverflow)?;
+        let liquidity_net = if upper {
+            self.liquidity_net.checked_sub(liquidity_delta)
+        } else {
+            self.liquidity_net.checked_add(liquidity_delta)
+        };
+        self.liquidity_net = liquidity_net.ok_or(AMMError::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Flips the fee growth to the new outside as the price crosses this
+    /// tick and returns its `liquidity_net`.
+    pub fn cross(&mut self, fee_growth_global: (u128, u128)) -> i128 {
+        self.fee_growth_outside_base_q64 = fee_growth_global
+            .0
+            .wrapping_sub(self.fee_growth_outside_base_q64);
+        self.fee_growth_outside_pc_q64 = fee_growth_global
+            .1
+            .wrapping_sub(self.fee_growth_outside_pc_q64);
+        self.liquidity_net
+    }
+}
+
+/// `TICK_ARRAY_SIZE` consecutive ticks of a pool, one per `tick_spacing`,
+/// PDA `[b"tick_array", concentrated_pool, start_tick_index]`
+#[account(zero_copy)]
+pub struct TickArray {
+    pub concentrated_pool: Pubkey,
+    pub start_tick_index: i32,
+    pub ticks: [Tick; TICK_ARRAY_SIZE],
+}
+
+impl TickArray {
+    pub const SIZE: usize = 32 + 4 + TICK_ARRAY_SIZE * Tick::SIZE;
+
+    fn offset(&self, tick_index: i32, tick_spacing: u16) -> Option<usize> {
+        let offset = (tick_index - self.start_tick_index).div_euclid(i32::from(tick_spacing));
+        usize::try_from(offset)
+            .ok()
+            .filter(|offset| *offset < TICK_ARRAY_SIZE)
+    }
+
+    pub fn tick_mut(&mut self, tick_index: i32, tick_spacing: u16) -> Result<&mut Tick> {
+        let offset = self
+            .offset(tick_index, tick_spacing)
+            .ok_or(AMMError::InvalidTickArray)?;
+        Ok(&mut self.ticks[offset])
+    }
+
+    /// Closest initialized tick at or below `tick_current` when moving down,
+    /// or above it when moving up. Falls back to the first or last tick of
+    /// the array, reported as not initialized.
+    pub fn next_tick(&self, tick_current: i32, tick_spacing: u16, base_to_pc: bool) -> (i32, bool) {
+        let tick_spacing = i32::from(tick_spacing);
+        let offset = (tick_current - self.start_tick_index).div_euclid(tick_spacing);
+        let tick_at = |offset: i32| self.start_tick_index + offset * tick_spacing;
+        if base_to_pc {
+            (0..=offset)
+                .rev()
+                .find(|offset| self.ticks[*offset as usize].initialized())
+                .map_or((self.start_tick_index, false), |offset| {
+                    (tick_at(offset), true)
+                })
+        } else {
+            (offset + 1..TICK_ARRAY_SIZE as i32)
+                .find(|offset| self.ticks[*offset as usize].initialized())
+                .map_or((tick_at(TICK_ARRAY_SIZE as i32 - 1), false), |offset| {
+                    (tick_at(offset), true)
+                })
+        }
+    }
+}
+
+/// Liquidity an owner provides over `[tick_lower, tick_upper)`, PDA
+/// `[b"position", concentrated_pool, owner, tick_lower, tick_upper]`
+#[account]
+pub struct Position {
+    pub concentrated_pool: Pubkey,
+    pub owner: Pubkey,
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    pub liquidity: u128,
+    /// Fee growth inside the range when the fees were last settled
+    pub fee_growth_inside_base_last_q64: u128,
+    pub fee_growth_inside_pc_last_q64: u128,
+    /// Settled fees not yet paid out
+    pub fees_owed_base_token: u64,
+    pub fees_owed_pc_token: u64,
+    pub bump: u8,
+}
+
+impl Position {
+    pub const MAX_SIZE: usize = 32 + 32 + 4 + 4 + 16 + 16 + 16 + 8 + 8 + 1;
+
+    pub fn update(&mut self, liquidity_delta: i128, fee_growth_inside: (u128, u128)) -> Result<()> {
+        let owed = |inside: u128, last: u128| {
+            let growth = inside.wrapping_sub(last);
+            let owed = (U256::from(growth) * U256::from(self.liquidity)) >> 64;
+            if owed > U256::from(u64::MAX) {
+                return Err(AMMError::MathOverflow);
+            }
+            Ok(owed.as_u64())
+        };
+        let base_owed = owed(fee_growth_inside.0, self.fee_growth_inside_base_last_q64)?;
+        let pc_owed = owed(fee_growth_inside.1, self.fee_growth_inside_pc_last_q64)?;
+        self.fees_owed_base_token = self
+            .fees_owed_base_token
+            .checked_add(base_owed)
+            .ok_or(AMMError::MathOverflow)?;
+        self.fees_owed_pc_token = self
+            .fees_owed_pc_token
+            .checked_add(pc_owed)
+            .ok_or(AMMError::MathOverflow)?;
+        self.fee_growth_inside_base_last_q64 = fee_growth_inside.0;
+        self.fee_growth_inside_pc_last_q64 = fee_growth_inside.1;
+        self.liquidity = self
+            .liquidity
+            .checked_add_signed(liquidity_delta)
+            .ok_or(AMMError::MathOverflow)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(tick_current: i32) -> ConcentratedPool {
+        ConcentratedPool {
+            base_token: Pubkey::default(),
+            pc_token: Pubkey::default(),
+            creator: Pubkey::default(),
+            bump: 0,
+            base_token_vault_bump: 0,
+            pc_token_vault_bump: 0,
+            tick_spacing: 10,
+            fee_tier_index: 0,
+            fees: Fees::default(),
+            sqrt_price_q64: TickMath::sqrt_price_at_tick(tick_current).unwrap(),
+            tick_current,
+            liquidity: 0,
+            fee_growth_global_base_q64: 0,
+            fee_growth_global_pc_q64: 0,
+            protocol_fees_base_token: 0,
+            protocol_fees_pc_token: 0,
+            status: 0,
+        }
+    }
+
+    #[test]
+    fn tick_arrays_start_on_multiples_of_their_span() {
+        let pool = pool(0);
+        assert_eq!(pool.tick_array_start(0), 0);
+        assert_eq!(pool.tick_array_start(639), 0);
+        assert_eq!(pool.tick_array_start(640), 640);
+        assert_eq!(pool.tick_array_start(-1), -640);
+        assert_eq!(pool.tick_array_start(-640), -640);
+        assert_eq!(pool.tick_array_start(-641), -1280);
+
+        assert!(pool.validate_tick_range(-100, 100).is_ok());
+        let err = pool.validate_tick_range(100, 100).unwrap_err();
+        assert_eq!(err, AMMError::InvalidTickRange.into());
+        assert!(pool.validate_tick_range(-105, 100).is_err());
+        assert!(pool
+            .validate_tick_range(-100, TickMath::MAX_TICK + 2)
+            .is_err());
+    }
+
+    #[test]
+    fn next_tick_finds_initialized_ticks_or_the_array_edge() {
+        let mut tick_array: TickArray = bytemuck::Zeroable::zeroed();
+        tick_array.start_tick_index = -640;
+        let tick = tick_array.tick_mut(-200, 10).unwrap();
+        tick.update(-200, 0, 5, (0, 0), false).unwrap();
+        assert!(tick_array.tick_mut(0, 10).is_err());
+        assert!(tick_array.tick_mut(-641, 10).is_err());
+
+        assert_eq!(tick_array.next_tick(-1, 10, true), (-200, true));
+        assert_eq!(tick_array.next_tick(-200, 10, true), (-200, true));
+        assert_eq!(tick_array.next_tick(-201, 10, true), (-640, false));
+        assert_eq!(tick_array.next_tick(-300, 10, false), (-200, true));
+        assert_eq!(tick_array.next_tick(-200, 10, false), (-10, false));
+        // Moving up from just below the array searches it from its start
+        assert_eq!(tick_array.next_tick(-641, 10, false), (-200, true));
+    }
+
+    #[test]
+    fn fees_inside_a_range_only_grow_while_the_price_is_in_it() {
+        let mut pool = pool(0);
+        pool.fee_growth_global_base_q64 = 1_000;
+        let (mut lower, mut upper) = (Tick::default(), Tick::default());
+        // Both ticks come into use with all earlier growth below the lower
+        // one, so nothing is inside yet
+        lower
+            .update(-100, 0, 10, pool.fee_growth_global(), false)
+            .unwrap();
+        upper
+            .update(100, 0, 10, pool.fee_growth_global(), true)
+            .unwrap();
+        assert_eq!(pool.fee_growth_inside(-100, &lower, 100, &upper), (0, 0));
+        assert_eq!({ lower.liquidity_net }, 10);
+        assert_eq!({ upper.liquidity_net }, -10);
+
+        pool.fee_growth_global_base_q64 = 1_500;
+        assert_eq!(pool.fee_growth_inside(-100, &lower, 100, &upper), (500, 0));
+
+        // The price leaves the range upwards, later growth is outside
+        assert_eq!(upper.cross(pool.fee_growth_global()), -10);
+        pool.tick_current = 100;
+        pool.fee_growth_global_base_q64 = 2_000;
+        assert_eq!(pool.fee_growth_inside(-100, &lower, 100, &upper), (500, 0));
+
+        // And comes back down
+        upper.cross(pool.fee_growth_global());
+        pool.tick_current = 99;
+        pool.fee_growth_global_base_q64 = 2_200;
+        assert_eq!(pool.fee_growth_inside(-100, &lower, 100, &upper), (700, 0));
+    }
+
+    #[test]
+    fn swap_fees_split_off_the_protocol_share() {
+        let mut pool = pool(0);
+        pool.fees = Fees {
+            swap_fee_numerator: 25,
+            swap_fee_denominator: 10_000,
+            protocol_fee_numerator: 1,
+            protocol_fee_denominator: 5,
+        };
+        pool.liquidity = 1_000;
+        pool.add_fees(1_000, true).unwrap();
+        pool.add_fees(500, false).unwrap();
+        assert_eq!(
+            pool.fee_growth_global(),
+            ((800 << 64) / 1_000, (400 << 64) / 1_000)
+        );
+        assert_eq!(pool.collect_protocol_fees(), (200, 100));
+        assert_eq!(pool.collect_protocol_fees(), (0, 0));
+    }
+
+    #[test]
+    fn positions_accrue_fees_on_their_liquidity() {
+        let mut position = Position {
+            concentrated_pool: Pubkey::default(),
+            owner: Pubkey::default(),
+            tick_lower: -100,
+            tick_upper: 100,
+            liquidity: 0,
+            fee_growth_inside_base_last_q64: 0,
+            fee_growth_inside_pc_last_q64: 0,
+            fees_owed_base_token: 0,
+            fees_owed_pc_token: 0,
+            bump: 0,
+        };
+        position.update(1_000, (5 << 64, 0)).unwrap();
+        assert_eq!(position.fees_owed_base_token, 0);
+        // 3 base and 1/2 pc per unit of liquidity
+        position.update(0, (8 << 64, 1 << 63)).unwrap();
+        assert_eq!(position.fees_owed_base_token, 3_000);
+        assert_eq!(position.fees_owed_pc_token, 500);
+        position.update(-1_000, (8 << 64, 1 << 63)).unwrap();
+        assert_eq!(position.liquidity, 0);
+        assert_eq!(position.fees_owed_base_token, 3_000);
+        assert!(position.update(-1, (8 << 64, 1 << 63)).is_err());
+    }
+}
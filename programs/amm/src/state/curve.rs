@@ -0,0 +1,179 @@
+use anchor_lang::prelude::*;
+
+use crate::AMMError;
+
+pub const MIN_AMP: u64 = 1;
+pub const MAX_AMP: u64 = 1_000_000;
+/// Largest factor the amplification can move by in one ramp
+pub const MAX_AMP_CHANGE: u64 = 10;
+pub const MIN_RAMP_DURATION: i64 = 86_400;
+/// Largest sum of a weighted pool's two weights, so each side carries at
+/// least 1% of the total
+pub const MAX_TOTAL_WEIGHT: u64 = 100;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CurveType {
+    /// `x * y = k`
+    #[default]
+    ConstantProduct,
+    /// Curve-style StableSwap invariant for pegged pairs
+    StableSwap,
+    /// Balancer-style `x^w_x * y^w_y = k` with the pool's `Weights`
+    Weighted,
+}
+
+/// Relative weights of a weighted pool's two sides, e.g. 80 and 20
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct Weights {
+    pub base_token_weight: u64,
+    pub pc_token_weight: u64,
+}
+
+impl Weights {
+    pub const SIZE: usize = 8 + 8;
+
+    /// Both sides need a weight, and together at most `MAX_TOTAL_WEIGHT`
+    pub fn validate(&self) -> Result<()> {
+        let total_weight = self.base_token_weight.checked_add(self.pc_token_weight);
+        require!(
+            self.base_token_weight > 0
+                && self.pc_token_weight > 0
+                && total_weight.is_some_and(|total_weight| total_weight <= MAX_TOTAL_WEIGHT),
+            AMMError::InvalidWeights
+        );
+        Ok(())
+    }
+}
+
+/// Amplification coefficient of a StableSwap pool, moving linearly from
+/// `initial_amp` to `target_amp` between the two timestamps.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct AmpRamp {
+    pub initial_amp: u64,
+    pub target_amp: u64,
+    pub ramp_start_timestamp: i64,
+    pub ramp_stop_timestamp: i64,
+}
+
+impl AmpRamp {
+    pub const SIZE: usize = 8 + 8 + 8 + 8;
+
+    pub fn new(amp: u64, timestamp: i64) -> Result<Self> {
+        require!((MIN_AMP..=MAX_AMP).contains(&amp), AMMError::InvalidAmp);
+        Ok(Self {
+            initial_amp: amp,
+            target_amp: amp,
+            ramp_start_timestamp: timestamp,
+            ramp_stop_timestamp: timestamp,
+        })
+    }
+
+    pub fn current(&self, timestamp: i64) -> u64 {
+        if timestamp >= self.ramp_stop_timestamp {
+            return self.target_amp;
+        }
+        let elapsed = (timestamp - self.ramp_start_timestamp).max(0) as u128;
+        let duration = (self.ramp_stop_timestamp - self.ramp_start_timestamp) as u128;
+        let (initial_amp, target_amp) = (self.initial_amp as u128, self.target_amp as u128);
+        let amp = if target_amp > initial_amp {
+            initial_amp + (target_amp - initial_amp) * elapsed / duration
+        } else {
+            initial_amp - (initial_amp - target_amp) * elapsed / duration
+        };
+        amp as u64
+    }
+
+    /// Starts moving from the current amplification to `target_amp`. Each
+    /// ramp lasts at least a day and changes the amplification by at most
+    /// `MAX_AMP_CHANGE` times, so the curve can't be yanked under traders.
+    pub fn ramp(
+        &mut self,
+        target_amp: u64,
+        timestamp: i64,
+        ramp_stop_timestamp: i64,
+    ) -> Result<()> {
+        require!(
+            (MIN_AMP..=MAX_AMP).contains(&target_amp),
+            AMMError::InvalidAmp
+        );
+        require!(
+            ramp_stop_timestamp >= timestamp.saturating_add(MIN_RAMP_DURATION),
+            AMMError::InvalidAmp
+        );
+        let current_amp = self.current(timestamp);
+        require!(
+            target_amp <= current_amp.saturating_mul(MAX_AMP_CHANGE)
+                && current_amp <= target_amp.saturating_mul(MAX_AMP_CHANGE),
+            AMMError::InvalidAmp
+        );
+        *self = Self {
+            initial_amp: current_amp,
+            target_amp,
+            ramp_start_timestamp: timestamp,
+            ramp_stop_timestamp,
+        };
+        Ok(())
+    }
+
+    /// Freezes the amplification at its current value.
+    pub fn stop(&mut self, timestamp: i64) {
+        let current_amp = self.current(timestamp);
+        *self = Self {
+            initial_amp: current_amp,
+            target_amp: current_amp,
+            ramp_start_timestamp: timestamp,
+            ramp_stop_timestamp: timestamp,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weights_stay_between_one_and_ninety_nine_percent() {
+        let weights = |base_token_weight, pc_token_weight| Weights {
+            base_token_weight,
+            pc_token_weight,
+        };
+        assert!(weights(80, 20).validate().is_ok());
+        assert!(weights(1, 99).validate().is_ok());
+        assert!(weights(99, 1).validate().is_ok());
+        let err = weights(100, 0).validate().unwrap_err();
+        assert_eq!(err, AMMError::InvalidWeights.into());
+        assert!(weights(4, 1).validate().is_ok());
+        assert!(weights(1, 100).validate().is_err());
+        assert!(weights(0, 0).validate().is_err());
+        assert!(weights(800, 200).validate().is_err());
+        assert!(weights(u64::MAX, u64::MAX).validate().is_err());
+    }
+
+    #[test]
+    fn amp_moves_linearly_during_a_ramp() {
+        let mut amp = AmpRamp::new(100, 0).unwrap();
+        amp.ramp(200, 1_000, 1_000 + MIN_RAMP_DURATION).unwrap();
+        assert_eq!(amp.current(1_000), 100);
+        assert_eq!(amp.current(1_000 + MIN_RAMP_DURATION / 4), 125);
+        assert_eq!(amp.current(1_000 + MIN_RAMP_DURATION), 200);
+        assert_eq!(amp.current(i64::MAX), 200);
+
+        amp.ramp(50, 1_000 + MIN_RAMP_DURATION, 1_000 + 3 * MIN_RAMP_DURATION)
+            .unwrap();
+        assert_eq!(amp.current(1_000 + 2 * MIN_RAMP_DURATION), 125);
+        amp.stop(1_000 + 2 * MIN_RAMP_DURATION);
+        assert_eq!(amp.current(i64::MAX), 125);
+    }
+
+    #[test]
+    fn ramp_rejects_out_of_bounds_schedules() {
+        let mut amp = AmpRamp::new(100, 0).unwrap();
+        let err = amp.ramp(1_001, 0, MIN_RAMP_DURATION).unwrap_err();
+        assert_eq!(err, AMMError::InvalidAmp.into());
+        assert!(amp.ramp(9, 0, MIN_RAMP_DURATION).is_err());
+        assert!(amp.ramp(200, 0, MIN_RAMP_DURATION - 1).is_err());
+        assert!(amp.ramp(MAX_AMP + 1, 0, MIN_RAMP_DURATION).is_err());
+        assert!(AmpRamp::new(0, 0).is_err());
+        assert!(amp.ramp(1_000, 0, MIN_RAMP_DURATION).is_ok());
+    }
+}
@@ -1,14 +1,202 @@
 use anchor_lang::prelude::*;
 
-use crate::AMMError;
+use crate::{
+    AMMCalculator, AMMError, AmpRamp, Converter, CurveType, DynamicFee, FixedPoint,
+    PriceCalculator, PriceCumulatives, StableSwapCalculator, SwapDirection, TokenCalculator,
+    WeightedCalculator, Weights,
+};
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
 pub struct Fees {
     pub swap_fee_numerator: u64,
     pub swap_fee_denominator: u64,
+    /// Share of each swap fee that goes to the protocol instead of the LPs
+    pub protocol_fee_numerator: u64,
+    pub protocol_fee_denominator: u64,
+}
+
+impl Fees {
+    pub const SIZE: usize = 8 + 8 + 8 + 8;
+
+    pub fn validate(&self) -> Result<()> {
+        require!(
+            self.swap_fee_denominator > 0 && self.swap_fee_numerator < self.swap_fee_denominator,
+            AMMError::InvalidFee
+        );
+        require!(
+            self.protocol_fee_denominator > 0
+                && self.protocol_fee_numerator <= self.protocol_fee_denominator,
+            AMMError::InvalidFee
+        );
+        Ok(())
+    }
+
+    pub fn swap_fee(&self, amount_in: u64) -> Result<u64> {
+        Self::share_of(
+            amount_in,
+            self.swap_fee_numerator,
+            self.swap_fee_denominator,
+        )
+    }
+
+    pub fn protocol_fee(&self, swap_fee: u64) -> Result<u64> {
+        Self::share_of(
+            swap_fee,
+            self.protocol_fee_numerator,
+            self.protocol_fee_denominator,
+        )
+    }
+
+    fn share_of(amount: u64, numerator: u64, denominator: u64) -> Result<u64> {
+        let share = Converter::to_u128(amount)?
+            .checked_mul(Converter::to_u128(numerator)?)
+            .ok_or(AMMError::MathOverflow)?
+            .checked_div(Converter::to_u128(denominator)?)
+            .ok_or(AMMError::MathOverflow)?;
+        Ok(Converter::to_u64(share)?)
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LaunchFeeDecay {
+    #[default]
+    Linear,
+    /// The part above the normal fee halves ten times over the window
+    Exponential,
+}
+
+/// Swap fee that starts high at `open_time` and decays to the pool's normal
+/// swap fee over `duration` seconds, to keep snipers off fair launches.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct LaunchFee {
+    pub start_fee_numerator: u64,
+    pub start_fee_denominator: u64,
+    /// Zero for pools without a launch fee
+    pub duration: u32,
+    pub decay: LaunchFeeDecay,
+}
+
+impl LaunchFee {
+    pub const SIZE: usize = 8 + 8 + 4 + 1;
+    /// Swap fee denominator while the launch fee applies
+    pub const FEE_DENOMINATOR: u64 = 1_000_000_000;
+    const EXPONENTIAL_HALVINGS: u128 = 10;
+
+    pub fn validate(&self) -> Result<()> {
+        require!(
+            self.duration > 0
+                && self.start_fee_denominator > 0
+                && self.start_fee_numerator < self.start_fee_denominator,
+            AMMError::InvalidLaunchFee
+        );
+        Ok(())
+    }
+
+    /// `fees` with the swap fee charged `elapsed` seconds after opening.
+    /// Once the window is over, or if the start fee isn't above the normal
+    /// one, that is `fees` unchanged.
+    pub fn fees_at(&self, fees: &Fees, elapsed: i64) -> Result<Fees> {
+        if elapsed >= i64::from(self.duration) {
+            return Ok(fees.clone());
+        }
+        let one = FixedPoint::ONE;
+        let fee_rate = |numerator: u64, denominator: u64| -> Result<u128> {
+            Ok(u128::from(numerator)
+                .checked_mul(one)
+                .ok_or(AMMError::MathOverflow)?
+                .checked_div(denominator.into())
+                .ok_or(AMMError::MathOverflow)?)
+        };
+        let normal_fee_rate = fee_rate(fees.swap_fee_numerator, fees.swap_fee_denominator)?;
+        let start_fee_rate = fee_rate(self.start_fee_numerator, self.start_fee_denominator)?;
+        if start_fee_rate <= normal_fee_rate {
+            return Ok(fees.clone());
+        }
+        let elapsed = elapsed.max(0) as u128;
+        let duration = u128::from(self.duration);
+        let remaining_share = match self.decay {
+            LaunchFeeDecay::Linear => (duration - elapsed) * one / duration,
+            LaunchFeeDecay::Exponential => FixedPoint::pow(
+                one / 2,
+                Self::EXPONENTIAL_HALVINGS * elapsed * one / duration,
+            )?
+            .min(one),
+        };
+        let fee_rate = normal_fee_rate + (start_fee_rate - normal_fee_rate) * remaining_share / one;
+        Ok(Fees {
+            swap_fee_numerator: Converter::to_u64(
+                fee_rate / (one / u128::from(Self::FEE_DENOMINATOR)),
+            )?,
+            swap_fee_denominator: Self::FEE_DENOMINATOR,
+            ..fees.clone()
+        })
+    }
+}
+
+/// Loan taken from a pool's vaults by `flash_loan_begin`, open until the
+/// matching `flash_loan_end` later in the same transaction repays it.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct FlashLoan {
+    pub active: bool,
+    pub borrower: Pubkey,
+    pub base_token_amount: u64,
+    pub pc_token_amount: u64,
+    /// Fees owed on top of the amounts, which go to the LPs
+    pub base_token_fee: u64,
+    pub pc_token_fee: u64,
+}
+
+impl FlashLoan {
+    pub const SIZE: usize = 1 + 32 + 8 + 8 + 8 + 8;
+
+    /// Keeps other pool instructions out while the vaults are lent out
+    pub fn require_inactive(&self) -> Result<()> {
+        require!(!self.active, AMMError::FlashLoanActive);
+        Ok(())
+    }
+}
+
+/// Bits of a pool's `status`, and of the config's global status applied to
+/// every pool on top of it. A set bit halts that operation.
+pub struct PoolStatus;
+
+impl PoolStatus {
+    pub const SWAP_DISABLED: u8 = 1 << 0;
+    pub const DEPOSIT_DISABLED: u8 = 1 << 1;
+    pub const WITHDRAW_DISABLED: u8 = 1 << 2;
+    /// Halts trading and new liquidity while LPs can still exit
+    pub const WITHDRAW_ONLY: u8 = Self::SWAP_DISABLED | Self::DEPOSIT_DISABLED;
+    pub const ALL: u8 = Self::SWAP_DISABLED | Self::DEPOSIT_DISABLED | Self::WITHDRAW_DISABLED;
+
+    pub fn validate(status: u8) -> Result<()> {
+        require!(status & !Self::ALL == 0, AMMError::InvalidPoolStatus);
+        Ok(())
+    }
+
+    pub fn require_swap_enabled(status: u8) -> Result<()> {
+        require!(status & Self::SWAP_DISABLED == 0, AMMError::SwapDisabled);
+        Ok(())
+    }
+
+    pub fn require_deposit_enabled(status: u8) -> Result<()> {
+        require!(
+            status & Self::DEPOSIT_DISABLED == 0,
+            AMMError::DepositDisabled
+        );
+        Ok(())
+    }
+
+    pub fn require_withdraw_enabled(status: u8) -> Result<()> {
+        require!(
+            status & Self::WITHDRAW_DISABLED == 0,
+            AMMError::WithdrawDisabled
+        );
+        Ok(())
+    }
 }
 
 #[account]
+#[derive(Default)]
 pub struct InitalizeLiquidityAccount {
     pub base_token: Pubkey,
     pub pc_token: Pubkey,
@@ -21,12 +209,52 @@ pub struct InitalizeLiquidityAccount {
     pub base_token_vault_bump: u8,
     pub pc_token_vault_bump: u8,
     pub lp_token_mint_bump: u8,
+    pub fee_tier_index: u16,
+    /// Protocol fees sitting in the vaults but kept out of the reserves
+    pub protocol_fees_base_token: u64,
+    pub protocol_fees_pc_token: u64,
+    pub price_cumulatives: PriceCumulatives,
+    pub curve_type: CurveType,
+    /// Only used by StableSwap pools
+    pub amp: AmpRamp,
+    /// Only used by weighted pools
+    pub weights: Weights,
+    pub flash_loan: FlashLoan,
+    /// `PoolStatus` bits set by the admin
+    pub status: u8,
+    pub launch_fee: LaunchFee,
+    pub dynamic_fee: DynamicFee,
+    /// LP tokens minted to the pool's lock account at initialization
+    pub locked_lp_amount: u64,
 }
 
 impl InitalizeLiquidityAccount {
-    pub const MAX_SIZE: usize = 32 + 32 + 32 + 8 + 8 + 8 + 16 + 1 + 1 + 1 + 1;
+    pub const MAX_SIZE: usize = 32
+        + 32
+        + 32
+        + 8
+        + 8
+        + 8
+        + Fees::SIZE
+        + 1
+        + 1
+        + 1
+        + 1
+        + 2
+        + 8
+        + 8
+        + PriceCumulatives::SIZE
+        + 1
+        + AmpRamp::SIZE
+        + Weights::SIZE
+        + FlashLoan::SIZE
+        + 1
+        + LaunchFee::SIZE
+        + DynamicFee::SIZE
+        + 8;
 
-    pub fn initialize<'info>(
+    #[allow(clippy::too_many_arguments)]
+    pub fn initialize(
         &mut self,
         base_token: Pubkey,
         pc_token: Pubkey,
@@ -37,6 +265,13 @@ impl InitalizeLiquidityAccount {
         base_token_vault_bump: u8,
         pc_token_vault_bump: u8,
         lp_token_mint_bump: u8,
+        fee_tier_index: u16,
+        fees: Fees,
+        curve_type: CurveType,
+        amp: u64,
+        weights: Weights,
+        open_time: i64,
+        launch_fee: Option<LaunchFee>,
     ) -> Result<()> {
         require!(base_token_amount > 0, AMMError::InvalidBaseTokenAmount);
         require!(pc_token_amount > 0, AMMError::InvalidPcTokenAmount);
@@ -50,9 +285,480 @@ impl InitalizeLiquidityAccount {
         self.base_token_vault_bump = base_token_vault_bump;
         self.pc_token_vault_bump = pc_token_vault_bump;
         self.lp_token_mint_bump = lp_token_mint_bump;
-        self.open_time = Clock::get()?.unix_timestamp;
-        self.fees.swap_fee_numerator = 25;
-        self.fees.swap_fee_denominator = 10000;
+        let timestamp = Clock::get()?.unix_timestamp;
+        // A past or zero open time opens the pool right away
+        self.open_time = open_time.max(timestamp);
+        self.price_cumulatives = PriceCumulatives {
+            timestamp,
+            ..Default::default()
+        };
+        self.curve_type = curve_type;
+        match curve_type {
+            CurveType::ConstantProduct => {}
+            CurveType::StableSwap => self.amp = AmpRamp::new(amp, timestamp)?,
+            CurveType::Weighted => {
+                weights.validate()?;
+                self.weights = weights;
+            }
+        }
+        if let Some(launch_fee) = launch_fee {
+            launch_fee.validate()?;
+            self.launch_fee = launch_fee;
+        }
+        self.set_fees(fee_tier_index, fees)
+    }
+
+    /// Fees for a swap right now, with any launch fee still decaying and
+    /// any variable fee for the current volatility
+    pub fn swap_fees(&self) -> Result<Fees> {
+        let timestamp = Clock::get()?.unix_timestamp;
+        let fees = self
+            .launch_fee
+            .fees_at(&self.fees, timestamp.saturating_sub(self.open_time))?;
+        let volatility = self.dynamic_fee.volatility_at(self.price_q64()?, timestamp);
+        self.dynamic_fee.fees_with(&fees, &volatility)
+    }
+
+    /// Reserve ratio the dynamic fee measures volatility on, pc per base
+    fn price_q64(&self) -> Result<u128> {
+        Ok(PriceCalculator::price_q64(
+            self.pc_token_amount,
+            self.base_token_amount,
+        )?)
+    }
+
+    /// Trading, and deposits by anyone but the creator, wait for `open_time`
+    pub fn require_open(&self) -> Result<()> {
+        require_gte!(
+            Clock::get()?.unix_timestamp,
+            self.open_time,
+            AMMError::PoolNotOpen
+        );
+        Ok(())
+    }
+
+    /// Brings a pool that hasn't opened yet forward to `open_time`, or to
+    /// now if that has already passed
+    pub fn set_open_time(&mut self, open_time: i64) -> Result<()> {
+        let timestamp = Clock::get()?.unix_timestamp;
+        require!(
+            timestamp < self.open_time && open_time < self.open_time,
+            AMMError::InvalidOpenTime
+        );
+        self.open_time = open_time.max(timestamp);
+        Ok(())
+    }
+
+    pub fn set_fees(&mut self, fee_tier_index: u16, fees: Fees) -> Result<()> {
+        fees.validate()?;
+        self.fee_tier_index = fee_tier_index;
+        self.fees = fees;
+        Ok(())
+    }
+
+    pub fn current_amp(&self) -> Result<u64> {
+        Ok(self.amp.current(Clock::get()?.unix_timestamp))
+    }
+
+    /// Pool invariant on the pool's curve, `x * y` or StableSwap's `D`
+    pub fn invariant(&self) -> Result<u128> {
+        match self.curve_type {
+            CurveType::ConstantProduct => Ok(AMMCalculator::invariant(
+                self.base_token_amount,
+                self.pc_token_amount,
+            )?),
+            CurveType::StableSwap => Ok(StableSwapCalculator::compute_d(
+                self.current_amp()?,
+                self.base_token_amount.into(),
+                self.pc_token_amount.into(),
+            )?),
+            CurveType::Weighted => Ok(WeightedCalculator::invariant(
+                self.base_token_amount.into(),
+                self.pc_token_amount.into(),
+                self.weights.base_token_weight,
+                self.weights.pc_token_weight,
+            )?),
+        }
+    }
+
+    /// Weights of the swap's input and output sides
+    fn swap_weights(&self, swap_direction: SwapDirection) -> (u64, u64) {
+        match swap_direction {
+            SwapDirection::Coin2Pc => {
+                (self.weights.base_token_weight, self.weights.pc_token_weight)
+            }
+            SwapDirection::Pc2Coin => {
+                (self.weights.pc_token_weight, self.weights.base_token_weight)
+            }
+        }
+    }
+
+    /// Swap reserves as `(total_in, total_out)`
+    fn swap_reserves(&self, swap_direction: SwapDirection) -> (u128, u128) {
+        match swap_direction {
+            SwapDirection::Coin2Pc => (self.base_token_amount.into(), self.pc_token_amount.into()),
+            SwapDirection::Pc2Coin => (self.pc_token_amount.into(), self.base_token_amount.into()),
+        }
+    }
+
+    /// Output of swapping `amount_in`, already net of the swap fee, on the
+    /// pool's curve.
+    pub fn swap_amount_out(&self, amount_in: u64, swap_direction: SwapDirection) -> Result<u64> {
+        let amount_out = match self.curve_type {
+            CurveType::ConstantProduct => AMMCalculator::swap_token_base_amount_in(
+                amount_in.into(),
+                self.base_token_amount.into(),
+                self.pc_token_amount.into(),
+                swap_direction,
+            ),
+            CurveType::StableSwap => StableSwapCalculator::swap_token_base_amount_in(
+                self.current_amp()?,
+                amount_in.into(),
+                self.base_token_amount.into(),
+                self.pc_token_amount.into(),
+                swap_direction,
+            )?,
+            CurveType::Weighted => {
+                let (total_in, total_out) = self.swap_reserves(swap_direction);
+                let (weight_in, weight_out) = self.swap_weights(swap_direction);
+                WeightedCalculator::swap_token_base_amount_in(
+                    amount_in.into(),
+                    total_in,
+                    total_out,
+                    weight_in,
+                    weight_out,
+                )?
+            }
+        };
+        Ok(Converter::to_u64(amount_out)?)
+    }
+
+    /// Input, swap fee at `fees` included, the pool needs to send out
+    /// `amount_out`.
+    pub fn swap_amount_in(
+        &self,
+        amount_out: u64,
+        swap_direction: SwapDirection,
+        fees: &Fees,
+    ) -> Result<u64> {
+        let amount_in = match self.curve_type {
+            CurveType::ConstantProduct => AMMCalculator::swap_token_base_amount_out(
+                amount_out.into(),
+                self.base_token_amount.into(),
+                self.pc_token_amount.into(),
+                fees,
+                swap_direction,
+            )?,
+            CurveType::StableSwap => StableSwapCalculator::swap_token_base_amount_out(
+                self.current_amp()?,
+                amount_out.into(),
+                self.base_token_amount.into(),
+                self.pc_token_amount.into(),
+                fees,
+                swap_direction,
+            )?,
+            CurveType::Weighted => {
+                let (total_in, total_out) = self.swap_reserves(swap_direction);
+                let (weight_in, weight_out) = self.swap_weights(swap_direction);
+                WeightedCalculator::swap_token_base_amount_out(
+                    amount_out.into(),
+                    total_in,
+                    total_out,
+                    weight_in,
+                    weight_out,
+                    fees,
+                )?
+            }
+        };
+        Ok(Converter::to_u64(amount_in)?)
+    }
+
+    /// LP tokens minted for adding `base_token_amount` and `pc_token_amount`
+    /// to the reserves, given the current LP supply.
+    pub fn lp_tokens_for_deposit(
+        &self,
+        lp_token_supply: u64,
+        base_token_amount: u64,
+        pc_token_amount: u64,
+    ) -> Result<u64> {
+        match self.curve_type {
+            // The weighted invariant grows linearly with the reserves, so the
+            // proportional share is exact and skips the fixed-point error
+            CurveType::ConstantProduct | CurveType::Weighted => Ok(TokenCalculator {
+                base_token: self.base_token_amount,
+                pc_token: self.pc_token_amount,
+            }
+            .exchange_token_to_pool(lp_token_supply, base_token_amount, pc_token_amount)),
+            CurveType::StableSwap => {
+                // LP tokens track D, so mint the share of D the deposit adds
+                let amp = self.current_amp()?;
+                let d_before = StableSwapCalculator::compute_d(
+                    amp,
+                    self.base_token_amount.into(),
+                    self.pc_token_amount.into(),
+                )?;
+                let d_after = StableSwapCalculator::compute_d(
+                    amp,
+                    Converter::to_u128(self.base_token_amount)? + u128::from(base_token_amount),
+                    Converter::to_u128(self.pc_token_amount)? + u128::from(pc_token_amount),
+                )?;
+                let lp_tokens = Converter::to_u128(lp_token_supply)?
+                    .checked_mul(d_after.saturating_sub(d_before))
+                    .ok_or(AMMError::MathOverflow)?
+                    .checked_div(d_before)
+                    .ok_or(AMMError::MathOverflow)?;
+                Ok(Converter::to_u64(lp_tokens)?)
+            }
+        }
+    }
+
+    /// Moves a swap into the stored reserves and checks that the curve's
+    /// invariant did not decrease. The input stays with the LPs, except for the
+    /// protocol's cut of the fee which is set aside for the treasury.
+    pub fn apply_swap(
+        &mut self,
+        amount_in: u64,
+        amount_out: u64,
+        protocol_fee: u64,
+        swap_direction: SwapDirection,
+    ) -> Result<()> {
+        self.accumulate_prices()?;
+        // Same pre-swap price and time the swap's fee was computed with
+        self.dynamic_fee
+            .update(self.price_q64()?, Clock::get()?.unix_timestamp);
+        let invariant_before = self.invariant()?;
+        let (reserve_in, reserve_out, protocol_fees_in) = match swap_direction {
+            SwapDirection::Coin2Pc => (
+                &mut self.base_token_amount,
+                &mut self.pc_token_amount,
+                &mut self.protocol_fees_base_token,
+            ),
+            SwapDirection::Pc2Coin => (
+                &mut self.pc_token_amount,
+                &mut self.base_token_amount,
+                &mut self.protocol_fees_pc_token,
+            ),
+        };
+        *protocol_fees_in = protocol_fees_in
+            .checked_add(protocol_fee)
+            .ok_or(AMMError::MathOverflow)?;
+        *reserve_in = reserve_in
+            .checked_add(
+                amount_in
+                    .checked_sub(protocol_fee)
+                    .ok_or(AMMError::MathOverflow)?,
+            )
+            .ok_or(AMMError::MathOverflow)?;
+        *reserve_out = reserve_out
+            .checked_sub(amount_out)
+            .ok_or(AMMError::InsufficientPoolFund)?;
+
+        let invariant_after = self.invariant()?;
+        // The weighted invariant comes out of fixed-point ln and exp, so it
+        // is only exact up to their error
+        let min_invariant_after = match self.curve_type {
+            CurveType::Weighted => invariant_before
+                .saturating_sub(WeightedCalculator::invariant_error(invariant_before)),
+            _ => invariant_before,
+        };
+        require_gte!(
+            invariant_after,
+            min_invariant_after,
+            AMMError::InvariantViolated
+        );
         Ok(())
     }
+
+    /// Opens a flash loan of up to the reserves on each side. The reserves
+    /// stay as they are, since the loan is repaid within the transaction.
+    pub fn begin_flash_loan(
+        &mut self,
+        borrower: Pubkey,
+        base_token_amount: u64,
+        pc_token_amount: u64,
+        base_token_fee: u64,
+        pc_token_fee: u64,
+    ) -> Result<()> {
+        self.flash_loan.require_inactive()?;
+        require!(
+            base_token_amount > 0 || pc_token_amount > 0,
+            AMMError::InvalidAmount
+        );
+        require!(
+            base_token_amount <= self.base_token_amount && pc_token_amount <= self.pc_token_amount,
+            AMMError::InsufficientPoolFund
+        );
+        self.flash_loan = FlashLoan {
+            active: true,
+            borrower,
+            base_token_amount,
+            pc_token_amount,
+            base_token_fee,
+            pc_token_fee,
+        };
+        Ok(())
+    }
+
+    /// Closes the open flash loan once repaid and adds its fees to the
+    /// reserves.
+    pub fn end_flash_loan(&mut self, borrower: Pubkey) -> Result<FlashLoan> {
+        let flash_loan = self.flash_loan;
+        require!(
+            flash_loan.active && flash_loan.borrower == borrower,
+            AMMError::InvalidFlashLoan
+        );
+        self.flash_loan = FlashLoan::default();
+        self.add_liquidity(flash_loan.base_token_fee, flash_loan.pc_token_fee)?;
+        Ok(flash_loan)
+    }
+
+    pub fn collect_protocol_fees(&mut self) -> (u64, u64) {
+        let protocol_fees = (self.protocol_fees_base_token, self.protocol_fees_pc_token);
+        self.protocol_fees_base_token = 0;
+        self.protocol_fees_pc_token = 0;
+        protocol_fees
+    }
+
+    /// Carries the price accumulators forward at the current reserves. Has
+    /// to run before every reserve change.
+    fn accumulate_prices(&mut self) -> Result<()> {
+        self.price_cumulatives = self.observe(Clock::get()?.unix_timestamp)?;
+        Ok(())
+    }
+
+    /// Cumulative prices as of `timestamp`, including the time since the
+    /// last reserve change.
+    pub fn observe(&self, timestamp: i64) -> Result<PriceCumulatives> {
+        self.price_cumulatives
+            .accumulated(self.base_token_amount, self.pc_token_amount, timestamp)
+    }
+
+    pub fn add_liquidity(&mut self, base_token_amount: u64, pc_token_amount: u64) -> Result<()> {
+        self.accumulate_prices()?;
+        self.base_token_amount = self
+            .base_token_amount
+            .checked_add(base_token_amount)
+            .ok_or(AMMError::MathOverflow)?;
+        self.pc_token_amount = self
+            .pc_token_amount
+            .checked_add(pc_token_amount)
+            .ok_or(AMMError::MathOverflow)?;
+        Ok(())
+    }
+
+    pub fn remove_liquidity(&mut self, base_token_amount: u64, pc_token_amount: u64) -> Result<()> {
+        self.accumulate_prices()?;
+        self.base_token_amount = self
+            .base_token_amount
+            .checked_sub(base_token_amount)
+            .ok_or(AMMError::InsufficientPoolFund)?;
+        self.pc_token_amount = self
+            .pc_token_amount
+            .checked_sub(pc_token_amount)
+            .ok_or(AMMError::InsufficientPoolFund)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fees() -> Fees {
+        Fees {
+            swap_fee_numerator: 25,
+            swap_fee_denominator: 10000,
+            protocol_fee_numerator: 1,
+            protocol_fee_denominator: 5,
+        }
+    }
+
+    fn launch_fee(decay: LaunchFeeDecay) -> LaunchFee {
+        LaunchFee {
+            start_fee_numerator: 1,
+            start_fee_denominator: 2,
+            duration: 100,
+            decay,
+        }
+    }
+
+    fn fee_rate(fees: &Fees) -> u64 {
+        assert_eq!(fees.swap_fee_denominator, LaunchFee::FEE_DENOMINATOR);
+        assert_eq!(fees.protocol_fee_numerator, 1);
+        fees.swap_fee_numerator
+    }
+
+    #[test]
+    fn launch_fee_decays_linearly_to_the_pool_fee() {
+        let launch_fee = launch_fee(LaunchFeeDecay::Linear);
+        assert_eq!(
+            fee_rate(&launch_fee.fees_at(&fees(), 0).unwrap()),
+            500_000_000
+        );
+        assert_eq!(
+            fee_rate(&launch_fee.fees_at(&fees(), -5).unwrap()),
+            500_000_000
+        );
+        assert_eq!(
+            fee_rate(&launch_fee.fees_at(&fees(), 50).unwrap()),
+            251_250_000
+        );
+        let after = launch_fee.fees_at(&fees(), 100).unwrap();
+        assert_eq!(after.swap_fee_numerator, 25);
+        assert_eq!(after.swap_fee_denominator, 10000);
+    }
+
+    #[test]
+    fn launch_fee_decays_exponentially_to_the_pool_fee() {
+        let launch_fee = launch_fee(LaunchFeeDecay::Exponential);
+        let start = fee_rate(&launch_fee.fees_at(&fees(), 0).unwrap());
+        assert_eq!(start, 500_000_000);
+        // One halving of the part above the normal fee per tenth of the window
+        let halved = fee_rate(&launch_fee.fees_at(&fees(), 10).unwrap());
+        assert!(halved.abs_diff(251_250_000) <= 1);
+        let last = fee_rate(&launch_fee.fees_at(&fees(), 99).unwrap());
+        assert!(last > 2_500_000 && last < 3_100_000);
+        assert_eq!(
+            launch_fee.fees_at(&fees(), 100).unwrap().swap_fee_numerator,
+            25
+        );
+    }
+
+    #[test]
+    fn launch_fee_never_undercuts_the_pool_fee() {
+        let launch_fee = LaunchFee {
+            start_fee_numerator: 1,
+            start_fee_denominator: 1000,
+            ..launch_fee(LaunchFeeDecay::Linear)
+        };
+        assert_eq!(
+            launch_fee.fees_at(&fees(), 0).unwrap().swap_fee_numerator,
+            25
+        );
+        assert_eq!(
+            LaunchFee::default()
+                .fees_at(&fees(), 0)
+                .unwrap()
+                .swap_fee_numerator,
+            25
+        );
+    }
+
+    #[test]
+    fn launch_fee_needs_a_window_and_a_fee_below_one() {
+        assert!(launch_fee(LaunchFeeDecay::Linear).validate().is_ok());
+        let err = LaunchFee {
+            duration: 0,
+            ..launch_fee(LaunchFeeDecay::Linear)
+        }
+        .validate()
+        .unwrap_err();
+        assert_eq!(err, AMMError::InvalidLaunchFee.into());
+        assert!(LaunchFee {
+            start_fee_numerator: 2,
+            ..launch_fee(LaunchFeeDecay::Linear)
+        }
+        .validate()
+        .is_err());
+    }
 }
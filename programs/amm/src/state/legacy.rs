@@ -0,0 +1,224 @@
+use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
+
+use crate::{AMMError, Fees, InitalizeLiquidityAccount, PriceCumulatives};
+
+/// Pools migrated from the legacy layout aren't on any fee tier until the
+/// admin moves them onto one with `set_pool_fees`
+pub const NO_FEE_TIER: u16 = u16::MAX;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct LegacyFees {
+    pub swap_fee_numerator: u64,
+    pub swap_fee_denominator: u64,
+}
+
+/// Pool account as laid out by the previous release. Only `migrate_pool`
+/// reads it, so pools keep loading there while the current layout changes.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct LegacyLiquidityAccount {
+    pub base_token: Pubkey,
+    pub pc_token: Pubkey,
+    pub liquidity_provider: Pubkey,
+    pub base_token_amount: u64,
+    pub pc_token_amount: u64,
+    pub open_time: i64,
+    pub fees: LegacyFees,
+    pub bump: u8,
+    pub base_token_vault_bump: u8,
+    pub pc_token_vault_bump: u8,
+    pub lp_token_mint_bump: u8,
+}
+
+/// Pool-scoped vaults that `migrate_pool` moved the legacy vault balances into
+pub struct MigratedVaults {
+    pub base_token_vault_bump: u8,
+    pub pc_token_vault_bump: u8,
+    pub base_token_amount: u64,
+    pub pc_token_amount: u64,
+}
+
+impl LegacyLiquidityAccount {
+    pub const SIZE: usize = 32 + 32 + 32 + 8 + 8 + 8 + 8 + 8 + 1 + 1 + 1 + 1;
+
+    /// Reads a pool that is still in the legacy layout. Both layouts share
+    /// the discriminator, so the data length tells them apart.
+    pub fn try_from_account_info(account_info: &AccountInfo) -> Result<Self> {
+        require_keys_eq!(
+            *account_info.owner,
+            crate::ID,
+            ErrorCode::AccountOwnedByWrongProgram
+        );
+        let data = account_info.try_borrow_data()?;
+        require!(data.len() == 8 + Self::SIZE, AMMError::PoolAlreadyMigrated);
+        require!(
+            data.starts_with(InitalizeLiquidityAccount::DISCRIMINATOR),
+            ErrorCode::AccountDiscriminatorMismatch
+        );
+        Ok(Self::deserialize(&mut &data[8..])?)
+    }
+
+    /// The pool in the current layout: a constant product pool with the
+    /// legacy fields carried over, no fee tier, no protocol fee share, price
+    /// accumulators starting at `timestamp` and everything added since left
+    /// at its default. The reserves are set to what the vaults hold, since
+    /// legacy pools didn't keep them in sync.
+    pub fn migrate(&self, vaults: &MigratedVaults, timestamp: i64) -> InitalizeLiquidityAccount {
+        InitalizeLiquidityAccount {
+            base_token: self.base_token,
+            pc_token: self.pc_token,
+            liquidity_provider: self.liquidity_provider,
+            base_token_amount: vaults.base_token_amount,
+            pc_token_amount: vaults.pc_token_amount,
+            open_time: self.open_time,
+            fees: Fees {
+                swap_fee_numerator: self.fees.swap_fee_numerator,
+                swap_fee_denominator: self.fees.swap_fee_denominator,
+                protocol_fee_numerator: 0,
+                protocol_fee_denominator: 1,
+            },
+            bump: self.bump,
+            base_token_vault_bump: vaults.base_token_vault_bump,
+            pc_token_vault_bump: vaults.pc_token_vault_bump,
+            lp_token_mint_bump: self.lp_token_mint_bump,
+            fee_tier_index: NO_FEE_TIER,
+            price_cumulatives: PriceCumulatives {
+                timestamp,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CurveType;
+    use anchor_lang::AccountSerialize;
+
+    fn legacy_pool() -> LegacyLiquidityAccount {
+        LegacyLiquidityAccount {
+            base_token: Pubkey::new_unique(),
+            pc_token: Pubkey::new_unique(),
+            liquidity_provider: Pubkey::new_unique(),
+            base_token_amount: 1_000_000,
+            pc_token_amount: 4_000_000,
+            open_time: 1_700_000_000,
+            fees: LegacyFees {
+                swap_fee_numerator: 25,
+                swap_fee_denominator: 10_000,
+            },
+            bump: 255,
+            base_token_vault_bump: 254,
+            pc_token_vault_bump: 253,
+            lp_token_mint_bump: 252,
+        }
+    }
+
+    fn migrated_vaults() -> MigratedVaults {
+        MigratedVaults {
+            base_token_vault_bump: 251,
+            pc_token_vault_bump: 250,
+            base_token_amount: 1_200_000,
+            pc_token_amount: 3_900_000,
+        }
+    }
+
+    fn legacy_account_data(pool: &LegacyLiquidityAccount) -> Vec<u8> {
+        let mut data = InitalizeLiquidityAccount::DISCRIMINATOR.to_vec();
+        pool.serialize(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn reads_the_legacy_layout() {
+        let pool = legacy_pool();
+        let key = Pubkey::new_unique();
+        let mut lamports = 0;
+        let mut data = legacy_account_data(&pool);
+        assert_eq!(data.len(), 8 + LegacyLiquidityAccount::SIZE);
+        let account_info = AccountInfo::new(
+            &key,
+            false,
+            true,
+            &mut lamports,
+            &mut data,
+            &crate::ID,
+            false,
+            0,
+        );
+
+        let read = LegacyLiquidityAccount::try_from_account_info(&account_info).unwrap();
+        assert_eq!(read.liquidity_provider, pool.liquidity_provider);
+        assert_eq!(read.pc_token_amount, pool.pc_token_amount);
+        assert_eq!(read.lp_token_mint_bump, pool.lp_token_mint_bump);
+    }
+
+    #[test]
+    fn rejects_pools_in_the_current_layout() {
+        let key = Pubkey::new_unique();
+        let mut lamports = 0;
+        let mut data = Vec::new();
+        legacy_pool()
+            .migrate(&migrated_vaults(), 0)
+            .try_serialize(&mut data)
+            .unwrap();
+        let account_info = AccountInfo::new(
+            &key,
+            false,
+            true,
+            &mut lamports,
+            &mut data,
+            &crate::ID,
+            false,
+            0,
+        );
+
+        assert!(LegacyLiquidityAccount::try_from_account_info(&account_info).is_err());
+    }
+
+    #[test]
+    fn migrates_to_the_current_layout() {
+        let pool = legacy_pool();
+        let key = Pubkey::new_unique();
+        let mut lamports = 0;
+        let mut data = legacy_account_data(&pool);
+        let account_info = AccountInfo::new(
+            &key,
+            false,
+            true,
+            &mut lamports,
+            &mut data,
+            &crate::ID,
+            false,
+            0,
+        );
+        let vaults = migrated_vaults();
+        let migrated = LegacyLiquidityAccount::try_from_account_info(&account_info)
+            .unwrap()
+            .migrate(&vaults, 1_700_000_100);
+
+        let mut data = Vec::new();
+        migrated.try_serialize(&mut data).unwrap();
+        assert_eq!(data.len(), 8 + InitalizeLiquidityAccount::MAX_SIZE);
+        let migrated = InitalizeLiquidityAccount::try_deserialize(&mut &data[..]).unwrap();
+        assert_eq!(migrated.base_token, pool.base_token);
+        assert_eq!(migrated.liquidity_provider, pool.liquidity_provider);
+        // Reserves follow the vaults, not the stale stored amounts
+        assert_eq!(migrated.base_token_amount, vaults.base_token_amount);
+        assert_eq!(migrated.pc_token_amount, vaults.pc_token_amount);
+        assert_eq!(migrated.open_time, pool.open_time);
+        assert_eq!(migrated.fees.swap_fee_numerator, 25);
+        assert_eq!(migrated.fees.protocol_fee_numerator, 0);
+        assert!(migrated.fees.validate().is_ok());
+        assert_eq!(migrated.bump, pool.bump);
+        assert_eq!(migrated.lp_token_mint_bump, pool.lp_token_mint_bump);
+        assert_eq!(migrated.base_token_vault_bump, vaults.base_token_vault_bump);
+        assert_eq!(migrated.pc_token_vault_bump, vaults.pc_token_vault_bump);
+        assert_eq!(migrated.fee_tier_index, NO_FEE_TIER);
+        assert_eq!(migrated.price_cumulatives.timestamp, 1_700_000_100);
+        assert!(migrated.curve_type == CurveType::ConstantProduct);
+        assert_eq!(migrated.locked_lp_amount, 0);
+    }
+}
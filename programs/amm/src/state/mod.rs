@@ -1,2 +1,12 @@
+pub mod amm_config;
+pub mod concentrated;
+pub mod curve;
 pub mod initialize;
+pub mod legacy;
+pub mod oracle;
+pub use amm_config::*;
+pub use concentrated::*;
+pub use curve::*;
 pub use initialize::*;
+pub use legacy::*;
+pub use oracle::*;
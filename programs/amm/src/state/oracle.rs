@@ -0,0 +1,415 @@
+use anchor_lang::prelude::*;
+use integer_sqrt::IntegerSquareRoot;
+
+use crate::{AMMError, Converter, InitalizeLiquidityAccount, PriceCalculator};
+
+/// Uniswap v2 style cumulative prices: each one is the sum of the pool's
+/// Q64.64 price times the seconds it was in effect. The TWAP between two
+/// snapshots is their difference divided by the time between them.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct PriceCumulatives {
+    pub timestamp: i64,
+    /// Cumulative price of the base token in pc token units
+    pub base_price_cumulative: u128,
+    /// Cumulative price of the pc token in base token units
+    pub pc_price_cumulative: u128,
+    /// Cumulative `sqrt(x * y)` liquidity
+    pub liquidity_cumulative: u128,
+}
+
+impl PriceCumulatives {
+    pub const SIZE: usize = 8 + 16 + 16 + 16;
+
+    /// Cumulative prices carried forward to `timestamp` at the given
+    /// reserves, which must be the reserves since the last update.
+    pub fn accumulated(
+        &self,
+        base_token_amount: u64,
+        pc_token_amount: u64,
+        timestamp: i64,
+    ) -> Result<Self> {
+        let mut cumulatives = self.clone();
+        let elapsed = timestamp.saturating_sub(self.timestamp);
+        if elapsed <= 0 {
+            return Ok(cumulatives);
+        }
+        if base_token_amount > 0 && pc_token_amount > 0 {
+            let elapsed = elapsed as u64;
+            cumulatives.base_price_cumulative = PriceCalculator::accumulate(
+                self.base_price_cumulative,
+                PriceCalculator::price_q64(pc_token_amount, base_token_amount)?,
+                elapsed,
+            );
+            cumulatives.pc_price_cumulative = PriceCalculator::accumulate(
+                self.pc_price_cumulative,
+                PriceCalculator::price_q64(base_token_amount, pc_token_amount)?,
+                elapsed,
+            );
+            let liquidity = Converter::to_u128(base_token_amount)?
+                .checked_mul(Converter::to_u128(pc_token_amount)?)
+                .ok_or(AMMError::MathOverflow)?
+                .integer_sqrt();
+            cumulatives.liquidity_cumulative =
+                PriceCalculator::accumulate(self.liquidity_cumulative, liquidity, elapsed);
+        }
+        cumulatives.timestamp = timestamp;
+        Ok(cumulatives)
+    }
+
+    /// Cumulative values at `timestamp`, between `self` and the later
+    /// snapshot `next`. Reserves do not change between two consecutive
+    /// snapshots, so every accumulator grows linearly in between.
+    pub fn interpolate(&self, next: &Self, timestamp: i64) -> Result<Self> {
+        let span = next.timestamp.saturating_sub(self.timestamp);
+        let elapsed = timestamp.saturating_sub(self.timestamp);
+        if span <= 0 || elapsed <= 0 {
+            return Ok(self.clone());
+        }
+        let (span, elapsed) = (span as u64, elapsed as u64);
+        let interpolate = |start: u128, end: u128| -> Result<u128> {
+            let rate = PriceCalculator::twap_q64(start, end, span)?;
+            Ok(PriceCalculator::accumulate(start, rate, elapsed))
+        };
+        Ok(Self {
+            timestamp,
+            base_price_cumulative: interpolate(
+                self.base_price_cumulative,
+                next.base_price_cumulative,
+            )?,
+            pc_price_cumulative: interpolate(self.pc_price_cumulative, next.pc_price_cumulative)?,
+            liquidity_cumulative: interpolate(
+                self.liquidity_cumulative,
+                next.liquidity_cumulative,
+            )?,
+        })
+    }
+
+    /// Averages between the earlier snapshot `start` and `self`
+    pub fn twap_since(&self, start: &Self) -> Result<Twap> {
+        let elapsed = self.timestamp.saturating_sub(start.timestamp);
+        require!(elapsed > 0, AMMError::InvalidTwapWindow);
+        let elapsed = elapsed as u64;
+        Ok(Twap {
+            base_price: PriceCalculator::twap_q64(
+                start.base_price_cumulative,
+                self.base_price_cumulative,
+                elapsed,
+            )?,
+            pc_price: PriceCalculator::twap_q64(
+                start.pc_price_cumulative,
+                self.pc_price_cumulative,
+                elapsed,
+            )?,
+            liquidity: PriceCalculator::twap_q64(
+                start.liquidity_cumulative,
+                self.liquidity_cumulative,
+                elapsed,
+            )?,
+        })
+    }
+}
+
+/// Time-weighted averages over a window, as returned by `observe`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct Twap {
+    /// Average Q64.64 price of the base token in pc token units
+    pub base_price: u128,
+    /// Average Q64.64 price of the pc token in base token units
+    pub pc_price: u128,
+    /// Average `sqrt(x * y)` liquidity
+    pub liquidity: u128,
+}
+
+/// One slot of the observation ring buffer. Packed so the slots can be cast
+/// straight out of the account data whatever its alignment.
+#[zero_copy]
+#[repr(C, packed)]
+#[derive(Default)]
+pub struct Observation {
+    pub timestamp: i64,
+    pub base_price_cumulative: u128,
+    pub pc_price_cumulative: u128,
+    pub liquidity_cumulative: u128,
+}
+
+impl Observation {
+    pub const SIZE: usize = 8 + 16 + 16 + 16;
+}
+
+impl From<&PriceCumulatives> for Observation {
+    fn from(cumulatives: &PriceCumulatives) -> Self {
+        Self {
+            timestamp: cumulatives.timestamp,
+            base_price_cumulative: cumulatives.base_price_cumulative,
+            pc_price_cumulative: cumulatives.pc_price_cumulative,
+            liquidity_cumulative: cumulatives.liquidity_cumulative,
+        }
+    }
+}
+
+impl From<Observation> for PriceCumulatives {
+    fn from(observation: Observation) -> Self {
+        Self {
+            timestamp: observation.timestamp,
+            base_price_cumulative: observation.base_price_cumulative,
+            pc_price_cumulative: observation.pc_price_cumulative,
+            liquidity_cumulative: observation.liquidity_cumulative,
+        }
+    }
+}
+
+/// Header of a pool's observation ring buffer, PDA `[b"observation",
+/// amm_pda]`. `capacity` slots of `Observation` follow it in the account
+/// data. Slots `0..cardinality` are in use and `index` holds the newest.
+#[account(zero_copy)]
+pub struct ObservationState {
+    pub amm_pda: Pubkey,
+    pub index: u16,
+    pub cardinality: u16,
+    pub capacity: u16,
+    pub bump: u8,
+    pub padding: u8,
+}
+
+impl ObservationState {
+    pub const SIZE: usize = 32 + 2 + 2 + 2 + 1 + 1;
+
+    pub fn space(capacity: u16) -> usize {
+        8 + Self::SIZE + usize::from(capacity) * Observation::SIZE
+    }
+
+    /// Stores a snapshot, overwriting the newest one if it has the same
+    /// timestamp. The ring only takes in new slots once the write position
+    /// reaches its end, so growing never reorders existing observations.
+    pub fn write(&mut self, slots: &mut [Observation], cumulatives: &PriceCumulatives) {
+        let observation = Observation::from(cumulatives);
+        if self.cardinality == 0 {
+            self.index = 0;
+            self.cardinality = 1;
+        } else if { slots[usize::from(self.index)].timestamp } != observation.timestamp {
+            if self.index + 1 == self.cardinality && self.cardinality < self.capacity {
+                self.cardinality += 1;
+            }
+            self.index = (self.index + 1) % self.cardinality;
+        }
+        slots[usize::from(self.index)] = observation;
+    }
+
+    pub fn grow(&mut self, capacity: u16) -> Result<()> {
+        require_gt!(
+            capacity,
+            self.capacity,
+            AMMError::InvalidObservationCapacity
+        );
+        self.capacity = capacity;
+        Ok(())
+    }
+
+    /// Cumulative values at `timestamp`, interpolated between the stored
+    /// observations or between the newest one and the pool's `current`
+    /// values. Fails for timestamps older than the oldest observation or
+    /// later than `current`.
+    pub fn observe_at(
+        &self,
+        slots: &[Observation],
+        current: &PriceCumulatives,
+        timestamp: i64,
+    ) -> Result<PriceCumulatives> {
+        require!(
+            self.cardinality > 0 && timestamp <= current.timestamp,
+            AMMError::ObservationNotAvailable
+        );
+        let cardinality = usize::from(self.cardinality);
+        let oldest = (usize::from(self.index) + 1) % cardinality;
+        let at = |position: usize| PriceCumulatives::from(slots[(oldest + position) % cardinality]);
+
+        let newest = at(cardinality - 1);
+        if timestamp >= newest.timestamp {
+            return newest.interpolate(current, timestamp);
+        }
+        require_gte!(
+            timestamp,
+            at(0).timestamp,
+            AMMError::ObservationNotAvailable
+        );
+        // at(low) <= timestamp < at(high)
+        let (mut low, mut high) = (0, cardinality - 1);
+        while high - low > 1 {
+            let middle = (low + high) / 2;
+            if at(middle).timestamp <= timestamp {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+        at(low).interpolate(&at(high), timestamp)
+    }
+
+    /// Sets up a freshly created buffer with a single slot holding the current
+    /// values of `pool`, stored at `amm_pda`.
+    pub fn create(
+        observation_state: &AccountLoader<Self>,
+        amm_pda: Pubkey,
+        pool: &InitalizeLiquidityAccount,
+        bump: u8,
+    ) -> Result<()> {
+        {
+            let mut header = observation_state.load_init()?;
+            header.amm_pda = amm_pda;
+            header.capacity = 1;
+            header.bump = bump;
+        }
+        Self::record(observation_state, pool)
+    }
+
+    /// Records the pool's cumulative values as of now. Has to run before the
+    /// pool's reserves change.
+    pub fn record(
+        observation_state: &AccountLoader<Self>,
+        amm_pda: &InitalizeLiquidityAccount,
+    ) -> Result<()> {
+        let cumulatives = amm_pda.observe(Clock::get()?.unix_timestamp)?;
+        let account_info = observation_state.to_account_info();
+        let mut data = account_info.try_borrow_mut_data()?;
+        let (header, slots) = data[8..].split_at_mut(Self::SIZE);
+        let header: &mut Self = bytemuck::from_bytes_mut(header);
+        let slots = bytemuck::cast_slice_mut(
+            &mut slots[..usize::from(header.capacity) * Observation::SIZE],
+        );
+        header.write(slots, &cumulatives);
+        Ok(())
+    }
+
+    pub fn lookup(
+        observation_state: &AccountLoader<Self>,
+        amm_pda: &InitalizeLiquidityAccount,
+        timestamp: i64,
+    ) -> Result<PriceCumulatives> {
+        let current = amm_pda.observe(Clock::get()?.unix_timestamp)?;
+        let account_info = observation_state.to_account_info();
+        let data = account_info.try_borrow_data()?;
+        let (header, slots) = data[8..].split_at(Self::SIZE);
+        let header: &Self = bytemuck::from_bytes(header);
+        let slots =
+            bytemuck::cast_slice(&slots[..usize::from(header.capacity) * Observation::SIZE]);
+        header.observe_at(slots, &current, timestamp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn observation_state(capacity: u16) -> (ObservationState, Vec<Observation>) {
+        let state = ObservationState {
+            amm_pda: Pubkey::default(),
+            index: 0,
+            cardinality: 0,
+            capacity,
+            bump: 0,
+            padding: 0,
+        };
+        (state, vec![Observation::default(); usize::from(capacity)])
+    }
+
+    /// Snapshot of a pool whose base price is `price` pc per base
+    fn cumulatives_at(timestamp: i64, previous: &PriceCumulatives, price: u64) -> PriceCumulatives {
+        previous
+            .accumulated(1_000, 1_000 * price, timestamp)
+            .unwrap()
+    }
+
+    #[test]
+    fn ring_keeps_newest_observations_in_order() {
+        let (mut state, mut slots) = observation_state(3);
+        let mut cumulatives = PriceCumulatives::default();
+        for timestamp in 1..=5 {
+            cumulatives = cumulatives_at(timestamp, &cumulatives, 1);
+            state.write(&mut slots, &cumulatives);
+        }
+        assert_eq!(state.cardinality, 3);
+        assert_eq!(state.index, 1);
+        let timestamps: Vec<i64> = slots.iter().map(|slot| slot.timestamp).collect();
+        assert_eq!(timestamps, vec![4, 5, 3]);
+
+        // Same second overwrites instead of taking a slot
+        state.write(&mut slots, &cumulatives);
+        assert_eq!(state.index, 1);
+    }
+
+    #[test]
+    fn growing_appends_slots_after_the_newest_observation() {
+        let (mut state, mut slots) = observation_state(2);
+        let mut cumulatives = PriceCumulatives::default();
+        for timestamp in 1..=3 {
+            cumulatives = cumulatives_at(timestamp, &cumulatives, 1);
+            state.write(&mut slots, &cumulatives);
+        }
+        // [3, 2], newest at index 0
+        state.grow(4).unwrap();
+        slots.resize(4, Observation::default());
+        assert!(state.grow(4).is_err());
+
+        for timestamp in 4..=7 {
+            cumulatives = cumulatives_at(timestamp, &cumulatives, 1);
+            state.write(&mut slots, &cumulatives);
+        }
+        let timestamps: Vec<i64> = slots.iter().map(|slot| slot.timestamp).collect();
+        // Slot 1 is overwritten before the ring reaches the new slots
+        assert_eq!(timestamps, vec![7, 4, 5, 6]);
+        assert_eq!(state.cardinality, 4);
+        assert_eq!(state.index, 0);
+    }
+
+    #[test]
+    fn observe_at_interpolates_between_observations() {
+        let (mut state, mut slots) = observation_state(4);
+        // Price 2 from t=0 to t=10, then 5 until t=20, then 3 until now
+        let start = PriceCumulatives::default();
+        state.write(&mut slots, &start);
+        let first = cumulatives_at(10, &start, 2);
+        state.write(&mut slots, &first);
+        let second = cumulatives_at(20, &first, 5);
+        state.write(&mut slots, &second);
+        let current = cumulatives_at(30, &second, 3);
+
+        let price = |value: u64| u128::from(value) << 64;
+        let observed = state.observe_at(&slots, &current, 15).unwrap();
+        assert_eq!(observed.base_price_cumulative, price(2) * 10 + price(5) * 5);
+        let observed = state.observe_at(&slots, &current, 25).unwrap();
+        assert_eq!(
+            observed.base_price_cumulative,
+            price(2) * 10 + price(5) * 10 + price(3) * 5
+        );
+        let observed = state.observe_at(&slots, &current, 10).unwrap();
+        assert_eq!(observed.base_price_cumulative, price(2) * 10);
+        assert_eq!(observed.liquidity_cumulative, first.liquidity_cumulative);
+
+        assert!(state.observe_at(&slots, &current, -1).is_err());
+        assert!(state.observe_at(&slots, &current, 31).is_err());
+    }
+
+    #[test]
+    fn twap_averages_over_the_window() {
+        let (mut state, mut slots) = observation_state(4);
+        // Price 2 from t=0 to t=10, then 5 until t=20, then 3 until now
+        let start = PriceCumulatives::default();
+        state.write(&mut slots, &start);
+        let first = cumulatives_at(10, &start, 2);
+        state.write(&mut slots, &first);
+        let second = cumulatives_at(20, &first, 5);
+        state.write(&mut slots, &second);
+        let current = cumulatives_at(30, &second, 3);
+
+        let price = |value: u64| u128::from(value) << 64;
+        let window_start = state.observe_at(&slots, &current, 15).unwrap();
+        let twap = current.twap_since(&window_start).unwrap();
+        assert_eq!(twap.base_price, (price(5) * 5 + price(3) * 10) / 15);
+        let twap = current.twap_since(&second).unwrap();
+        assert_eq!(twap.base_price, price(3));
+        assert_eq!(twap.pc_price, (1u128 << 64) / 3);
+        assert_eq!(twap.liquidity, (1_000u128 * 3_000).integer_sqrt());
+
+        assert!(current.twap_since(&current).is_err());
+    }
+}
@@ -1,20 +1,258 @@
-use anchor_lang::{prelude::*, solana_program::program_pack::Pack};
-use anchor_spl::token::spl_token;
-use std::result::Result;
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    token_2022::spl_token_2022::{
+        self,
+        extension::{
+            transfer_fee::TransferFeeConfig, BaseStateWithExtensions, ExtensionType,
+            StateWithExtensions,
+        },
+    },
+    token_interface::{
+        self, CloseAccount, HarvestWithheldTokensToMint, Mint, TokenInterface, TransferChecked,
+    },
+};
 
 use crate::AMMError;
 
+pub struct SlippageValidator {}
+
+impl SlippageValidator {
+    /// Fails with both the computed output and the caller's minimum attached
+    /// to the error when the swap would pay out less than `min_amount_out`.
+    pub fn validate_min_amount_out(
+        amount_out: u64,
+        min_amount_out: u64,
+    ) -> anchor_lang::Result<()> {
+        require_gte!(amount_out, min_amount_out, AMMError::ExceededSlippage);
+        Ok(())
+    }
+
+    /// Fails with both the computed input and the caller's maximum attached
+    /// to the error when the swap would charge more than `max_amount_in`.
+    pub fn validate_max_amount_in(amount_in: u64, max_amount_in: u64) -> anchor_lang::Result<()> {
+        require_gte!(max_amount_in, amount_in, AMMError::ExceededMaxAmountIn);
+        Ok(())
+    }
+
+    /// Bounds a deposit by the LP tokens it has to mint at least and by the
+    /// most the caller allowed to be taken from the computed side.
+    pub fn validate_deposit(
+        mint_lp_amount: u64,
+        min_lp_token_amount: u64,
+        deduct_amount: u64,
+        max_amount: u64,
+    ) -> anchor_lang::Result<()> {
+        require_gte!(
+            mint_lp_amount,
+            min_lp_token_amount,
+            AMMError::DepositSlippageExceeded
+        );
+        require_gte!(max_amount, deduct_amount, AMMError::DepositSlippageExceeded);
+        Ok(())
+    }
+
+    pub fn validate_withdraw(amount_out: u64, min_amount_out: u64) -> anchor_lang::Result<()> {
+        require_gte!(
+            amount_out,
+            min_amount_out,
+            AMMError::WithdrawSlippageExceeded
+        );
+        Ok(())
+    }
+}
+
+/// Token-2022 mint extensions a pool can be created with. Anything else, such
+/// as non-transferable, confidential, transfer hook or permanent delegate
+/// mints, could lock or drain the vaults.
+const SUPPORTED_MINT_EXTENSIONS: [ExtensionType; 9] = [
+    ExtensionType::TransferFeeConfig,
+    ExtensionType::MintCloseAuthority,
+    ExtensionType::InterestBearingConfig,
+    ExtensionType::MetadataPointer,
+    ExtensionType::TokenMetadata,
+    ExtensionType::GroupPointer,
+    ExtensionType::TokenGroup,
+    ExtensionType::GroupMemberPointer,
+    ExtensionType::TokenGroupMember,
+];
+
 pub struct ProcessTokenInstructions {}
 
 impl ProcessTokenInstructions {
-    pub fn unpack_token_accounts(
-        account_info: &AccountInfo,
-        token_program_id: &Pubkey,
-    ) -> Result<spl_token::state::Account, AMMError> {
-        if account_info.owner != token_program_id {
-            return Err(AMMError::InvalidSplTokenProgram);
+    /// Rejects Token-2022 mints with extensions outside
+    /// `SUPPORTED_MINT_EXTENSIONS`. SPL Token mints have none.
+    pub fn validate_mint_extensions(mint: &InterfaceAccount<Mint>) -> anchor_lang::Result<()> {
+        let mint_info = mint.to_account_info();
+        if *mint_info.owner != spl_token_2022::ID {
+            return Ok(());
+        }
+        let mint_data = mint_info.try_borrow_data()?;
+        let mint_state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&mint_data)?;
+        for extension in mint_state.get_extension_types()? {
+            require!(
+                SUPPORTED_MINT_EXTENSIONS.contains(&extension),
+                AMMError::UnsupportedMintExtension
+            );
+        }
+        Ok(())
+    }
+
+    /// Fee withheld by the mint when `amount` is sent, so the receiver gets
+    /// `amount - fee`.
+    pub fn transfer_fee(mint: &InterfaceAccount<Mint>, amount: u64) -> anchor_lang::Result<u64> {
+        Self::with_transfer_fee_config(mint, |config, epoch| {
+            config.calculate_epoch_fee(epoch, amount)
+        })
+    }
+
+    /// Fee the mint withholds from a transfer that leaves the receiver with
+    /// exactly `post_fee_amount`, so the sender has to send
+    /// `post_fee_amount + fee`.
+    pub fn transfer_inverse_fee(
+        mint: &InterfaceAccount<Mint>,
+        post_fee_amount: u64,
+    ) -> anchor_lang::Result<u64> {
+        Self::with_transfer_fee_config(mint, |config, epoch| {
+            config.calculate_inverse_epoch_fee(epoch, post_fee_amount)
+        })
+    }
+
+    fn with_transfer_fee_config(
+        mint: &InterfaceAccount<Mint>,
+        calculate_fee: impl FnOnce(&TransferFeeConfig, u64) -> Option<u64>,
+    ) -> anchor_lang::Result<u64> {
+        let mint_info = mint.to_account_info();
+        if *mint_info.owner != spl_token_2022::ID {
+            return Ok(0);
+        }
+        let mint_data = mint_info.try_borrow_data()?;
+        let mint_state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&mint_data)?;
+        let Ok(config) = mint_state.get_extension::<TransferFeeConfig>() else {
+            return Ok(0);
+        };
+        let fee = calculate_fee(config, Clock::get()?.epoch).ok_or(AMMError::MathOverflow)?;
+        Ok(fee)
+    }
+
+    /// Moves `amount` of `mint` with `transfer_checked` through the token
+    /// program that owns the mint. Pass empty `signer_seeds` when the
+    /// authority signs the transaction itself.
+    pub fn transfer<'info>(
+        token_program: &Interface<'info, TokenInterface>,
+        mint: &InterfaceAccount<'info, Mint>,
+        from: AccountInfo<'info>,
+        to: AccountInfo<'info>,
+        authority: AccountInfo<'info>,
+        signer_seeds: &[&[&[u8]]],
+        amount: u64,
+    ) -> anchor_lang::Result<()> {
+        let cpi_context = CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            TransferChecked {
+                from,
+                mint: mint.to_account_info(),
+                to,
+                authority,
+            },
+            signer_seeds,
+        );
+        token_interface::transfer_checked(cpi_context, amount, mint.decimals)
+    }
+
+    /// Closes an emptied token account owned by `authority` and sends its
+    /// rent to `destination`. Token-2022 won't close an account still
+    /// holding withheld transfer fees, so those are moved to the mint first.
+    pub fn close<'info>(
+        token_program: &Interface<'info, TokenInterface>,
+        mint: &InterfaceAccount<'info, Mint>,
+        account: AccountInfo<'info>,
+        destination: AccountInfo<'info>,
+        authority: AccountInfo<'info>,
+        signer_seeds: &[&[&[u8]]],
+    ) -> anchor_lang::Result<()> {
+        if Self::has_transfer_fee(mint)? {
+            let cpi_context = CpiContext::new(
+                token_program.to_account_info(),
+                HarvestWithheldTokensToMint {
+                    token_program_id: token_program.to_account_info(),
+                    mint: mint.to_account_info(),
+                },
+            );
+            token_interface::harvest_withheld_tokens_to_mint(cpi_context, vec![account.clone()])?;
+        }
+        let cpi_context = CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            CloseAccount {
+                account,
+                destination,
+                authority,
+            },
+            signer_seeds,
+        );
+        token_interface::close_account(cpi_context)
+    }
+
+    fn has_transfer_fee(mint: &InterfaceAccount<Mint>) -> anchor_lang::Result<bool> {
+        let mint_info = mint.to_account_info();
+        if *mint_info.owner != spl_token_2022::ID {
+            return Ok(false);
         }
-        spl_token::state::Account::unpack(&account_info.data.borrow())
-            .map_err(|_| AMMError::InvalidAmount)
+        let mint_data = mint_info.try_borrow_data()?;
+        let mint_state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&mint_data)?;
+        Ok(mint_state.get_extension::<TransferFeeConfig>().is_ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AMMCalculator, SwapDirection};
+
+    #[test]
+    fn min_amount_out_accepts_exact_and_better_output() {
+        assert!(SlippageValidator::validate_min_amount_out(1_000, 1_000).is_ok());
+        assert!(SlippageValidator::validate_min_amount_out(1_001, 1_000).is_ok());
+        assert!(SlippageValidator::validate_min_amount_out(1, 0).is_ok());
+    }
+
+    #[test]
+    fn min_amount_out_rejects_output_one_below_minimum() {
+        let err = SlippageValidator::validate_min_amount_out(999, 1_000).unwrap_err();
+        assert_eq!(err, AMMError::ExceededSlippage.into());
+        assert!(SlippageValidator::validate_min_amount_out(0, 1).is_err());
+        assert!(SlippageValidator::validate_min_amount_out(u64::MAX - 1, u64::MAX).is_err());
+    }
+
+    #[test]
+    fn deposit_bounds_check_lp_minted_and_computed_side() {
+        assert!(SlippageValidator::validate_deposit(500, 500, 100, 100).is_ok());
+        let err = SlippageValidator::validate_deposit(499, 500, 100, 100).unwrap_err();
+        assert_eq!(err, AMMError::DepositSlippageExceeded.into());
+        let err = SlippageValidator::validate_deposit(500, 500, 101, 100).unwrap_err();
+        assert_eq!(err, AMMError::DepositSlippageExceeded.into());
+    }
+
+    #[test]
+    fn withdraw_bounds_check_each_side() {
+        assert!(SlippageValidator::validate_withdraw(1_000, 1_000).is_ok());
+        let err = SlippageValidator::validate_withdraw(999, 1_000).unwrap_err();
+        assert_eq!(err, AMMError::WithdrawSlippageExceeded.into());
+    }
+
+    #[test]
+    fn min_amount_out_boundary_on_quoted_swap() {
+        // 2 base : 1 pc pool, 0.25% fee taken out of a 0.2 base input
+        let amount_in: u64 = 200_000_000;
+        let amount_in_after_fee = amount_in - amount_in * 25 / 10_000;
+        let quoted = AMMCalculator::swap_token_base_amount_in(
+            amount_in_after_fee.into(),
+            2_000_000_000,
+            1_000_000_000,
+            SwapDirection::Coin2Pc,
+        ) as u64;
+
+        assert_eq!(quoted, 90_702_432);
+        assert!(SlippageValidator::validate_min_amount_out(quoted, quoted).is_ok());
+        assert!(SlippageValidator::validate_min_amount_out(quoted, quoted + 1).is_err());
     }
 }
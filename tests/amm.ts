@@ -2,14 +2,25 @@ import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { Amm } from "../target/types/amm";
 import {
+  calculateEpochFee,
   createAssociatedTokenAccount,
+  createInitializeMintInstruction,
+  createInitializeNonTransferableMintInstruction,
+  createInitializeTransferFeeConfigInstruction,
+  ExtensionType,
+  getMintLen,
+  getTransferFeeConfig,
+  getOrCreateAssociatedTokenAccount,
   createAssociatedTokenAccountInstruction,
   createMint,
   createSyncNativeInstruction,
   getAccount,
   getAssociatedTokenAddress,
+  getMint,
   mintTo,
   NATIVE_MINT,
+  TOKEN_2022_PROGRAM_ID,
+  TOKEN_PROGRAM_ID,
 } from "@solana/spl-token";
 import {
   LAMPORTS_PER_SOL,
@@ -34,6 +45,37 @@ describe("amm", () => {
   const lpMintDecimal: number = 9;
 
   let ammVariables = {};
+  const [ammConfig] = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("amm_config")],
+    program.programId
+  );
+  const [programData] = anchor.web3.PublicKey.findProgramAddressSync(
+    [program.programId.toBuffer()],
+    new anchor.web3.PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+  );
+  // Only weighted pools read their weights
+  function poolWeights(
+    baseTokenWeight: number = 0,
+    pcTokenWeight: number = 0
+  ) {
+    return {
+      baseTokenWeight: new anchor.BN(baseTokenWeight),
+      pcTokenWeight: new anchor.BN(pcTokenWeight),
+    };
+  }
+  function feeTier(
+    swapFeeNumerator: number,
+    swapFeeDenominator: number,
+    protocolFeeNumerator: number = 0,
+    protocolFeeDenominator: number = 10000
+  ) {
+    return {
+      swapFeeNumerator: new anchor.BN(swapFeeNumerator),
+      swapFeeDenominator: new anchor.BN(swapFeeDenominator),
+      protocolFeeNumerator: new anchor.BN(protocolFeeNumerator),
+      protocolFeeDenominator: new anchor.BN(protocolFeeDenominator),
+    };
+  }
   async function getPda(seeds) {
     const [pda, bump] = anchor.web3.PublicKey.findProgramAddressSync(
       seeds,
@@ -53,6 +95,133 @@ describe("amm", () => {
     return mint;
   }
 
+  async function generateToken2022Mint(
+    extensions: ExtensionType[],
+    initializeExtensionInstructions: (
+      mint: anchor.web3.PublicKey
+    ) => anchor.web3.TransactionInstruction[]
+  ) {
+    const mintKeypair = anchor.web3.Keypair.generate();
+    const mintLen = getMintLen(extensions);
+    const lamports = await connection.getMinimumBalanceForRentExemption(
+      mintLen
+    );
+    const transaction = new Transaction().add(
+      SystemProgram.createAccount({
+        fromPubkey: provider.wallet.publicKey,
+        newAccountPubkey: mintKeypair.publicKey,
+        space: mintLen,
+        lamports,
+        programId: TOKEN_2022_PROGRAM_ID,
+      }),
+      ...initializeExtensionInstructions(mintKeypair.publicKey),
+      createInitializeMintInstruction(
+        mintKeypair.publicKey,
+        9,
+        provider.wallet.publicKey,
+        null,
+        TOKEN_2022_PROGRAM_ID
+      )
+    );
+    await sendAndConfirmTransaction(connection, transaction, [
+      provider.wallet.payer,
+      mintKeypair,
+    ]);
+    return mintKeypair.publicKey;
+  }
+
+  async function prepareToken2022Pool(
+    ammPdaIndex: anchor.BN,
+    pcMint: anchor.web3.PublicKey,
+    userKeypair: anchor.web3.Keypair,
+    mintToAmount: number
+  ) {
+    const baseMint = await generateTokenMint();
+    const baseTokenAta = await getOrCreateAssociatedTokenAccount(
+      connection,
+      provider.wallet.payer,
+      baseMint,
+      userKeypair.publicKey
+    );
+    const pcTokenAta = await getOrCreateAssociatedTokenAccount(
+      connection,
+      provider.wallet.payer,
+      pcMint,
+      userKeypair.publicKey,
+      false,
+      undefined,
+      undefined,
+      TOKEN_2022_PROGRAM_ID
+    );
+    await mintTo(
+      connection,
+      provider.wallet.payer,
+      baseMint,
+      baseTokenAta.address,
+      provider.wallet.payer,
+      mintToAmount
+    );
+    await mintTo(
+      connection,
+      provider.wallet.payer,
+      pcMint,
+      pcTokenAta.address,
+      provider.wallet.payer,
+      mintToAmount,
+      [],
+      undefined,
+      TOKEN_2022_PROGRAM_ID
+    );
+
+    const { pda: ammPda } = await getPda([
+      Buffer.from("amm_pda"),
+      ammPdaIndex.toArrayLike(Buffer, "le", 8),
+    ]);
+    const { pda: baseTokenVault } = await getPda([
+      Buffer.from("base_token_vault"),
+      baseMint.toBuffer(),
+      ammPda.toBuffer(),
+    ]);
+    const { pda: pcTokenVault } = await getPda([
+      Buffer.from("pc_token_vault"),
+      pcMint.toBuffer(),
+      ammPda.toBuffer(),
+    ]);
+    const { pda: lpTokenMint } = await getPda([
+      Buffer.from("lp_mint"),
+      baseMint.toBuffer(),
+      pcMint.toBuffer(),
+      ammPda.toBuffer(),
+    ]);
+    const liquidityProviderLpTokenAta = await getAssociatedTokenAddress(
+      lpTokenMint,
+      userKeypair.publicKey
+    );
+    const mintAccounts = {
+      baseTokenMint: baseMint,
+      pcTokenMint: pcMint,
+      baseTokenProgram: TOKEN_PROGRAM_ID,
+      pcTokenProgram: TOKEN_2022_PROGRAM_ID,
+    };
+    return {
+      baseMint,
+      pcMint,
+      baseTokenAta: baseTokenAta.address,
+      pcTokenAta: pcTokenAta.address,
+      mintAccounts,
+      poolAccounts: {
+        ammPda: ammPda,
+        baseTokenVault: baseTokenVault,
+        pcTokenVault: pcTokenVault,
+        lpTokenMint: lpTokenMint,
+        liquidityProviderLpTokenAta: liquidityProviderLpTokenAta,
+        liquidityProviderBaseTokenAta: baseTokenAta.address,
+        liquidityProviderPcTokenAta: pcTokenAta.address,
+        ...mintAccounts,
+      },
+    };
+  }
+
   async function createBaseAndPCMint() {
     const baseMint = await generateTokenMint();
     const pcMint = await generateTokenMint();
@@ -92,7 +261,7 @@ describe("amm", () => {
     );
 
     if (!isNativeBase) {
-      await createAssociatedTokenAccount(
+      await getOrCreateAssociatedTokenAccount(
         connection,
         provider.wallet.payer,
         baseMint,
@@ -108,7 +277,7 @@ describe("amm", () => {
       );
     }
     if (!isNativePc) {
-      await createAssociatedTokenAccount(
+      await getOrCreateAssociatedTokenAccount(
         connection,
         provider.wallet.payer,
         pcMint,
@@ -133,10 +302,12 @@ describe("amm", () => {
     const { pda: baseTokenVault } = await getPda([
       Buffer.from("base_token_vault"),
       baseMint.toBuffer(),
+      ammPda.toBuffer(),
     ]);
     const { pda: pcTokenVault } = await getPda([
       Buffer.from("pc_token_vault"),
       pcMint.toBuffer(),
+      ammPda.toBuffer(),
     ]);
 
     const { pda: lpTokenMint } = await getPda([
@@ -145,11 +316,10 @@ describe("amm", () => {
       pcMint.toBuffer(),
       ammPda.toBuffer(),
     ]);
-    const { pda: liquidityProviderLpTokenAta } = await getPda([
-      Buffer.from("lp_token_ata"),
-      userKeypair.publicKey.toBuffer(),
-      ammPda.toBuffer(),
-    ]);
+    const liquidityProviderLpTokenAta = await getAssociatedTokenAddress(
+      lpTokenMint,
+      userKeypair.publicKey
+    );
     if (!(ammPdaIndex.toNumber() in ammVariables)) {
       ammVariables[ammPdaIndex.toNumber()] = {
         ammPda,
@@ -286,6 +456,54 @@ describe("amm", () => {
 
     return maxBaseAmount;
   }
+  async function quoteSwapBaseIn(
+    amountIn: anchor.BN,
+    totalSourceAmount: anchor.BN,
+    totalDestinationAmount: anchor.BN
+  ) {
+    const swapFee = amountIn.mul(new anchor.BN(25)).div(new anchor.BN(10000));
+    const amountInAfterFee = amountIn.sub(swapFee);
+
+    return amountInAfterFee
+      .mul(totalDestinationAmount)
+      .div(totalSourceAmount.add(amountInAfterFee));
+  }
+  async function assertReservesMatchVaults(
+    ammPda: anchor.web3.PublicKey,
+    baseTokenVault: anchor.web3.PublicKey,
+    pcTokenVault: anchor.web3.PublicKey,
+    baseTokenProgram: anchor.web3.PublicKey = TOKEN_PROGRAM_ID,
+    pcTokenProgram: anchor.web3.PublicKey = TOKEN_PROGRAM_ID
+  ) {
+    const ammAccount = await program.account.initalizeLiquidityAccount.fetch(
+      ammPda
+    );
+    const baseTokenVaultAccount = await getAccount(
+      provider.connection,
+      baseTokenVault,
+      undefined,
+      baseTokenProgram
+    );
+    const pcTokenVaultAccount = await getAccount(
+      provider.connection,
+      pcTokenVault,
+      undefined,
+      pcTokenProgram
+    );
+    // Vaults hold the reserves plus the protocol fees not yet collected
+    assert.equal(
+      ammAccount.baseTokenAmount
+        .add(ammAccount.protocolFeesBaseToken)
+        .toString(),
+      baseTokenVaultAccount.amount.toString(),
+      "Stored base reserve should track the base token vault"
+    );
+    assert.equal(
+      ammAccount.pcTokenAmount.add(ammAccount.protocolFeesPcToken).toString(),
+      pcTokenVaultAccount.amount.toString(),
+      "Stored pc reserve should track the pc token vault"
+    );
+  }
   before(async () => {
     const airdropSig = await provider.connection.requestAirdrop(
       liquidityProvider.publicKey,
@@ -302,6 +520,16 @@ describe("amm", () => {
       anchor.web3.LAMPORTS_PER_SOL * 100
     );
     await provider.connection.confirmTransaction(airdropSigDepositor);
+
+    await program.methods
+      .initializeAmmConfig()
+      .accounts({
+        admin: provider.wallet.publicKey,
+        ammConfig: ammConfig,
+        program: program.programId,
+        programData: programData,
+      })
+      .rpc();
   });
   it("Initialize liquidity pool", async () => {
     const ammPdaIndex = new anchor.BN(1);
@@ -334,10 +562,17 @@ describe("amm", () => {
           baseMint,
           pcMint,
           baseMintAmount,
-          pcMintAmount
+          pcMintAmount,
+          0,
+          { constantProduct: {} },
+          new anchor.BN(0),
+          poolWeights(),
+          new anchor.BN(0),
+          null
         )
         .accounts({
           liquidityProvider: liquidityProvider.publicKey,
+          ammConfig: ammConfig,
           ammPda: ammPda,
           baseTokenVault: baseTokenVault,
           pcTokenVault: pcTokenVault,
@@ -348,6 +583,8 @@ describe("amm", () => {
           liquidityProviderBaseTokenAta: liquidityProviderBaseTokenAta,
           liquidityProviderPcTokenAta: liquidityProviderPCTokenAta,
           tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
+          baseTokenProgram: TOKEN_PROGRAM_ID,
+          pcTokenProgram: TOKEN_PROGRAM_ID,
         })
         .signers([liquidityProvider])
         .rpc({ commitment: "confirmed" });
@@ -465,10 +702,17 @@ describe("amm", () => {
           baseMint,
           pcMint,
           baseMintAmount,
-          pcMintAmount
+          pcMintAmount,
+          0,
+          { constantProduct: {} },
+          new anchor.BN(0),
+          poolWeights(),
+          new anchor.BN(0),
+          null
         )
         .accounts({
           liquidityProvider: liquidityProvider.publicKey,
+          ammConfig: ammConfig,
           ammPda: ammPda,
           baseTokenVault: baseTokenVault,
           pcTokenVault: pcTokenVault,
@@ -479,6 +723,8 @@ describe("amm", () => {
           liquidityProviderBaseTokenAta: liquidityProviderBaseTokenAta,
           liquidityProviderPcTokenAta: liquidityProviderPCTokenAta,
           tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
+          baseTokenProgram: TOKEN_PROGRAM_ID,
+          pcTokenProgram: TOKEN_PROGRAM_ID,
         })
         .signers([liquidityProvider])
         .rpc({ commitment: "confirmed" });
@@ -558,29 +804,196 @@ describe("amm", () => {
     assert.equal(logEmitted, true, "Should emit event");
   });
 
-  it("Swap Coin2Pc", async () => {
-    const ammPdaIndex = new anchor.BN(2);
-    const {
-      ammPda,
-      baseTokenVault,
-      pcTokenVault,
+  it("Initialize overlapping pools sharing a mint", async () => {
+    const { baseMint: sharedBaseMint, pcMint: firstPcMint } = ammVariables[1];
+    const secondPcMint = await generateTokenMint();
+    // Pool 3 reuses pool 1's base mint, pool 4 reuses pool 1's exact pair
+    const overlappingPools = [
+      { ammPdaIndex: new anchor.BN(3), pcMint: secondPcMint },
+      { ammPdaIndex: new anchor.BN(4), pcMint: firstPcMint },
+    ];
+
+    for (const { ammPdaIndex, pcMint: overlappingPcMint } of overlappingPools) {
+      const {
+        ammPda,
+        baseTokenVault,
+        pcTokenVault,
+        lpTokenMint,
+        liquidityProviderLpTokenAta,
+        userPCTokenAta: liquidityProviderPCTokenAta,
+        userBaseTokenAta: liquidityProviderBaseTokenAta,
+        baseMint,
+        pcMint,
+        baseMintAmount,
+        pcMintAmount,
+      } = await prepareInitalizeLiquidityPool(
+        2_000_000_000,
+        1_000_000_000,
+        ammPdaIndex,
+        liquidityProvider,
+        false,
+        false,
+        sharedBaseMint,
+        overlappingPcMint
+      );
+
+      await program.methods
+        .initializeLiquidity(
+          lpMintDecimal,
+          ammPdaIndex,
+          baseMint,
+          pcMint,
+          baseMintAmount,
+          pcMintAmount,
+          0,
+          { constantProduct: {} },
+          new anchor.BN(0),
+          poolWeights(),
+          new anchor.BN(0),
+          null
+        )
+        .accounts({
+          liquidityProvider: liquidityProvider.publicKey,
+          ammConfig: ammConfig,
+          ammPda: ammPda,
+          baseTokenVault: baseTokenVault,
+          pcTokenVault: pcTokenVault,
+          lpTokenMint: lpTokenMint,
+          liquidityProviderLpTokenAta: liquidityProviderLpTokenAta,
+          baseTokenMint: baseMint,
+          pcTokenMint: pcMint,
+          liquidityProviderBaseTokenAta: liquidityProviderBaseTokenAta,
+          liquidityProviderPcTokenAta: liquidityProviderPCTokenAta,
+          tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
+          baseTokenProgram: TOKEN_PROGRAM_ID,
+          pcTokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([liquidityProvider])
+        .rpc({ commitment: "confirmed" });
+
+      assert.notEqual(
+        baseTokenVault.toString(),
+        ammVariables[1].baseTokenVault.toString(),
+        "Base token vault should be scoped to its own pool"
+      );
+      const baseTokenAccount = await getAccount(
+        provider.connection,
+        baseTokenVault
+      );
+      const pcTokenAccount = await getAccount(
+        provider.connection,
+        pcTokenVault
+      );
+      assert.equal(
+        baseTokenAccount.amount.toString(),
+        baseMintAmount.toString(),
+        "Base token mint amount doesn't match"
+      );
+      assert.equal(
+        pcTokenAccount.amount.toString(),
+        pcMintAmount.toString(),
+        "Pc token mint amount doesn't match"
+      );
+    }
+
+    const firstPoolBaseTokenAccount = await getAccount(
+      provider.connection,
+      ammVariables[1].baseTokenVault
+    );
+    assert.equal(
+      firstPoolBaseTokenAccount.amount.toString(),
+      ammVariables[1].baseMintAmount.toString(),
+      "Existing pool vault should be untouched"
+    );
+  });
+
+  it("Manage fee tiers and pool fees", async () => {
+    const ammPdaIndex = new anchor.BN(1);
+    const { ammPda } = ammVariables[ammPdaIndex.toNumber()];
+
+    try {
+      await program.methods
+        .createFeeTier(feeTier(10000, 10000))
+        .accounts({ admin: provider.wallet.publicKey, ammConfig: ammConfig })
+        .rpc();
+      assert.fail("Fee tier with numerator >= denominator should fail");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "InvalidFee");
+    }
+
+    await program.methods
+      .createFeeTier(feeTier(30, 10000))
+      .accounts({ admin: provider.wallet.publicKey, ammConfig: ammConfig })
+      .rpc();
+
+    try {
+      await program.methods
+        .setPoolFees(ammPdaIndex, 1)
+        .accounts({ admin: user.publicKey, ammConfig: ammConfig, ammPda })
+        .signers([user])
+        .rpc();
+      assert.fail("Only the admin can change pool fees");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "Unauthorized");
+    }
+
+    await program.methods
+      .setPoolFees(ammPdaIndex, 1)
+      .accounts({
+        admin: provider.wallet.publicKey,
+        ammConfig: ammConfig,
+        ammPda,
+      })
+      .rpc();
+    const ammAccount = await program.account.initalizeLiquidityAccount.fetch(
+      ammPda
+    );
+    assert.equal(ammAccount.feeTierIndex, 1);
+    assert.equal(ammAccount.fees.swapFeeNumerator.toString(), "30");
+    assert.equal(ammAccount.fees.swapFeeDenominator.toString(), "10000");
+  });
+
+  it("Collect protocol fees", async () => {
+    const ammPdaIndex = new anchor.BN(3);
+    const { ammPda, baseTokenVault, pcTokenVault, baseMint, pcMint } =
+      ammVariables[ammPdaIndex.toNumber()];
+    const treasury = anchor.web3.Keypair.generate();
+    const trader = anchor.web3.Keypair.generate();
+    const airdropSig = await provider.connection.requestAirdrop(
+      trader.publicKey,
+      anchor.web3.LAMPORTS_PER_SOL
+    );
+    await provider.connection.confirmTransaction(airdropSig);
+
+    // Tier 2 sends a fifth of each swap fee to the protocol
+    await program.methods
+      .createFeeTier(feeTier(30, 10000, 2000, 10000))
+      .accounts({ admin: provider.wallet.publicKey, ammConfig: ammConfig })
+      .rpc();
+    await program.methods
+      .setPoolFees(ammPdaIndex, 2)
+      .accounts({
+        admin: provider.wallet.publicKey,
+        ammConfig: ammConfig,
+        ammPda,
+      })
+      .rpc();
+    await program.methods
+      .setTreasury(treasury.publicKey)
+      .accounts({ admin: provider.wallet.publicKey, ammConfig: ammConfig })
+      .rpc();
 
-      baseMint,
-      pcMint,
-    } = ammVariables[ammPdaIndex.toNumber()];
     const { userBaseTokenAta, userPCTokenAta } = await prepareSwap(
-      2_000_000_000,
       1_000_000_000,
+      0,
       ammPdaIndex.toNumber(),
-      user,
-      true
+      trader
     );
-    await wrapSol(user, userBaseTokenAta);
-    const amountIn = new anchor.BN(2_000_000_00);
+    const amountIn = new anchor.BN(100_000_000);
     await program.methods
-      .swapBaseIn(ammPdaIndex, amountIn, amountIn)
+      .swapBaseIn(ammPdaIndex, amountIn, new anchor.BN(0))
       .accounts({
-        user: user.publicKey,
+        user: trader.publicKey,
         ammPda: ammPda,
         baseTokenVault: baseTokenVault,
         pcTokenVault: pcTokenVault,
@@ -588,27 +1001,86 @@ describe("amm", () => {
         userDestinationAta: userPCTokenAta,
         baseTokenMint: baseMint,
         pcTokenMint: pcMint,
-        tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
+        baseTokenProgram: TOKEN_PROGRAM_ID,
+        pcTokenProgram: TOKEN_PROGRAM_ID,
       })
-      .signers([user])
+      .signers([trader])
       .rpc();
+    await assertReservesMatchVaults(ammPda, baseTokenVault, pcTokenVault);
 
-    const userbaseTokenAccount = await getAccount(
-      provider.connection,
-      userBaseTokenAta
+    const expectedProtocolFee = amountIn
+      .mul(new anchor.BN(30))
+      .div(new anchor.BN(10000))
+      .mul(new anchor.BN(2000))
+      .div(new anchor.BN(10000));
+    let ammAccount = await program.account.initalizeLiquidityAccount.fetch(
+      ammPda
     );
-    const userpcTokenAccount = await getAccount(
-      provider.connection,
-      userPCTokenAta
+    assert.equal(
+      ammAccount.protocolFeesBaseToken.toString(),
+      expectedProtocolFee.toString()
     );
 
-    console.log("Base:  ", userbaseTokenAccount.amount.toString());
-    console.log("PC:  ", userpcTokenAccount.amount.toString());
+    const treasuryBaseTokenAccount = await getOrCreateAssociatedTokenAccount(
+      connection,
+      provider.wallet.payer,
+      baseMint,
+      treasury.publicKey
+    );
+    const treasuryPcTokenAccount = await getOrCreateAssociatedTokenAccount(
+      connection,
+      provider.wallet.payer,
+      pcMint,
+      treasury.publicKey
+    );
+    const collectAccounts = {
+      ammConfig: ammConfig,
+      ammPda: ammPda,
+      baseTokenVault: baseTokenVault,
+      pcTokenVault: pcTokenVault,
+      treasuryBaseTokenAccount: treasuryBaseTokenAccount.address,
+      treasuryPcTokenAccount: treasuryPcTokenAccount.address,
+      baseTokenMint: baseMint,
+      pcTokenMint: pcMint,
+      baseTokenProgram: TOKEN_PROGRAM_ID,
+      pcTokenProgram: TOKEN_PROGRAM_ID,
+    };
+
+    try {
+      await program.methods
+        .collectProtocolFees(ammPdaIndex)
+        .accounts({ ...collectAccounts, treasury: trader.publicKey })
+        .signers([trader])
+        .rpc();
+      assert.fail("Only the treasury can collect protocol fees");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "Unauthorized");
+    }
+
+    await program.methods
+      .collectProtocolFees(ammPdaIndex)
+      .accounts({ ...collectAccounts, treasury: treasury.publicKey })
+      .signers([treasury])
+      .rpc();
+
+    const treasuryBaseTokenAfter = await getAccount(
+      provider.connection,
+      treasuryBaseTokenAccount.address
+    );
+    assert.equal(
+      treasuryBaseTokenAfter.amount.toString(),
+      expectedProtocolFee.toString(),
+      "Treasury should receive the accrued protocol fees"
+    );
+    ammAccount = await program.account.initalizeLiquidityAccount.fetch(
+      ammPda
+    );
+    assert.equal(ammAccount.protocolFeesBaseToken.toString(), "0");
+    await assertReservesMatchVaults(ammPda, baseTokenVault, pcTokenVault);
   });
 
-  it("Despoit into pool", async () => {
+  it("Swap Coin2Pc", async () => {
     const ammPdaIndex = new anchor.BN(2);
-
     const {
       ammPda,
       baseTokenVault,
@@ -617,22 +1089,171 @@ describe("amm", () => {
       baseMint,
       pcMint,
     } = ammVariables[ammPdaIndex.toNumber()];
-    const {
-      lpTokenMint,
-      liquidityProviderLpTokenAta,
-      userPCTokenAta: liquidityProviderPCTokenAta,
-      userBaseTokenAta: liquidityProviderBaseTokenAta,
-
-      baseMintAmount,
-      pcMintAmount,
-    } = await prepareInitalizeLiquidityPool(
+    const { userBaseTokenAta, userPCTokenAta } = await prepareSwap(
       2_000_000_000,
       1_000_000_000,
-      ammPdaIndex,
-      depositor,
-      true,
-      false,
-      baseMint,
+      ammPdaIndex.toNumber(),
+      user,
+      true
+    );
+    await wrapSol(user, userBaseTokenAta);
+    const amountIn = new anchor.BN(2_000_000_00);
+    const baseTokenVaultAccount = await getAccount(
+      provider.connection,
+      baseTokenVault
+    );
+    const pcTokenVaultAccount = await getAccount(
+      provider.connection,
+      pcTokenVault
+    );
+    const expectedAmountOut = await quoteSwapBaseIn(
+      amountIn,
+      new anchor.BN(baseTokenVaultAccount.amount.toString()),
+      new anchor.BN(pcTokenVaultAccount.amount.toString())
+    );
+    const swapAccounts = {
+      user: user.publicKey,
+      ammPda: ammPda,
+      baseTokenVault: baseTokenVault,
+      pcTokenVault: pcTokenVault,
+      userSourceAta: userBaseTokenAta,
+      userDestinationAta: userPCTokenAta,
+      baseTokenMint: baseMint,
+      pcTokenMint: pcMint,
+      baseTokenProgram: TOKEN_PROGRAM_ID,
+      pcTokenProgram: TOKEN_PROGRAM_ID,
+    };
+
+    try {
+      await program.methods
+        .swapBaseIn(ammPdaIndex, amountIn, expectedAmountOut.addn(1))
+        .accounts(swapAccounts)
+        .signers([user])
+        .rpc();
+      assert.fail("Swap below min_amount_out should fail");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "ExceededSlippage");
+    }
+
+    const pcBeforeSwap = await getAccount(provider.connection, userPCTokenAta);
+    await program.methods
+      .swapBaseIn(ammPdaIndex, amountIn, expectedAmountOut)
+      .accounts(swapAccounts)
+      .signers([user])
+      .rpc();
+
+    const userbaseTokenAccount = await getAccount(
+      provider.connection,
+      userBaseTokenAta
+    );
+    const userpcTokenAccount = await getAccount(
+      provider.connection,
+      userPCTokenAta
+    );
+
+    console.log("Base:  ", userbaseTokenAccount.amount.toString());
+    console.log("PC:  ", userpcTokenAccount.amount.toString());
+    assert.equal(
+      (userpcTokenAccount.amount - pcBeforeSwap.amount).toString(),
+      expectedAmountOut.toString(),
+      "Swap output should match the quoted amount"
+    );
+    await assertReservesMatchVaults(ammPda, baseTokenVault, pcTokenVault);
+  });
+
+  it("Swap base out Pc2Coin", async () => {
+    const ammPdaIndex = new anchor.BN(2);
+    const { ammPda, baseTokenVault, pcTokenVault, baseMint, pcMint } =
+      ammVariables[ammPdaIndex.toNumber()];
+    const userBaseTokenAta = await getAssociatedTokenAddress(
+      baseMint,
+      user.publicKey
+    );
+    const userPCTokenAta = await getAssociatedTokenAddress(
+      pcMint,
+      user.publicKey
+    );
+    const amountOut = new anchor.BN(10_000_000);
+    const maxAmountIn = new anchor.BN(10_000_000);
+    const swapAccounts = {
+      user: user.publicKey,
+      ammPda: ammPda,
+      baseTokenVault: baseTokenVault,
+      pcTokenVault: pcTokenVault,
+      userSourceAta: userPCTokenAta,
+      userDestinationAta: userBaseTokenAta,
+      baseTokenMint: baseMint,
+      pcTokenMint: pcMint,
+      baseTokenProgram: TOKEN_PROGRAM_ID,
+      pcTokenProgram: TOKEN_PROGRAM_ID,
+    };
+
+    try {
+      await program.methods
+        .swapBaseOut(ammPdaIndex, amountOut, new anchor.BN(1))
+        .accounts(swapAccounts)
+        .signers([user])
+        .rpc();
+      assert.fail("Swap above max_amount_in should fail");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "ExceededMaxAmountIn");
+    }
+
+    const baseBeforeSwap = await getAccount(
+      provider.connection,
+      userBaseTokenAta
+    );
+    const pcBeforeSwap = await getAccount(provider.connection, userPCTokenAta);
+    await program.methods
+      .swapBaseOut(ammPdaIndex, amountOut, maxAmountIn)
+      .accounts(swapAccounts)
+      .signers([user])
+      .rpc();
+    const baseAfterSwap = await getAccount(
+      provider.connection,
+      userBaseTokenAta
+    );
+    const pcAfterSwap = await getAccount(provider.connection, userPCTokenAta);
+
+    assert.equal(
+      (baseAfterSwap.amount - baseBeforeSwap.amount).toString(),
+      amountOut.toString(),
+      "User should receive exactly amount_out"
+    );
+    assert.isTrue(
+      pcBeforeSwap.amount - pcAfterSwap.amount <= BigInt(maxAmountIn.toString()),
+      "User should not pay more than max_amount_in"
+    );
+    await assertReservesMatchVaults(ammPda, baseTokenVault, pcTokenVault);
+  });
+
+  it("Despoit into pool", async () => {
+    const ammPdaIndex = new anchor.BN(2);
+
+    const {
+      ammPda,
+      baseTokenVault,
+      pcTokenVault,
+
+      baseMint,
+      pcMint,
+    } = ammVariables[ammPdaIndex.toNumber()];
+    const {
+      lpTokenMint,
+      liquidityProviderLpTokenAta,
+      userPCTokenAta: liquidityProviderPCTokenAta,
+      userBaseTokenAta: liquidityProviderBaseTokenAta,
+
+      baseMintAmount,
+      pcMintAmount,
+    } = await prepareInitalizeLiquidityPool(
+      2_000_000_000,
+      1_000_000_000,
+      ammPdaIndex,
+      depositor,
+      true,
+      false,
+      baseMint,
       pcMint
     );
 
@@ -668,6 +1289,41 @@ describe("amm", () => {
       liquidityProviderPCTokenAta
     );
 
+    const depositAccounts = {
+      user: depositor.publicKey,
+      ammPda: ammPda,
+      baseTokenVault: baseTokenVault,
+      pcTokenVault: pcTokenVault,
+      lpTokenMint: lpTokenMint,
+      baseTokenMint: baseMint,
+      pcTokenMint: pcMint,
+      liquidityProviderLpTokenAta: liquidityProviderLpTokenAta,
+      liquidityProviderBaseTokenAta: liquidityProviderBaseTokenAta,
+      liquidityProviderPcTokenAta: liquidityProviderPCTokenAta,
+      baseTokenProgram: TOKEN_PROGRAM_ID,
+      pcTokenProgram: TOKEN_PROGRAM_ID,
+    };
+
+    try {
+      await program.methods
+        .deposit(
+          lpMintDecimal,
+          ammPdaIndex,
+          baseMint,
+          pcMint,
+          userBaseInput,
+          maxPcAmount,
+          base_side,
+          new anchor.BN("18446744073709551615")
+        )
+        .accounts(depositAccounts)
+        .signers([depositor])
+        .rpc();
+      assert.fail("Deposit minting less than min_lp_token_amount should fail");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "DepositSlippageExceeded");
+    }
+
     await program.methods
       .deposit(
         lpMintDecimal,
@@ -676,20 +1332,10 @@ describe("amm", () => {
         pcMint,
         userBaseInput,
         maxPcAmount,
-        base_side
+        base_side,
+        new anchor.BN(1)
       )
-      .accounts({
-        user: depositor.publicKey,
-        ammPda: ammPda,
-        baseTokenVault: baseTokenVault,
-        pcTokenVault: pcTokenVault,
-        lpTokenMint: lpTokenMint,
-        baseTokenMint: baseMint,
-        pcTokenMint: pcMint,
-        liquidityProviderLpTokenAta: liquidityProviderLpTokenAta,
-        liquidityProviderBaseTokenAta: liquidityProviderBaseTokenAta,
-        liquidityProviderPcTokenAta: liquidityProviderPCTokenAta,
-      })
+      .accounts(depositAccounts)
       .signers([depositor])
       .rpc();
     let lpAta = await getAccount(
@@ -714,6 +1360,84 @@ describe("amm", () => {
       finalPcTokenCountInVault.toString(),
       pcTokenvaultAccount.amount.toString()
     );
+    await assertReservesMatchVaults(ammPda, baseTokenVault, pcTokenVault);
+  });
+
+  it("Deposit repeatedly and as pool creator", async () => {
+    const ammPdaIndex = new anchor.BN(2);
+    const { ammPda, baseTokenVault, pcTokenVault, baseMint, pcMint } =
+      ammVariables[ammPdaIndex.toNumber()];
+
+    // The depositor already holds an LP account from the previous deposit and
+    // the creator got theirs when the pool was initialized
+    for (const depositUser of [depositor, depositor, liquidityProvider]) {
+      const {
+        lpTokenMint,
+        liquidityProviderLpTokenAta,
+        userPCTokenAta,
+        userBaseTokenAta,
+      } = await prepareInitalizeLiquidityPool(
+        0,
+        1_000_000_000,
+        ammPdaIndex,
+        depositUser,
+        true,
+        false,
+        baseMint,
+        pcMint
+      );
+      const userBaseInput = new anchor.BN(1_000_000_00);
+      const ammAccount = await program.account.initalizeLiquidityAccount.fetch(
+        ammPda
+      );
+      const maxPcAmount = await exchangeBaseToPc(
+        userBaseInput,
+        ammAccount.baseTokenAmount,
+        ammAccount.pcTokenAmount
+      );
+      const lpBeforeDeposit = await getAccount(
+        provider.connection,
+        liquidityProviderLpTokenAta
+      );
+
+      await program.methods
+        .deposit(
+          lpMintDecimal,
+          ammPdaIndex,
+          baseMint,
+          pcMint,
+          userBaseInput,
+          maxPcAmount,
+          0,
+          new anchor.BN(1)
+        )
+        .accounts({
+          user: depositUser.publicKey,
+          ammPda: ammPda,
+          baseTokenVault: baseTokenVault,
+          pcTokenVault: pcTokenVault,
+          lpTokenMint: lpTokenMint,
+          baseTokenMint: baseMint,
+          pcTokenMint: pcMint,
+          liquidityProviderLpTokenAta: liquidityProviderLpTokenAta,
+          liquidityProviderBaseTokenAta: userBaseTokenAta,
+          liquidityProviderPcTokenAta: userPCTokenAta,
+          baseTokenProgram: TOKEN_PROGRAM_ID,
+          pcTokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([depositUser])
+        .rpc();
+
+      const lpAfterDeposit = await getAccount(
+        provider.connection,
+        liquidityProviderLpTokenAta
+      );
+      assert.isTrue(
+        lpAfterDeposit.amount > lpBeforeDeposit.amount,
+        "Every deposit should mint LP tokens into the same LP account"
+      );
+    }
+    await assertReservesMatchVaults(ammPda, baseTokenVault, pcTokenVault);
   });
 
   it("withdraw", async () => {
@@ -743,25 +1467,2197 @@ describe("amm", () => {
       .mul(new anchor.BN(50))
       .div(new anchor.BN(100));
     console.log(maxLpAmount.toString());
+    const ammAccount = await program.account.initalizeLiquidityAccount.fetch(
+      ammPda
+    );
+    const lpMintAccount = await getMint(provider.connection, lpTokenMint);
+    const lpSupply = new anchor.BN(lpMintAccount.supply.toString());
+    const minBaseAmount = maxLpAmount
+      .mul(ammAccount.baseTokenAmount)
+      .div(lpSupply);
+    const minPcAmount = maxLpAmount.mul(ammAccount.pcTokenAmount).div(lpSupply);
+    const withdrawAccounts = {
+      user: liquidityProvider.publicKey,
+      ammPda: ammPda,
+      baseTokenVault: baseTokenVault,
+      pcTokenVault: pcTokenVault,
+      lpTokenMint: lpTokenMint,
+      liquidityProviderLpTokenAta: liquidityProviderLpTokenAta,
+      liquidityProviderBaseTokenAta: userBaseTokenAta,
+      liquidityProviderPcTokenAta: userPCTokenAta,
+      baseTokenMint: baseMint,
+      pcTokenMint: pcMint,
+      tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
+      baseTokenProgram: TOKEN_PROGRAM_ID,
+      pcTokenProgram: TOKEN_PROGRAM_ID,
+    };
+
+    try {
+      await program.methods
+        .withdraw(
+          lpMintDecimal,
+          ammPdaIndex,
+          maxLpAmount,
+          minBaseAmount.addn(1),
+          minPcAmount
+        )
+        .accounts(withdrawAccounts)
+        .signers([liquidityProvider])
+        .rpc();
+      assert.fail("Withdraw below min_base_token_amount should fail");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "WithdrawSlippageExceeded");
+    }
+
+    await program.methods
+      .withdraw(
+        lpMintDecimal,
+        ammPdaIndex,
+        maxLpAmount,
+        minBaseAmount,
+        minPcAmount
+      )
+      .accounts(withdrawAccounts)
+      .signers([liquidityProvider])
+      .rpc();
+
+    lpAta = await getAccount(provider.connection, liquidityProviderLpTokenAta);
+    console.log(lpAta.amount.toString());
+    await assertReservesMatchVaults(ammPda, baseTokenVault, pcTokenVault);
+  });
+
+  it("Mixed SPL Token and Token-2022 pool", async () => {
+    const ammPdaIndex = new anchor.BN(5);
+    const pcMint = await createMint(
+      connection,
+      provider.wallet.payer,
+      provider.wallet.publicKey,
+      null,
+      6,
+      undefined,
+      undefined,
+      TOKEN_2022_PROGRAM_ID
+    );
+    const { baseMint, baseTokenAta, pcTokenAta, mintAccounts, poolAccounts } =
+      await prepareToken2022Pool(
+        ammPdaIndex,
+        pcMint,
+        liquidityProvider,
+        4_000_000_000
+      );
+    const {
+      ammPda,
+      baseTokenVault,
+      pcTokenVault,
+      liquidityProviderLpTokenAta,
+    } = poolAccounts;
+
+    await program.methods
+      .initializeLiquidity(
+        lpMintDecimal,
+        ammPdaIndex,
+        baseMint,
+        pcMint,
+        new anchor.BN(2_000_000_000),
+        new anchor.BN(1_000_000_000),
+        0,
+        { constantProduct: {} },
+        new anchor.BN(0),
+        poolWeights(),
+        new anchor.BN(0),
+        null
+      )
+      .accounts({
+        liquidityProvider: liquidityProvider.publicKey,
+        ammConfig: ammConfig,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        ...poolAccounts,
+      })
+      .signers([liquidityProvider])
+      .rpc({ commitment: "confirmed" });
+    await assertReservesMatchVaults(
+      ammPda,
+      baseTokenVault,
+      pcTokenVault,
+      TOKEN_PROGRAM_ID,
+      TOKEN_2022_PROGRAM_ID
+    );
+
+    // Swap the Token-2022 side in and the SPL Token side back out
+    const amountIn = new anchor.BN(100_000_000);
+    const ammAccount = await program.account.initalizeLiquidityAccount.fetch(
+      ammPda
+    );
+    const expectedAmountOut = await quoteSwapBaseIn(
+      amountIn,
+      ammAccount.pcTokenAmount,
+      ammAccount.baseTokenAmount
+    );
+    const baseBeforeSwap = await getAccount(connection, baseTokenAta);
     await program.methods
-      .withdraw(lpMintDecimal, ammPdaIndex, maxLpAmount)
+      .swapBaseIn(ammPdaIndex, amountIn, expectedAmountOut)
       .accounts({
         user: liquidityProvider.publicKey,
         ammPda: ammPda,
         baseTokenVault: baseTokenVault,
         pcTokenVault: pcTokenVault,
-        lpTokenMint: lpTokenMint,
-        liquidityProviderLpTokenAta: liquidityProviderLpTokenAta,
-        liquidityProviderBaseTokenAta: userBaseTokenAta,
-        liquidityProviderPcTokenAta: userPCTokenAta,
-        baseTokenMint: baseMint,
-        pcTokenMint: pcMint,
-        tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
+        userSourceAta: pcTokenAta,
+        userDestinationAta: baseTokenAta,
+        ...mintAccounts,
       })
       .signers([liquidityProvider])
       .rpc();
+    const baseAfterSwap = await getAccount(connection, baseTokenAta);
+    assert.equal(
+      (baseAfterSwap.amount - baseBeforeSwap.amount).toString(),
+      expectedAmountOut.toString(),
+      "Swap output should match the quoted amount"
+    );
+
+    await program.methods
+      .deposit(
+        lpMintDecimal,
+        ammPdaIndex,
+        baseMint,
+        pcMint,
+        new anchor.BN(100_000_000),
+        new anchor.BN(1_000_000_000),
+        0,
+        new anchor.BN(1)
+      )
+      .accounts({ user: liquidityProvider.publicKey, ...poolAccounts })
+      .signers([liquidityProvider])
+      .rpc();
 
-    lpAta = await getAccount(provider.connection, liquidityProviderLpTokenAta);
-    console.log(lpAta.amount.toString());
+    const lpAta = await getAccount(connection, liquidityProviderLpTokenAta);
+    await program.methods
+      .withdraw(
+        lpMintDecimal,
+        ammPdaIndex,
+        new anchor.BN(lpAta.amount.toString()).divn(2),
+        new anchor.BN(0),
+        new anchor.BN(0)
+      )
+      .accounts({ user: liquidityProvider.publicKey, ...poolAccounts })
+      .signers([liquidityProvider])
+      .rpc();
+    await assertReservesMatchVaults(
+      ammPda,
+      baseTokenVault,
+      pcTokenVault,
+      TOKEN_PROGRAM_ID,
+      TOKEN_2022_PROGRAM_ID
+    );
+  });
+
+  it("Transfer fee mint pool", async () => {
+    const ammPdaIndex = new anchor.BN(6);
+    // 1% transfer fee on the pc mint
+    const transferFeeBasisPoints = 100;
+    const pcMint = await generateToken2022Mint(
+      [ExtensionType.TransferFeeConfig],
+      (mint) => [
+        createInitializeTransferFeeConfigInstruction(
+          mint,
+          provider.wallet.publicKey,
+          provider.wallet.publicKey,
+          transferFeeBasisPoints,
+          BigInt(1_000_000_000),
+          TOKEN_2022_PROGRAM_ID
+        ),
+      ]
+    );
+    const { baseTokenAta, pcTokenAta, mintAccounts, poolAccounts } =
+      await prepareToken2022Pool(
+        ammPdaIndex,
+        pcMint,
+        liquidityProvider,
+        4_000_000_000
+      );
+    const { ammPda, baseTokenVault, pcTokenVault } = poolAccounts;
+    const pcMintAccount = await getMint(
+      connection,
+      pcMint,
+      undefined,
+      TOKEN_2022_PROGRAM_ID
+    );
+    const transferFeeConfig = getTransferFeeConfig(pcMintAccount);
+    const epoch = BigInt((await connection.getEpochInfo()).epoch);
+    const pcTransferFee = (amount: anchor.BN) =>
+      new anchor.BN(
+        calculateEpochFee(
+          transferFeeConfig,
+          epoch,
+          BigInt(amount.toString())
+        ).toString()
+      );
+
+    const pcAmount = new anchor.BN(1_000_000_000);
+    await program.methods
+      .initializeLiquidity(
+        lpMintDecimal,
+        ammPdaIndex,
+        mintAccounts.baseTokenMint,
+        pcMint,
+        new anchor.BN(2_000_000_000),
+        pcAmount,
+        0,
+        { constantProduct: {} },
+        new anchor.BN(0),
+        poolWeights(),
+        new anchor.BN(0),
+        null
+      )
+      .accounts({
+        liquidityProvider: liquidityProvider.publicKey,
+        ammConfig: ammConfig,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        ...poolAccounts,
+      })
+      .signers([liquidityProvider])
+      .rpc({ commitment: "confirmed" });
+    let ammAccount = await program.account.initalizeLiquidityAccount.fetch(
+      ammPda
+    );
+    assert.equal(
+      ammAccount.pcTokenAmount.toString(),
+      pcAmount.sub(pcTransferFee(pcAmount)).toString(),
+      "Pc reserve should exclude the transfer fee"
+    );
+
+    const swapAccounts = {
+      user: liquidityProvider.publicKey,
+      ammPda: ammPda,
+      baseTokenVault: baseTokenVault,
+      pcTokenVault: pcTokenVault,
+      ...mintAccounts,
+    };
+
+    // Pc in: the curve only sees what reaches the vault
+    const pcIn = new anchor.BN(100_000_000);
+    const expectedBaseOut = await quoteSwapBaseIn(
+      pcIn.sub(pcTransferFee(pcIn)),
+      ammAccount.pcTokenAmount,
+      ammAccount.baseTokenAmount
+    );
+    const baseBeforeSwap = await getAccount(connection, baseTokenAta);
+    await program.methods
+      .swapBaseIn(ammPdaIndex, pcIn, expectedBaseOut)
+      .accounts({
+        ...swapAccounts,
+        userSourceAta: pcTokenAta,
+        userDestinationAta: baseTokenAta,
+      })
+      .signers([liquidityProvider])
+      .rpc();
+    const baseAfterSwap = await getAccount(connection, baseTokenAta);
+    assert.equal(
+      (baseAfterSwap.amount - baseBeforeSwap.amount).toString(),
+      expectedBaseOut.toString()
+    );
+
+    // Pc out: min_amount_out is checked against what the user receives
+    const baseIn = new anchor.BN(100_000_000);
+    ammAccount = await program.account.initalizeLiquidityAccount.fetch(ammPda);
+    const pcOutSent = await quoteSwapBaseIn(
+      baseIn,
+      ammAccount.baseTokenAmount,
+      ammAccount.pcTokenAmount
+    );
+    const expectedPcOut = pcOutSent.sub(pcTransferFee(pcOutSent));
+    try {
+      await program.methods
+        .swapBaseIn(ammPdaIndex, baseIn, pcOutSent)
+        .accounts({
+          ...swapAccounts,
+          userSourceAta: baseTokenAta,
+          userDestinationAta: pcTokenAta,
+        })
+        .signers([liquidityProvider])
+        .rpc();
+      assert.fail("Slippage should account for the transfer fee");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "ExceededSlippage");
+    }
+    const pcBeforeSwap = await getAccount(
+      connection,
+      pcTokenAta,
+      undefined,
+      TOKEN_2022_PROGRAM_ID
+    );
+    await program.methods
+      .swapBaseIn(ammPdaIndex, baseIn, expectedPcOut)
+      .accounts({
+        ...swapAccounts,
+        userSourceAta: baseTokenAta,
+        userDestinationAta: pcTokenAta,
+      })
+      .signers([liquidityProvider])
+      .rpc();
+    const pcAfterSwap = await getAccount(
+      connection,
+      pcTokenAta,
+      undefined,
+      TOKEN_2022_PROGRAM_ID
+    );
+    assert.equal(
+      (pcAfterSwap.amount - pcBeforeSwap.amount).toString(),
+      expectedPcOut.toString()
+    );
+
+    await program.methods
+      .deposit(
+        lpMintDecimal,
+        ammPdaIndex,
+        mintAccounts.baseTokenMint,
+        pcMint,
+        new anchor.BN(100_000_000),
+        new anchor.BN(1_000_000_000),
+        0,
+        new anchor.BN(1)
+      )
+      .accounts({ user: liquidityProvider.publicKey, ...poolAccounts })
+      .signers([liquidityProvider])
+      .rpc();
+    await assertReservesMatchVaults(
+      ammPda,
+      baseTokenVault,
+      pcTokenVault,
+      TOKEN_PROGRAM_ID,
+      TOKEN_2022_PROGRAM_ID
+    );
+  });
+
+  it("Reject unsupported Token-2022 extensions", async () => {
+    const ammPdaIndex = new anchor.BN(7);
+    const pcMint = await generateToken2022Mint(
+      [ExtensionType.NonTransferable],
+      (mint) => [
+        createInitializeNonTransferableMintInstruction(
+          mint,
+          TOKEN_2022_PROGRAM_ID
+        ),
+      ]
+    );
+    const { mintAccounts, poolAccounts } = await prepareToken2022Pool(
+      ammPdaIndex,
+      pcMint,
+      liquidityProvider,
+      4_000_000_000
+    );
+
+    try {
+      await program.methods
+        .initializeLiquidity(
+          lpMintDecimal,
+          ammPdaIndex,
+          mintAccounts.baseTokenMint,
+          pcMint,
+          new anchor.BN(2_000_000_000),
+          new anchor.BN(1_000_000_000),
+          0,
+          { constantProduct: {} },
+          new anchor.BN(0),
+          poolWeights(),
+          new anchor.BN(0),
+          null
+        )
+        .accounts({
+          liquidityProvider: liquidityProvider.publicKey,
+          ammConfig: ammConfig,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          ...poolAccounts,
+        })
+        .signers([liquidityProvider])
+        .rpc();
+      assert.fail("Non-transferable mints should be rejected");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "UnsupportedMintExtension");
+    }
+  });
+
+  it("Observe TWAP", async () => {
+    const ammPdaIndex = new anchor.BN(2);
+    const { ammPda } = ammVariables[ammPdaIndex.toNumber()];
+    const { pda: observationState } = await getPda([
+      Buffer.from("observation"),
+      ammPda.toBuffer(),
+    ]);
+    await new Promise((resolve) => setTimeout(resolve, 2000));
+
+    // Nothing traded over the window, so the TWAP is the current spot price
+    const twap = await program.methods
+      .observe(ammPdaIndex, 1)
+      .accounts({ ammPda, observationState })
+      .view();
+    const ammAccount = await program.account.initalizeLiquidityAccount.fetch(
+      ammPda
+    );
+    assert.equal(
+      twap.basePrice.toString(),
+      ammAccount.pcTokenAmount
+        .shln(64)
+        .div(ammAccount.baseTokenAmount)
+        .toString()
+    );
+    assert.equal(
+      twap.pcPrice.toString(),
+      ammAccount.baseTokenAmount
+        .shln(64)
+        .div(ammAccount.pcTokenAmount)
+        .toString()
+    );
+
+    try {
+      await program.methods
+        .observe(ammPdaIndex, 0)
+        .accounts({ ammPda, observationState })
+        .view();
+      assert.fail("An empty window should fail");
+    } catch (err) {
+      assert.include(err.toString(), "InvalidTwapWindow");
+    }
+  });
+
+  it("Observation history", async () => {
+    const ammPdaIndex = new anchor.BN(1);
+    const { ammPda, baseTokenVault, pcTokenVault, baseMint, pcMint } =
+      ammVariables[ammPdaIndex.toNumber()];
+    const trader = anchor.web3.Keypair.generate();
+    const { userBaseTokenAta, userPCTokenAta } = await prepareSwap(
+      1_000_000_000,
+      0,
+      ammPdaIndex.toNumber(),
+      trader
+    );
+    const { pda: observationState } = await getPda([
+      Buffer.from("observation"),
+      ammPda.toBuffer(),
+    ]);
+    const sleep = (ms: number) =>
+      new Promise((resolve) => setTimeout(resolve, ms));
+
+    await program.methods
+      .growObservations(ammPdaIndex, 8)
+      .accounts({ payer: provider.wallet.publicKey, ammPda, observationState })
+      .rpc();
+    let header = await program.account.observationState.fetch(
+      observationState
+    );
+    assert.equal(header.capacity, 8);
+
+    const swap = () =>
+      program.methods
+        .swapBaseIn(ammPdaIndex, new anchor.BN(10_000_000), new anchor.BN(1))
+        .accounts({
+          user: trader.publicKey,
+          ammPda: ammPda,
+          observationState: observationState,
+          baseTokenVault: baseTokenVault,
+          pcTokenVault: pcTokenVault,
+          userSourceAta: userBaseTokenAta,
+          userDestinationAta: userPCTokenAta,
+          baseTokenMint: baseMint,
+          pcTokenMint: pcMint,
+          baseTokenProgram: TOKEN_PROGRAM_ID,
+          pcTokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([trader])
+        .rpc();
+
+    await swap();
+    const between = await program.account.initalizeLiquidityAccount.fetch(
+      ammPda
+    );
+    await sleep(2000);
+    await swap();
+    await sleep(2000);
+
+    header = await program.account.observationState.fetch(observationState);
+    assert.isAtLeast(header.cardinality, 3);
+
+    // The first swap stored the pool's cumulative values as of that swap,
+    // and the buffer gives them back exactly
+    const historical = await program.methods
+      .observeAt(ammPdaIndex, between.priceCumulatives.timestamp)
+      .accounts({ ammPda, observationState })
+      .view();
+    for (const field of [
+      "timestamp",
+      "basePriceCumulative",
+      "pcPriceCumulative",
+      "liquidityCumulative",
+    ]) {
+      assert.equal(
+        historical[field].toString(),
+        between.priceCumulatives[field].toString()
+      );
+    }
+
+    try {
+      await program.methods
+        .observeAt(ammPdaIndex, new anchor.BN(0))
+        .accounts({ ammPda, observationState })
+        .view();
+      assert.fail("Timestamps before the oldest observation should fail");
+    } catch (err) {
+      assert.include(err.toString(), "ObservationNotAvailable");
+    }
+  });
+
+  it("Swap route through two pools", async () => {
+    // Pool 3 trades its pc mint for the shared base mint, which pool 1 then
+    // trades for its own pc mint
+    const firstPool = ammVariables[3];
+    const secondPool = ammVariables[1];
+    const trader = anchor.web3.Keypair.generate();
+    const { userPCTokenAta: userSourceAta } = await prepareSwap(
+      0,
+      1_000_000_000,
+      3,
+      trader
+    );
+    const userDestinationAta = await createAssociatedTokenAccount(
+      connection,
+      provider.wallet.payer,
+      secondPool.pcMint,
+      trader.publicKey
+    );
+    const observationStateOf = async (ammPda: anchor.web3.PublicKey) =>
+      (await getPda([Buffer.from("observation"), ammPda.toBuffer()])).pda;
+    const hopAccounts = async (pool, sourceVault, destinationVault, mints) =>
+      [
+        pool.ammPda,
+        await observationStateOf(pool.ammPda),
+        sourceVault,
+        destinationVault,
+        ...mints,
+        TOKEN_PROGRAM_ID,
+        TOKEN_PROGRAM_ID,
+      ].map((pubkey, position) => ({
+        pubkey,
+        isSigner: false,
+        isWritable: position < 4,
+      }));
+    const remainingAccounts = [
+      ...(await hopAccounts(
+        firstPool,
+        firstPool.pcTokenVault,
+        firstPool.baseTokenVault,
+        [firstPool.pcMint, firstPool.baseMint]
+      )),
+      ...(await hopAccounts(
+        secondPool,
+        secondPool.baseTokenVault,
+        secondPool.pcTokenVault,
+        [secondPool.baseMint, secondPool.pcMint]
+      )),
+    ];
+    const route = (minAmountOut: anchor.BN) =>
+      program.methods
+        .swapRoute(new anchor.BN(10_000_000), minAmountOut, [
+          new anchor.BN(3),
+          new anchor.BN(1),
+        ])
+        .accounts({
+          user: trader.publicKey,
+          userSourceAta,
+          userDestinationAta,
+        })
+        .remainingAccounts(remainingAccounts)
+        .signers([trader])
+        .rpc({ commitment: "confirmed" });
+
+    try {
+      await route(new anchor.BN("18446744073709551615"));
+      assert.fail("Route should fail below the minimum amount out");
+    } catch (err) {
+      assert.include(err.toString(), "ExceededSlippage");
+    }
+
+    const firstPoolBefore =
+      await program.account.initalizeLiquidityAccount.fetch(firstPool.ammPda);
+    const secondPoolBefore =
+      await program.account.initalizeLiquidityAccount.fetch(secondPool.ammPda);
+    await route(new anchor.BN(1));
+    const firstPoolAfter =
+      await program.account.initalizeLiquidityAccount.fetch(firstPool.ammPda);
+    const secondPoolAfter =
+      await program.account.initalizeLiquidityAccount.fetch(secondPool.ammPda);
+
+    // The shared base mint moves vault to vault without touching the user
+    assert.equal(
+      firstPoolBefore.baseTokenAmount
+        .sub(firstPoolAfter.baseTokenAmount)
+        .toString(),
+      secondPoolAfter.baseTokenAmount
+        .sub(secondPoolBefore.baseTokenAmount)
+        .toString()
+    );
+    const userDestination = await getAccount(
+      provider.connection,
+      userDestinationAta
+    );
+    assert.equal(
+      userDestination.amount.toString(),
+      secondPoolBefore.pcTokenAmount
+        .sub(secondPoolAfter.pcTokenAmount)
+        .toString()
+    );
+    for (const pool of [firstPool, secondPool]) {
+      await assertReservesMatchVaults(
+        pool.ammPda,
+        pool.baseTokenVault,
+        pool.pcTokenVault
+      );
+    }
+  });
+
+  it("StableSwap pool", async () => {
+    const ammPdaIndex = new anchor.BN(8);
+    const {
+      ammPda,
+      baseTokenVault,
+      pcTokenVault,
+      lpTokenMint,
+      liquidityProviderLpTokenAta,
+      userPCTokenAta: liquidityProviderPCTokenAta,
+      userBaseTokenAta: liquidityProviderBaseTokenAta,
+      baseMint,
+      pcMint,
+      baseMintAmount,
+      pcMintAmount,
+    } = await prepareInitalizeLiquidityPool(
+      1_000_000_000,
+      1_000_000_000,
+      ammPdaIndex,
+      liquidityProvider
+    );
+    const { pda: observationState } = await getPda([
+      Buffer.from("observation"),
+      ammPda.toBuffer(),
+    ]);
+
+    await program.methods
+      .initializeLiquidity(
+        lpMintDecimal,
+        ammPdaIndex,
+        baseMint,
+        pcMint,
+        baseMintAmount,
+        pcMintAmount,
+        0,
+        { stableSwap: {} },
+        new anchor.BN(100),
+        poolWeights(),
+        new anchor.BN(0),
+        null
+      )
+      .accounts({
+        liquidityProvider: liquidityProvider.publicKey,
+        ammConfig: ammConfig,
+        ammPda: ammPda,
+        baseTokenVault: baseTokenVault,
+        pcTokenVault: pcTokenVault,
+        lpTokenMint: lpTokenMint,
+        liquidityProviderLpTokenAta: liquidityProviderLpTokenAta,
+        baseTokenMint: baseMint,
+        pcTokenMint: pcMint,
+        liquidityProviderBaseTokenAta: liquidityProviderBaseTokenAta,
+        liquidityProviderPcTokenAta: liquidityProviderPCTokenAta,
+        tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
+        baseTokenProgram: TOKEN_PROGRAM_ID,
+        pcTokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([liquidityProvider])
+      .rpc({ commitment: "confirmed" });
+
+    const trader = anchor.web3.Keypair.generate();
+    const { userBaseTokenAta, userPCTokenAta } = await prepareSwap(
+      100_000_000,
+      0,
+      ammPdaIndex.toNumber(),
+      trader
+    );
+    const amountIn = new anchor.BN(100_000_000);
+    const constantProductQuote = await quoteSwapBaseIn(
+      amountIn,
+      baseMintAmount,
+      pcMintAmount
+    );
+    await program.methods
+      .swapBaseIn(ammPdaIndex, amountIn, constantProductQuote)
+      .accounts({
+        user: trader.publicKey,
+        ammPda: ammPda,
+        observationState: observationState,
+        baseTokenVault: baseTokenVault,
+        pcTokenVault: pcTokenVault,
+        userSourceAta: userBaseTokenAta,
+        userDestinationAta: userPCTokenAta,
+        baseTokenMint: baseMint,
+        pcTokenMint: pcMint,
+        baseTokenProgram: TOKEN_PROGRAM_ID,
+        pcTokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([trader])
+      .rpc({ commitment: "confirmed" });
+
+    // A pegged pair trades close to par, well above the x * y quote
+    const userPcAccount = await getAccount(provider.connection, userPCTokenAta);
+    const amountOut = new anchor.BN(userPcAccount.amount.toString());
+    assert.isTrue(amountOut.gt(constantProductQuote));
+    assert.isTrue(amountOut.lt(amountIn));
+    assert.isTrue(amountOut.gt(amountIn.muln(99).divn(100)));
+    await assertReservesMatchVaults(ammPda, baseTokenVault, pcTokenVault);
+
+    const rampStop = new anchor.BN(Math.floor(Date.now() / 1000) + 2 * 86400);
+    try {
+      await program.methods
+        .rampAmp(ammPdaIndex, new anchor.BN(200), rampStop)
+        .accounts({ admin: user.publicKey, ammConfig: ammConfig, ammPda })
+        .signers([user])
+        .rpc();
+      assert.fail("Only the admin can ramp the amplification");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "Unauthorized");
+    }
+    try {
+      await program.methods
+        .rampAmp(new anchor.BN(1), new anchor.BN(200), rampStop)
+        .accounts({
+          admin: provider.wallet.publicKey,
+          ammConfig: ammConfig,
+          ammPda: ammVariables[1].ammPda,
+        })
+        .rpc();
+      assert.fail("Constant product pools have no amplification");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "InvalidCurveType");
+    }
+    try {
+      await program.methods
+        .rampAmp(ammPdaIndex, new anchor.BN(2_000), rampStop)
+        .accounts({ admin: provider.wallet.publicKey, ammConfig, ammPda })
+        .rpc();
+      assert.fail("Ramps are limited to a 10x change");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "InvalidAmp");
+    }
+
+    await program.methods
+      .rampAmp(ammPdaIndex, new anchor.BN(200), rampStop)
+      .accounts({ admin: provider.wallet.publicKey, ammConfig, ammPda })
+      .rpc();
+    let ammAccount = await program.account.initalizeLiquidityAccount.fetch(
+      ammPda
+    );
+    assert.equal(ammAccount.amp.initialAmp.toString(), "100");
+    assert.equal(ammAccount.amp.targetAmp.toString(), "200");
+    assert.equal(
+      ammAccount.amp.rampStopTimestamp.toString(),
+      rampStop.toString()
+    );
+
+    await program.methods
+      .stopRampAmp(ammPdaIndex)
+      .accounts({ admin: provider.wallet.publicKey, ammConfig, ammPda })
+      .rpc();
+    ammAccount = await program.account.initalizeLiquidityAccount.fetch(ammPda);
+    assert.equal(
+      ammAccount.amp.initialAmp.toString(),
+      ammAccount.amp.targetAmp.toString()
+    );
+  });
+
+  it("Weighted pool", async () => {
+    const ammPdaIndex = new anchor.BN(9);
+    const {
+      ammPda,
+      baseTokenVault,
+      pcTokenVault,
+      lpTokenMint,
+      liquidityProviderLpTokenAta,
+      userPCTokenAta: liquidityProviderPCTokenAta,
+      userBaseTokenAta: liquidityProviderBaseTokenAta,
+      baseMint,
+      pcMint,
+      baseMintAmount,
+      pcMintAmount,
+    } = await prepareInitalizeLiquidityPool(
+      4_000_000_000,
+      1_000_000_000,
+      ammPdaIndex,
+      liquidityProvider
+    );
+    const { pda: observationState } = await getPda([
+      Buffer.from("observation"),
+      ammPda.toBuffer(),
+    ]);
+    const initializeLiquidity = (weights) =>
+      program.methods
+        .initializeLiquidity(
+          lpMintDecimal,
+          ammPdaIndex,
+          baseMint,
+          pcMint,
+          baseMintAmount,
+          pcMintAmount,
+          0,
+          { weighted: {} },
+          new anchor.BN(0),
+          weights,
+          new anchor.BN(0),
+          null
+        )
+        .accounts({
+          liquidityProvider: liquidityProvider.publicKey,
+          ammConfig: ammConfig,
+          ammPda: ammPda,
+          baseTokenVault: baseTokenVault,
+          pcTokenVault: pcTokenVault,
+          lpTokenMint: lpTokenMint,
+          liquidityProviderLpTokenAta: liquidityProviderLpTokenAta,
+          baseTokenMint: baseMint,
+          pcTokenMint: pcMint,
+          liquidityProviderBaseTokenAta: liquidityProviderBaseTokenAta,
+          liquidityProviderPcTokenAta: liquidityProviderPCTokenAta,
+          tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
+          baseTokenProgram: TOKEN_PROGRAM_ID,
+          pcTokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([liquidityProvider])
+        .rpc({ commitment: "confirmed" });
+
+    try {
+      await initializeLiquidity(poolWeights(100, 0));
+      assert.fail("Weights outside 1% to 99% should fail");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "InvalidWeights");
+    }
+    await initializeLiquidity(poolWeights(80, 20));
+    const ammAccount = await program.account.initalizeLiquidityAccount.fetch(
+      ammPda
+    );
+    assert.deepEqual(ammAccount.curveType, { weighted: {} });
+    assert.equal(ammAccount.weights.baseTokenWeight.toString(), "80");
+    assert.equal(ammAccount.weights.pcTokenWeight.toString(), "20");
+
+    const trader = anchor.web3.Keypair.generate();
+    const { userBaseTokenAta, userPCTokenAta } = await prepareSwap(
+      10_000_000,
+      0,
+      ammPdaIndex.toNumber(),
+      trader
+    );
+    const swap = (amountIn: anchor.BN) =>
+      program.methods
+        .swapBaseIn(ammPdaIndex, amountIn, new anchor.BN(1))
+        .accounts({
+          user: trader.publicKey,
+          ammPda: ammPda,
+          observationState: observationState,
+          baseTokenVault: baseTokenVault,
+          pcTokenVault: pcTokenVault,
+          userSourceAta: userBaseTokenAta,
+          userDestinationAta: userPCTokenAta,
+          baseTokenMint: baseMint,
+          pcTokenMint: pcMint,
+          baseTokenProgram: TOKEN_PROGRAM_ID,
+          pcTokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([trader])
+        .rpc({ commitment: "confirmed" });
+
+    // 4e9 base at 80% against 1e9 pc at 20% prices the pair at par, where an
+    // x * y pool with the same reserves would pay a quarter as much
+    const amountIn = new anchor.BN(1_000_000);
+    await swap(amountIn);
+    const userPcAccount = await getAccount(provider.connection, userPCTokenAta);
+    const amountOut = new anchor.BN(userPcAccount.amount.toString());
+    const constantProductQuote = await quoteSwapBaseIn(
+      amountIn,
+      baseMintAmount,
+      pcMintAmount
+    );
+    assert.isTrue(amountOut.gt(constantProductQuote.muln(3)));
+    assert.isTrue(amountOut.lt(amountIn));
+    await assertReservesMatchVaults(ammPda, baseTokenVault, pcTokenVault);
+
+    try {
+      await swap(new anchor.BN(1_300_000_000));
+      assert.fail("Weighted trades above 30% of the reserve should fail");
+    } catch (err) {
+      assert.include(err.toString(), "TradeTooLarge");
+    }
+  });
+
+  it("Concentrated liquidity pool", async () => {
+    const poolIndex = new anchor.BN(0);
+    const { baseMint, pcMint } = await createBaseAndPCMint();
+    const { pda: concentratedPool } = await getPda([
+      Buffer.from("concentrated_pool"),
+      poolIndex.toArrayLike(Buffer, "le", 8),
+    ]);
+    const { pda: baseTokenVault } = await getPda([
+      Buffer.from("base_token_vault"),
+      baseMint.toBuffer(),
+      concentratedPool.toBuffer(),
+    ]);
+    const { pda: pcTokenVault } = await getPda([
+      Buffer.from("pc_token_vault"),
+      pcMint.toBuffer(),
+      concentratedPool.toBuffer(),
+    ]);
+    const tokenAccounts = async (owner: anchor.web3.PublicKey) => {
+      const base = await getOrCreateAssociatedTokenAccount(
+        connection,
+        provider.wallet.payer,
+        baseMint,
+        owner
+      );
+      const pc = await getOrCreateAssociatedTokenAccount(
+        connection,
+        provider.wallet.payer,
+        pcMint,
+        owner
+      );
+      for (const [mint, ata] of [
+        [baseMint, base.address],
+        [pcMint, pc.address],
+      ]) {
+        await mintTo(
+          connection,
+          provider.wallet.payer,
+          mint,
+          ata,
+          provider.wallet.payer,
+          1_000_000_000
+        );
+      }
+      return { base: base.address, pc: pc.address };
+    };
+    const owner = await tokenAccounts(liquidityProvider.publicKey);
+    const trader = await tokenAccounts(user.publicKey);
+    const tokenAmount = async (account: anchor.web3.PublicKey) =>
+      new anchor.BN(
+        (await getAccount(provider.connection, account)).amount.toString()
+      );
+
+    // Price 1 with 64 ticks of spacing 10 per tick array
+    await program.methods
+      .initializeConcentratedPool(
+        poolIndex,
+        10,
+        new anchor.BN(1).shln(64),
+        0
+      )
+      .accounts({
+        creator: liquidityProvider.publicKey,
+        ammConfig: ammConfig,
+        concentratedPool: concentratedPool,
+        baseTokenVault: baseTokenVault,
+        pcTokenVault: pcTokenVault,
+        baseTokenMint: baseMint,
+        pcTokenMint: pcMint,
+        baseTokenProgram: TOKEN_PROGRAM_ID,
+        pcTokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([liquidityProvider])
+      .rpc({ commitment: "confirmed" });
+    const tickArrays = {};
+    for (const start of [-640, 0]) {
+      const { pda: tickArray } = await getPda([
+        Buffer.from("tick_array"),
+        concentratedPool.toBuffer(),
+        new anchor.BN(start).toTwos(32).toArrayLike(Buffer, "le", 4),
+      ]);
+      await program.methods
+        .initializeTickArray(poolIndex, start)
+        .accounts({
+          payer: liquidityProvider.publicKey,
+          concentratedPool: concentratedPool,
+          tickArray: tickArray,
+        })
+        .signers([liquidityProvider])
+        .rpc({ commitment: "confirmed" });
+      tickArrays[start] = tickArray;
+    }
+
+    const positionPda = async (tickLower: number, tickUpper: number) =>
+      (
+        await getPda([
+          Buffer.from("position"),
+          concentratedPool.toBuffer(),
+          liquidityProvider.publicKey.toBuffer(),
+          new anchor.BN(tickLower).toTwos(32).toArrayLike(Buffer, "le", 4),
+          new anchor.BN(tickUpper).toTwos(32).toArrayLike(Buffer, "le", 4),
+        ])
+      ).pda;
+    const openPosition = async (tickLower: number, tickUpper: number) => {
+      const position = await positionPda(tickLower, tickUpper);
+      await program.methods
+        .openPosition(poolIndex, tickLower, tickUpper)
+        .accounts({
+          owner: liquidityProvider.publicKey,
+          concentratedPool: concentratedPool,
+          position: position,
+        })
+        .signers([liquidityProvider])
+        .rpc({ commitment: "confirmed" });
+      return position;
+    };
+    const liquidityAccounts = (
+      position: anchor.web3.PublicKey,
+      tickArrayLower: anchor.web3.PublicKey,
+      tickArrayUpper: anchor.web3.PublicKey
+    ) => ({
+      owner: liquidityProvider.publicKey,
+      concentratedPool: concentratedPool,
+      position: position,
+      tickArrayLower: tickArrayLower,
+      tickArrayUpper: tickArrayUpper,
+      baseTokenVault: baseTokenVault,
+      pcTokenVault: pcTokenVault,
+      ownerBaseTokenAta: owner.base,
+      ownerPcTokenAta: owner.pc,
+      baseTokenMint: baseMint,
+      pcTokenMint: pcMint,
+      baseTokenProgram: TOKEN_PROGRAM_ID,
+      pcTokenProgram: TOKEN_PROGRAM_ID,
+    });
+
+    try {
+      await openPosition(-605, 600);
+      assert.fail("Ticks off the tick spacing should fail");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "InvalidTickRange");
+    }
+
+    // [-600, 600) around price 1 holds 1e9 * (1 - 1.0001^-300) of each side
+    const wide = await openPosition(-600, 600);
+    const wideAccounts = liquidityAccounts(wide, tickArrays[-640], tickArrays[0]);
+    const liquidity = new anchor.BN(1_000_000_000);
+    await program.methods
+      .increaseLiquidity(
+        poolIndex,
+        liquidity,
+        new anchor.BN(30_000_000),
+        new anchor.BN(30_000_000)
+      )
+      .accounts(wideAccounts)
+      .signers([liquidityProvider])
+      .rpc({ commitment: "confirmed" });
+    assert.equal((await tokenAmount(baseTokenVault)).toString(), "29553011");
+    assert.equal((await tokenAmount(pcTokenVault)).toString(), "29553011");
+
+    // A range above the price only holds base, with both ticks in one array
+    const above = await openPosition(100, 300);
+    await program.methods
+      .increaseLiquidity(
+        poolIndex,
+        liquidity,
+        new anchor.BN(10_000_000),
+        new anchor.BN(0)
+      )
+      .accounts(liquidityAccounts(above, tickArrays[0], tickArrays[0]))
+      .signers([liquidityProvider])
+      .rpc({ commitment: "confirmed" });
+    assert.equal((await tokenAmount(pcTokenVault)).toString(), "29553011");
+
+    // Selling base moves the price down through the wide range only
+    const swap = (
+      amountIn: anchor.BN,
+      arrays: anchor.web3.PublicKey[],
+      sqrtPriceLimit: anchor.BN | null = null
+    ) =>
+      program.methods
+        .swapConcentrated(poolIndex, amountIn, new anchor.BN(1), sqrtPriceLimit)
+        .accounts({
+          user: user.publicKey,
+          concentratedPool: concentratedPool,
+          baseTokenVault: baseTokenVault,
+          pcTokenVault: pcTokenVault,
+          userSourceAta: trader.base,
+          userDestinationAta: trader.pc,
+          baseTokenMint: baseMint,
+          pcTokenMint: pcMint,
+          baseTokenProgram: TOKEN_PROGRAM_ID,
+          pcTokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .remainingAccounts(
+          arrays.map((pubkey) => ({ pubkey, isWritable: true, isSigner: false }))
+        )
+        .signers([user])
+        .rpc({ commitment: "confirmed" });
+    const pcBefore = await tokenAmount(trader.pc);
+    await swap(new anchor.BN(10_000_000), [tickArrays[0], tickArrays[-640]]);
+    const amountOut = (await tokenAmount(trader.pc)).sub(pcBefore);
+    assert.isTrue(amountOut.gt(new anchor.BN(9_800_000)));
+    assert.isTrue(amountOut.lt(new anchor.BN(9_975_000)));
+    let pool = await program.account.concentratedPool.fetch(concentratedPool);
+    assert.isTrue(pool.tickCurrent < 0);
+    assert.equal(pool.liquidity.toString(), liquidity.toString());
+    assert.isTrue(pool.feeGrowthGlobalBaseQ64.gtn(0));
+
+    try {
+      await swap(new anchor.BN(100_000_000), [tickArrays[0], tickArrays[-640]]);
+      assert.fail("Swapping past the provided tick arrays should fail");
+    } catch (err) {
+      assert.include(err.toString(), "InvalidTickArray");
+    }
+
+    // A price limit stops the swap early and only takes the input it used
+    try {
+      await swap(
+        new anchor.BN(1_000_000),
+        [tickArrays[0], tickArrays[-640]],
+        pool.sqrtPriceQ64
+      );
+      assert.fail("A limit at the current price should fail");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "InvalidSqrtPriceLimit");
+    }
+    const sqrtPriceLimit = pool.sqrtPriceQ64.sub(pool.sqrtPriceQ64.divn(10_000));
+    const traderBaseBefore = await tokenAmount(trader.base);
+    await swap(
+      new anchor.BN(100_000_000),
+      [tickArrays[0], tickArrays[-640]],
+      sqrtPriceLimit
+    );
+    const baseUsed = traderBaseBefore.sub(await tokenAmount(trader.base));
+    assert.isTrue(baseUsed.gtn(0));
+    assert.isTrue(baseUsed.lt(new anchor.BN(100_000_000)));
+    pool = await program.account.concentratedPool.fetch(concentratedPool);
+    assert.equal(pool.sqrtPriceQ64.toString(), sqrtPriceLimit.toString());
+
+    // Buying base back crosses into the range above the price
+    await program.methods
+      .swapConcentrated(
+        poolIndex,
+        new anchor.BN(25_000_000),
+        new anchor.BN(1),
+        null
+      )
+      .accounts({
+        user: user.publicKey,
+        concentratedPool: concentratedPool,
+        baseTokenVault: baseTokenVault,
+        pcTokenVault: pcTokenVault,
+        userSourceAta: trader.pc,
+        userDestinationAta: trader.base,
+        baseTokenMint: baseMint,
+        pcTokenMint: pcMint,
+        baseTokenProgram: TOKEN_PROGRAM_ID,
+        pcTokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .remainingAccounts(
+        [tickArrays[-640], tickArrays[0]].map((pubkey) => ({
+          pubkey,
+          isWritable: true,
+          isSigner: false,
+        }))
+      )
+      .signers([user])
+      .rpc({ commitment: "confirmed" });
+    pool = await program.account.concentratedPool.fetch(concentratedPool);
+    assert.isTrue(pool.tickCurrent >= 100);
+    assert.equal(pool.liquidity.toString(), liquidity.muln(2).toString());
+
+    try {
+      await program.methods
+        .closePosition(poolIndex)
+        .accounts({
+          owner: liquidityProvider.publicKey,
+          concentratedPool: concentratedPool,
+          position: wide,
+        })
+        .signers([liquidityProvider])
+        .rpc({ commitment: "confirmed" });
+      assert.fail("Closing a position with liquidity should fail");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "PositionNotEmpty");
+    }
+
+    // Taking everything out pays the wide position its share of both swaps
+    const baseBefore = await tokenAmount(owner.base);
+    const pcOwnerBefore = await tokenAmount(owner.pc);
+    await program.methods
+      .decreaseLiquidity(poolIndex, liquidity, new anchor.BN(0), new anchor.BN(0))
+      .accounts(wideAccounts)
+      .signers([liquidityProvider])
+      .rpc({ commitment: "confirmed" });
+    const position = await program.account.position.fetch(wide);
+    assert.equal(position.liquidity.toString(), "0");
+    assert.equal(position.feesOwedBaseToken.toString(), "0");
+    assert.isTrue((await tokenAmount(owner.base)).gt(baseBefore));
+    assert.isTrue((await tokenAmount(owner.pc)).gt(pcOwnerBefore));
+
+    await program.methods
+      .closePosition(poolIndex)
+      .accounts({
+        owner: liquidityProvider.publicKey,
+        concentratedPool: concentratedPool,
+        position: wide,
+      })
+      .signers([liquidityProvider])
+      .rpc({ commitment: "confirmed" });
+    assert.isNull(await connection.getAccountInfo(wide));
+  });
+
+  it("Flash loan", async () => {
+    const ammPdaIndex = new anchor.BN(2);
+    const { ammPda, baseTokenVault, pcTokenVault, baseMint, pcMint } =
+      ammVariables[ammPdaIndex.toNumber()];
+    const borrower = anchor.web3.Keypair.generate();
+    const airdropSig = await provider.connection.requestAirdrop(
+      borrower.publicKey,
+      anchor.web3.LAMPORTS_PER_SOL * 20
+    );
+    await provider.connection.confirmTransaction(airdropSig);
+    const { userBaseTokenAta, userPCTokenAta } = await prepareSwap(
+      0,
+      10_000_000,
+      ammPdaIndex.toNumber(),
+      borrower,
+      true
+    );
+    await wrapSol(borrower, userBaseTokenAta);
+
+    const config = await program.account.ammConfig.fetch(ammConfig);
+    assert.equal(config.flashLoanFeeNumerator.toString(), "9");
+    assert.equal(config.flashLoanFeeDenominator.toString(), "10000");
+    try {
+      await program.methods
+        .setFlashLoanFee(new anchor.BN(0), new anchor.BN(1))
+        .accounts({ admin: borrower.publicKey, ammConfig: ammConfig })
+        .signers([borrower])
+        .rpc();
+      assert.fail("Only the admin can set the flash loan fee");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "Unauthorized");
+    }
+
+    const loanAccounts = {
+      borrower: borrower.publicKey,
+      ammPda: ammPda,
+      ammConfig: ammConfig,
+      baseTokenVault: baseTokenVault,
+      pcTokenVault: pcTokenVault,
+      borrowerBaseTokenAta: userBaseTokenAta,
+      borrowerPcTokenAta: userPCTokenAta,
+      baseTokenMint: baseMint,
+      pcTokenMint: pcMint,
+      baseTokenProgram: TOKEN_PROGRAM_ID,
+      pcTokenProgram: TOKEN_PROGRAM_ID,
+      instructionsSysvar: anchor.web3.SYSVAR_INSTRUCTIONS_PUBKEY,
+    };
+    const amount = new anchor.BN(1_000_000);
+    const begin = () =>
+      program.methods
+        .flashLoanBegin(ammPdaIndex, amount, new anchor.BN(0))
+        .accounts(loanAccounts)
+        .signers([borrower]);
+    const end = await program.methods
+      .flashLoanEnd(ammPdaIndex)
+      .accounts(loanAccounts)
+      .instruction();
+
+    try {
+      await begin().rpc();
+      assert.fail("A flash loan without flash_loan_end should fail");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "FlashLoanNotRepaid");
+    }
+
+    const swap = await program.methods
+      .swapBaseIn(ammPdaIndex, amount, new anchor.BN(1))
+      .accounts({
+        user: borrower.publicKey,
+        ammPda: ammPda,
+        baseTokenVault: baseTokenVault,
+        pcTokenVault: pcTokenVault,
+        userSourceAta: userBaseTokenAta,
+        userDestinationAta: userPCTokenAta,
+        baseTokenMint: baseMint,
+        pcTokenMint: pcMint,
+        baseTokenProgram: TOKEN_PROGRAM_ID,
+        pcTokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .instruction();
+    try {
+      await begin().postInstructions([swap, end]).rpc();
+      assert.fail("Swapping against a pool with an open loan should fail");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "FlashLoanActive");
+    }
+
+    // 9/10000 of the 1e6 borrowed stays with the LPs
+    const reservesBefore = await program.account.initalizeLiquidityAccount.fetch(
+      ammPda
+    );
+    const borrowerBefore = await getAccount(connection, userBaseTokenAta);
+    await begin().postInstructions([end]).rpc();
+    const reservesAfter = await program.account.initalizeLiquidityAccount.fetch(
+      ammPda
+    );
+    const borrowerAfter = await getAccount(connection, userBaseTokenAta);
+    assert.equal(
+      reservesAfter.baseTokenAmount.sub(reservesBefore.baseTokenAmount).toString(),
+      "900"
+    );
+    assert.equal(
+      (borrowerBefore.amount - borrowerAfter.amount).toString(),
+      "900"
+    );
+    assert.isFalse(reservesAfter.flashLoan.active);
+    await assertReservesMatchVaults(ammPda, baseTokenVault, pcTokenVault);
+
+    // The repayment stored the pool's cumulative values from before the
+    // fees moved its price
+    const { pda: observationState } = await getPda([
+      Buffer.from("observation"),
+      ammPda.toBuffer(),
+    ]);
+    const observed = await program.methods
+      .observeAt(ammPdaIndex, reservesAfter.priceCumulatives.timestamp)
+      .accounts({ ammPda, observationState })
+      .view();
+    for (const field of [
+      "timestamp",
+      "basePriceCumulative",
+      "pcPriceCumulative",
+      "liquidityCumulative",
+    ]) {
+      assert.equal(
+        observed[field].toString(),
+        reservesAfter.priceCumulatives[field].toString()
+      );
+    }
+  });
+  it("Single-sided deposit", async () => {
+    const ammPdaIndex = new anchor.BN(2);
+    const {
+      ammPda,
+      baseTokenVault,
+      pcTokenVault,
+      lpTokenMint,
+      baseMint,
+      pcMint,
+    } = ammVariables[ammPdaIndex.toNumber()];
+    const zapper = anchor.web3.Keypair.generate();
+    const airdropSig = await provider.connection.requestAirdrop(
+      zapper.publicKey,
+      anchor.web3.LAMPORTS_PER_SOL * 20
+    );
+    await provider.connection.confirmTransaction(airdropSig);
+    const { userBaseTokenAta, userPCTokenAta } = await prepareSwap(
+      0,
+      10_000_000,
+      ammPdaIndex.toNumber(),
+      zapper,
+      true
+    );
+    await wrapSol(zapper, userBaseTokenAta);
+    const zapperLpTokenAta = await getAssociatedTokenAddress(
+      lpTokenMint,
+      zapper.publicKey
+    );
+
+    const zap = (minLpTokenAmount: anchor.BN) =>
+      program.methods
+        .depositSingleSide(
+          ammPdaIndex,
+          new anchor.BN(10_000_000),
+          1,
+          minLpTokenAmount
+        )
+        .accounts({
+          user: zapper.publicKey,
+          ammPda: ammPda,
+          baseTokenVault: baseTokenVault,
+          pcTokenVault: pcTokenVault,
+          lpTokenMint: lpTokenMint,
+          liquidityProviderLpTokenAta: zapperLpTokenAta,
+          liquidityProviderBaseTokenAta: userBaseTokenAta,
+          liquidityProviderPcTokenAta: userPCTokenAta,
+          baseTokenMint: baseMint,
+          pcTokenMint: pcMint,
+          baseTokenProgram: TOKEN_PROGRAM_ID,
+          pcTokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([zapper])
+        .rpc();
+
+    try {
+      await zap(new anchor.BN("18446744073709551615"));
+      assert.fail("Zapping for less than min_lp_token_amount should fail");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "DepositSlippageExceeded");
+    }
+
+    const baseBefore = await getAccount(connection, userBaseTokenAta);
+    await zap(new anchor.BN(1));
+    const lpAta = await getAccount(connection, zapperLpTokenAta);
+    assert.isTrue(lpAta.amount > BigInt(0));
+    // All the pc went in and only rounding dust comes back
+    const baseAfter = await getAccount(connection, userBaseTokenAta);
+    const pcAfter = await getAccount(connection, userPCTokenAta);
+    assert.isTrue(
+      pcAfter.amount + baseAfter.amount - baseBefore.amount < BigInt(10)
+    );
+    await assertReservesMatchVaults(ammPda, baseTokenVault, pcTokenVault);
+  });
+  it("Single-sided withdrawal", async () => {
+    const ammPdaIndex = new anchor.BN(2);
+    const {
+      ammPda,
+      baseTokenVault,
+      pcTokenVault,
+      lpTokenMint,
+      liquidityProviderLpTokenAta,
+      userPCTokenAta,
+      baseMint,
+      pcMint,
+    } = ammVariables[ammPdaIndex.toNumber()];
+    const lpAta = await getAccount(connection, liquidityProviderLpTokenAta);
+    const lpTokenAmount = new anchor.BN(lpAta.amount.toString()).divn(10);
+
+    const withdraw = (minAmountOut: anchor.BN) =>
+      program.methods
+        .withdrawSingleSide(ammPdaIndex, lpTokenAmount, 1, minAmountOut)
+        .accounts({
+          user: liquidityProvider.publicKey,
+          ammPda: ammPda,
+          baseTokenVault: baseTokenVault,
+          pcTokenVault: pcTokenVault,
+          lpTokenMint: lpTokenMint,
+          liquidityProviderLpTokenAta: liquidityProviderLpTokenAta,
+          userDestinationAta: userPCTokenAta,
+          baseTokenMint: baseMint,
+          pcTokenMint: pcMint,
+          baseTokenProgram: TOKEN_PROGRAM_ID,
+          pcTokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([liquidityProvider])
+        .rpc();
+
+    try {
+      await withdraw(new anchor.BN("18446744073709551615"));
+      assert.fail("Withdrawing below min_amount_out should fail");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "WithdrawSlippageExceeded");
+    }
+
+    const ammAccount = await program.account.initalizeLiquidityAccount.fetch(
+      ammPda
+    );
+    const lpMintAccount = await getMint(connection, lpTokenMint);
+    const pcTokenShare = lpTokenAmount
+      .mul(ammAccount.pcTokenAmount)
+      .div(new anchor.BN(lpMintAccount.supply.toString()));
+    const pcBefore = await getAccount(connection, userPCTokenAta);
+    await withdraw(pcTokenShare);
+    const pcAfter = await getAccount(connection, userPCTokenAta);
+    // The base share was swapped into pc on top of the pc share
+    assert.isTrue(
+      pcAfter.amount - pcBefore.amount > BigInt(pcTokenShare.toString())
+    );
+    await assertReservesMatchVaults(ammPda, baseTokenVault, pcTokenVault);
+  });
+  it("Pool pause", async () => {
+    const ammPdaIndex = new anchor.BN(2);
+    const {
+      ammPda,
+      baseTokenVault,
+      pcTokenVault,
+      lpTokenMint,
+      liquidityProviderLpTokenAta,
+      userBaseTokenAta,
+      userPCTokenAta,
+      baseMint,
+      pcMint,
+    } = ammVariables[ammPdaIndex.toNumber()];
+    const withdrawOnly = 0b011;
+    const paused = 0b111;
+
+    try {
+      await program.methods
+        .setPoolStatus(ammPdaIndex, paused)
+        .accounts({ admin: liquidityProvider.publicKey, ammConfig, ammPda })
+        .signers([liquidityProvider])
+        .rpc();
+      assert.fail("Only the admin can pause a pool");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "Unauthorized");
+    }
+    try {
+      await program.methods
+        .setGlobalPause(0b1000)
+        .accounts({ admin: provider.wallet.publicKey, ammConfig })
+        .rpc();
+      assert.fail("Unknown status bits should be rejected");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "InvalidPoolStatus");
+    }
+
+    const setPoolStatus = (status: number) =>
+      program.methods
+        .setPoolStatus(ammPdaIndex, status)
+        .accounts({ admin: provider.wallet.publicKey, ammConfig, ammPda })
+        .rpc();
+    const setGlobalPause = (status: number) =>
+      program.methods
+        .setGlobalPause(status)
+        .accounts({ admin: provider.wallet.publicKey, ammConfig })
+        .rpc();
+    const swap = () =>
+      program.methods
+        .swapBaseIn(ammPdaIndex, new anchor.BN(1_000), new anchor.BN(1))
+        .accounts({
+          user: liquidityProvider.publicKey,
+          ammPda: ammPda,
+          baseTokenVault: baseTokenVault,
+          pcTokenVault: pcTokenVault,
+          userSourceAta: userPCTokenAta,
+          userDestinationAta: userBaseTokenAta,
+          baseTokenMint: baseMint,
+          pcTokenMint: pcMint,
+          baseTokenProgram: TOKEN_PROGRAM_ID,
+          pcTokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([liquidityProvider])
+        .rpc();
+    const withdraw = () =>
+      program.methods
+        .withdraw(
+          lpMintDecimal,
+          ammPdaIndex,
+          new anchor.BN(1_000),
+          new anchor.BN(0),
+          new anchor.BN(0)
+        )
+        .accounts({
+          user: liquidityProvider.publicKey,
+          ammPda: ammPda,
+          baseTokenVault: baseTokenVault,
+          pcTokenVault: pcTokenVault,
+          lpTokenMint: lpTokenMint,
+          liquidityProviderLpTokenAta: liquidityProviderLpTokenAta,
+          liquidityProviderBaseTokenAta: userBaseTokenAta,
+          liquidityProviderPcTokenAta: userPCTokenAta,
+          baseTokenMint: baseMint,
+          pcTokenMint: pcMint,
+          baseTokenProgram: TOKEN_PROGRAM_ID,
+          pcTokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([liquidityProvider])
+        .rpc();
+
+    // Withdraw-only keeps the exit open
+    await setPoolStatus(withdrawOnly);
+    try {
+      await swap();
+      assert.fail("Swapping in a withdraw-only pool should fail");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "SwapDisabled");
+    }
+    await withdraw();
+    await setPoolStatus(0);
+    await swap();
+
+    // The global pause applies on top of each pool's own status
+    await setGlobalPause(paused);
+    try {
+      await withdraw();
+      assert.fail("Withdrawing during a global pause should fail");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "WithdrawDisabled");
+    }
+    await setGlobalPause(0);
+    await withdraw();
+    await assertReservesMatchVaults(ammPda, baseTokenVault, pcTokenVault);
+
+    // Concentrated pools have a status of their own
+    const { pda: concentratedPool } = await getPda([
+      Buffer.from("concentrated_pool"),
+      new anchor.BN(0).toArrayLike(Buffer, "le", 8),
+    ]);
+    const setConcentratedPoolStatus = (
+      status: number,
+      admin: anchor.web3.Keypair
+    ) =>
+      program.methods
+        .setConcentratedPoolStatus(new anchor.BN(0), status)
+        .accounts({ admin: admin.publicKey, ammConfig, concentratedPool })
+        .signers([admin])
+        .rpc();
+    try {
+      await setConcentratedPoolStatus(paused, liquidityProvider);
+      assert.fail("Only the admin can pause a concentrated pool");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "Unauthorized");
+    }
+    await setConcentratedPoolStatus(withdrawOnly, provider.wallet.payer);
+    let pool = await program.account.concentratedPool.fetch(concentratedPool);
+    assert.equal(pool.status, withdrawOnly);
+    await setConcentratedPoolStatus(0, provider.wallet.payer);
+    pool = await program.account.concentratedPool.fetch(concentratedPool);
+    assert.equal(pool.status, 0);
+  });
+  it("Scheduled pool opening", async () => {
+    const ammPdaIndex = new anchor.BN(10);
+    const {
+      ammPda,
+      baseTokenVault,
+      pcTokenVault,
+      lpTokenMint,
+      liquidityProviderLpTokenAta,
+      userPCTokenAta: liquidityProviderPCTokenAta,
+      userBaseTokenAta: liquidityProviderBaseTokenAta,
+      baseMint,
+      pcMint,
+      baseMintAmount,
+      pcMintAmount,
+    } = await prepareInitalizeLiquidityPool(
+      1_000_000_000,
+      1_000_000_000,
+      ammPdaIndex,
+      liquidityProvider
+    );
+    const now = await connection.getBlockTime(await connection.getSlot());
+    const openTime = new anchor.BN(now + 3600);
+
+    await program.methods
+      .initializeLiquidity(
+        lpMintDecimal,
+        ammPdaIndex,
+        baseMint,
+        pcMint,
+        baseMintAmount,
+        pcMintAmount,
+        0,
+        { constantProduct: {} },
+        new anchor.BN(0),
+        poolWeights(),
+        openTime,
+        null
+      )
+      .accounts({
+        liquidityProvider: liquidityProvider.publicKey,
+        ammConfig: ammConfig,
+        ammPda: ammPda,
+        baseTokenVault: baseTokenVault,
+        pcTokenVault: pcTokenVault,
+        lpTokenMint: lpTokenMint,
+        liquidityProviderLpTokenAta: liquidityProviderLpTokenAta,
+        baseTokenMint: baseMint,
+        pcTokenMint: pcMint,
+        liquidityProviderBaseTokenAta: liquidityProviderBaseTokenAta,
+        liquidityProviderPcTokenAta: liquidityProviderPCTokenAta,
+        tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
+        baseTokenProgram: TOKEN_PROGRAM_ID,
+        pcTokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([liquidityProvider])
+      .rpc({ commitment: "confirmed" });
+    let ammAccount = await program.account.initalizeLiquidityAccount.fetch(
+      ammPda
+    );
+    assert.equal(ammAccount.openTime.toString(), openTime.toString());
+
+    const trader = anchor.web3.Keypair.generate();
+    const { userBaseTokenAta, userPCTokenAta } = await prepareSwap(
+      1_000_000,
+      0,
+      ammPdaIndex.toNumber(),
+      trader
+    );
+    const swap = () =>
+      program.methods
+        .swapBaseIn(ammPdaIndex, new anchor.BN(1_000_000), new anchor.BN(1))
+        .accounts({
+          user: trader.publicKey,
+          ammPda: ammPda,
+          baseTokenVault: baseTokenVault,
+          pcTokenVault: pcTokenVault,
+          userSourceAta: userBaseTokenAta,
+          userDestinationAta: userPCTokenAta,
+          baseTokenMint: baseMint,
+          pcTokenMint: pcMint,
+          baseTokenProgram: TOKEN_PROGRAM_ID,
+          pcTokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([trader])
+        .rpc();
+    try {
+      await swap();
+      assert.fail("Swapping before the open time should fail");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "PoolNotOpen");
+    }
+
+    const updateOpenTime = (signer: anchor.web3.Keypair, time: anchor.BN) =>
+      program.methods
+        .updateOpenTime(ammPdaIndex, time)
+        .accounts({ liquidityProvider: signer.publicKey, ammPda })
+        .signers([signer])
+        .rpc();
+    try {
+      await updateOpenTime(trader, new anchor.BN(0));
+      assert.fail("Only the pool creator can move the open time");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "Unauthorized");
+    }
+    try {
+      await updateOpenTime(liquidityProvider, openTime.addn(3600));
+      assert.fail("The open time can't be pushed back");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "InvalidOpenTime");
+    }
+
+    // A time in the past opens the pool right away
+    await updateOpenTime(liquidityProvider, new anchor.BN(0));
+    ammAccount = await program.account.initalizeLiquidityAccount.fetch(ammPda);
+    assert.isTrue(ammAccount.openTime.lt(openTime));
+    await swap();
+    try {
+      await updateOpenTime(liquidityProvider, new anchor.BN(0));
+      assert.fail("An open pool's open time is final");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "InvalidOpenTime");
+    }
+  });
+  it("Launch fee", async () => {
+    const ammPdaIndex = new anchor.BN(11);
+    const {
+      ammPda,
+      baseTokenVault,
+      pcTokenVault,
+      lpTokenMint,
+      liquidityProviderLpTokenAta,
+      userPCTokenAta: liquidityProviderPCTokenAta,
+      userBaseTokenAta: liquidityProviderBaseTokenAta,
+      baseMint,
+      pcMint,
+      baseMintAmount,
+      pcMintAmount,
+    } = await prepareInitalizeLiquidityPool(
+      1_000_000_000,
+      1_000_000_000,
+      ammPdaIndex,
+      liquidityProvider
+    );
+    const initializeAccounts = {
+      liquidityProvider: liquidityProvider.publicKey,
+      ammConfig: ammConfig,
+      ammPda: ammPda,
+      baseTokenVault: baseTokenVault,
+      pcTokenVault: pcTokenVault,
+      lpTokenMint: lpTokenMint,
+      liquidityProviderLpTokenAta: liquidityProviderLpTokenAta,
+      baseTokenMint: baseMint,
+      pcTokenMint: pcMint,
+      liquidityProviderBaseTokenAta: liquidityProviderBaseTokenAta,
+      liquidityProviderPcTokenAta: liquidityProviderPCTokenAta,
+      tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
+      baseTokenProgram: TOKEN_PROGRAM_ID,
+      pcTokenProgram: TOKEN_PROGRAM_ID,
+    };
+    const initialize = (launchFee) =>
+      program.methods
+        .initializeLiquidity(
+          lpMintDecimal,
+          ammPdaIndex,
+          baseMint,
+          pcMint,
+          baseMintAmount,
+          pcMintAmount,
+          0,
+          { constantProduct: {} },
+          new anchor.BN(0),
+          poolWeights(),
+          new anchor.BN(0),
+          launchFee
+        )
+        .accounts(initializeAccounts)
+        .signers([liquidityProvider])
+        .rpc({ commitment: "confirmed" });
+    // 50% at open, down to the fee tier's rate over an hour
+    const launchFee = {
+      startFeeNumerator: new anchor.BN(1),
+      startFeeDenominator: new anchor.BN(2),
+      duration: 3600,
+      decay: { linear: {} },
+    };
+    try {
+      await initialize({ ...launchFee, duration: 0 });
+      assert.fail("A launch fee without a window should be rejected");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "InvalidLaunchFee");
+    }
+    await initialize(launchFee);
+
+    const trader = anchor.web3.Keypair.generate();
+    const { userBaseTokenAta, userPCTokenAta } = await prepareSwap(
+      1_000_000,
+      0,
+      ammPdaIndex.toNumber(),
+      trader
+    );
+    const txSig = await program.methods
+      .swapBaseIn(ammPdaIndex, new anchor.BN(1_000_000), new anchor.BN(1))
+      .accounts({
+        user: trader.publicKey,
+        ammPda: ammPda,
+        baseTokenVault: baseTokenVault,
+        pcTokenVault: pcTokenVault,
+        userSourceAta: userBaseTokenAta,
+        userDestinationAta: userPCTokenAta,
+        baseTokenMint: baseMint,
+        pcTokenMint: pcMint,
+        baseTokenProgram: TOKEN_PROGRAM_ID,
+        pcTokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([trader])
+      .rpc({ commitment: "confirmed" });
+
+    const tx = await provider.connection.getTransaction(txSig, {
+      commitment: "confirmed",
+    });
+    const eventParser = new anchor.EventParser(
+      program.programId,
+      new anchor.BorshCoder(program.idl)
+    );
+    const swapEvent = [...eventParser.parseLogs(tx.meta.logMessages)].find(
+      (event) => event.name == "swapEvent"
+    );
+    // A few seconds into the window the fee is still close to 50%
+    const swapFee = swapEvent.data.swapFee.toNumber();
+    assert.isTrue(swapFee > 490_000 && swapFee <= 500_000);
+    await assertReservesMatchVaults(ammPda, baseTokenVault, pcTokenVault);
+  });
+  it("Dynamic fee", async () => {
+    const ammPdaIndex = new anchor.BN(12);
+    const {
+      ammPda,
+      baseTokenVault,
+      pcTokenVault,
+      lpTokenMint,
+      liquidityProviderLpTokenAta,
+      userPCTokenAta: liquidityProviderPCTokenAta,
+      userBaseTokenAta: liquidityProviderBaseTokenAta,
+      baseMint,
+      pcMint,
+      baseMintAmount,
+      pcMintAmount,
+    } = await prepareInitalizeLiquidityPool(
+      1_000_000_000,
+      1_000_000_000,
+      ammPdaIndex,
+      liquidityProvider
+    );
+    await program.methods
+      .initializeLiquidity(
+        lpMintDecimal,
+        ammPdaIndex,
+        baseMint,
+        pcMint,
+        baseMintAmount,
+        pcMintAmount,
+        0,
+        { constantProduct: {} },
+        new anchor.BN(0),
+        poolWeights(),
+        new anchor.BN(0),
+        null
+      )
+      .accounts({
+        liquidityProvider: liquidityProvider.publicKey,
+        ammConfig: ammConfig,
+        ammPda: ammPda,
+        baseTokenVault: baseTokenVault,
+        pcTokenVault: pcTokenVault,
+        lpTokenMint: lpTokenMint,
+        liquidityProviderLpTokenAta: liquidityProviderLpTokenAta,
+        baseTokenMint: baseMint,
+        pcTokenMint: pcMint,
+        liquidityProviderBaseTokenAta: liquidityProviderBaseTokenAta,
+        liquidityProviderPcTokenAta: liquidityProviderPCTokenAta,
+        tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
+        baseTokenProgram: TOKEN_PROGRAM_ID,
+        pcTokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([liquidityProvider])
+      .rpc({ commitment: "confirmed" });
+
+    // Up to 10% on top of the fee tier's rate at 10% volatility
+    const params = {
+      filterPeriod: 30,
+      decayPeriod: 600,
+      reductionFactor: 5_000,
+      variableFeeControl: 100,
+      maxVolatilityAccumulator: 1_000,
+    };
+    try {
+      await program.methods
+        .setDynamicFee(ammPdaIndex, params)
+        .accounts({ admin: liquidityProvider.publicKey, ammConfig, ammPda })
+        .signers([liquidityProvider])
+        .rpc();
+      assert.fail("Only the admin can set a dynamic fee");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "Unauthorized");
+    }
+    try {
+      await program.methods
+        .setDynamicFee(ammPdaIndex, { ...params, decayPeriod: 30 })
+        .accounts({ admin: provider.wallet.publicKey, ammConfig, ammPda })
+        .rpc();
+      assert.fail("A decay period within the filter period should be rejected");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "InvalidDynamicFee");
+    }
+    await program.methods
+      .setDynamicFee(ammPdaIndex, params)
+      .accounts({ admin: provider.wallet.publicKey, ammConfig, ammPda })
+      .rpc({ commitment: "confirmed" });
+
+    const trader = anchor.web3.Keypair.generate();
+    const { userBaseTokenAta, userPCTokenAta } = await prepareSwap(
+      20_000_000,
+      0,
+      ammPdaIndex.toNumber(),
+      trader
+    );
+    const eventParser = new anchor.EventParser(
+      program.programId,
+      new anchor.BorshCoder(program.idl)
+    );
+    const swapFee = async () => {
+      const txSig = await program.methods
+        .swapBaseIn(ammPdaIndex, new anchor.BN(10_000_000), new anchor.BN(1))
+        .accounts({
+          user: trader.publicKey,
+          ammPda: ammPda,
+          baseTokenVault: baseTokenVault,
+          pcTokenVault: pcTokenVault,
+          userSourceAta: userBaseTokenAta,
+          userDestinationAta: userPCTokenAta,
+          baseTokenMint: baseMint,
+          pcTokenMint: pcMint,
+          baseTokenProgram: TOKEN_PROGRAM_ID,
+          pcTokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([trader])
+        .rpc({ commitment: "confirmed" });
+      const tx = await provider.connection.getTransaction(txSig, {
+        commitment: "confirmed",
+      });
+      const swapEvent = [...eventParser.parseLogs(tx.meta.logMessages)].find(
+        (event) => event.name == "swapEvent"
+      );
+      return swapEvent.data.swapFee.toNumber();
+    };
+    // The first swap moves the price about 2% from the reference, so the
+    // next one within the filter period pays about 0.4% more
+    const calmFee = await swapFee();
+    const volatileFee = await swapFee();
+    assert.isTrue(volatileFee > calmFee + 30_000);
+    await assertReservesMatchVaults(ammPda, baseTokenVault, pcTokenVault);
+
+    // Turning it off goes back to the fee tier's rate
+    await program.methods
+      .setDynamicFee(ammPdaIndex, null)
+      .accounts({ admin: provider.wallet.publicKey, ammConfig, ammPda })
+      .rpc({ commitment: "confirmed" });
+    const pool = await program.account.initalizeLiquidityAccount.fetch(ammPda);
+    assert.isFalse(pool.dynamicFee.enabled);
+  });
+  it("Close pool", async () => {
+    const ammPdaIndex = new anchor.BN(13);
+    const {
+      ammPda,
+      baseTokenVault,
+      pcTokenVault,
+      lpTokenMint,
+      liquidityProviderLpTokenAta,
+      userPCTokenAta: liquidityProviderPCTokenAta,
+      userBaseTokenAta: liquidityProviderBaseTokenAta,
+      baseMint,
+      pcMint,
+      baseMintAmount,
+      pcMintAmount,
+    } = await prepareInitalizeLiquidityPool(
+      1_000_000_000,
+      1_000_000_000,
+      ammPdaIndex,
+      liquidityProvider
+    );
+    await program.methods
+      .initializeLiquidity(
+        lpMintDecimal,
+        ammPdaIndex,
+        baseMint,
+        pcMint,
+        baseMintAmount,
+        pcMintAmount,
+        0,
+        { constantProduct: {} },
+        new anchor.BN(0),
+        poolWeights(),
+        new anchor.BN(0),
+        null
+      )
+      .accounts({
+        liquidityProvider: liquidityProvider.publicKey,
+        ammConfig: ammConfig,
+        ammPda: ammPda,
+        baseTokenVault: baseTokenVault,
+        pcTokenVault: pcTokenVault,
+        lpTokenMint: lpTokenMint,
+        liquidityProviderLpTokenAta: liquidityProviderLpTokenAta,
+        baseTokenMint: baseMint,
+        pcTokenMint: pcMint,
+        liquidityProviderBaseTokenAta: liquidityProviderBaseTokenAta,
+        liquidityProviderPcTokenAta: liquidityProviderPCTokenAta,
+        tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
+        baseTokenProgram: TOKEN_PROGRAM_ID,
+        pcTokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([liquidityProvider])
+      .rpc({ commitment: "confirmed" });
+
+    const closePool = (authority: anchor.web3.Keypair) =>
+      program.methods
+        .closePool(ammPdaIndex)
+        .accounts({
+          authority: authority.publicKey,
+          liquidityProvider: liquidityProvider.publicKey,
+          ammPda: ammPda,
+          baseTokenVault: baseTokenVault,
+          pcTokenVault: pcTokenVault,
+          lpTokenMint: lpTokenMint,
+          recipientBaseTokenAccount: liquidityProviderBaseTokenAta,
+          recipientPcTokenAccount: liquidityProviderPCTokenAta,
+          baseTokenMint: baseMint,
+          pcTokenMint: pcMint,
+          baseTokenProgram: TOKEN_PROGRAM_ID,
+          pcTokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([authority])
+        .rpc({ commitment: "confirmed" });
+    try {
+      await closePool(liquidityProvider);
+      assert.fail("A pool with LPs left should not close");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "PoolNotEmpty");
+    }
+
+    // Once the creator withdraws everything, only the locked LP is left
+    const lpAta = await getAccount(connection, liquidityProviderLpTokenAta);
+    await program.methods
+      .withdraw(
+        lpMintDecimal,
+        ammPdaIndex,
+        new anchor.BN(lpAta.amount.toString()),
+        new anchor.BN(0),
+        new anchor.BN(0)
+      )
+      .accounts({
+        user: liquidityProvider.publicKey,
+        ammPda: ammPda,
+        baseTokenVault: baseTokenVault,
+        pcTokenVault: pcTokenVault,
+        lpTokenMint: lpTokenMint,
+        liquidityProviderLpTokenAta: liquidityProviderLpTokenAta,
+        liquidityProviderBaseTokenAta: liquidityProviderBaseTokenAta,
+        liquidityProviderPcTokenAta: liquidityProviderPCTokenAta,
+        baseTokenMint: baseMint,
+        pcTokenMint: pcMint,
+        tokenProgram: anchor.utils.token.TOKEN_PROGRAM_ID,
+        baseTokenProgram: TOKEN_PROGRAM_ID,
+        pcTokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([liquidityProvider])
+      .rpc({ commitment: "confirmed" });
+
+    const stranger = anchor.web3.Keypair.generate();
+    try {
+      await closePool(stranger);
+      assert.fail("Only the creator or the admin can close a pool");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "Unauthorized");
+    }
+    const { pda: lockedLpTokenAccount } = await getPda([
+      Buffer.from("locked_lp"),
+      ammPda.toBuffer(),
+    ]);
+
+    // The admin can close it too, and the rent still goes to the creator
+    const lamportsBefore = await connection.getBalance(
+      liquidityProvider.publicKey
+    );
+    await closePool(provider.wallet.payer);
+    const lamportsAfter = await connection.getBalance(
+      liquidityProvider.publicKey
+    );
+    assert.isTrue(lamportsAfter > lamportsBefore);
+    assert.isNull(
+      await program.account.initalizeLiquidityAccount.fetchNullable(ammPda)
+    );
+    assert.isNull(await connection.getAccountInfo(baseTokenVault));
+    assert.isNull(await connection.getAccountInfo(pcTokenVault));
+    assert.isNull(await connection.getAccountInfo(lockedLpTokenAccount));
+    const lpMintAccount = await getMint(connection, lpTokenMint);
+    assert.equal(lpMintAccount.supply.toString(), "0");
+  });
+  it("Locked liquidity", async () => {
+    const { ammPda, lpTokenMint, liquidityProviderLpTokenAta } =
+      ammVariables[1];
+    const { pda: lockedLpTokenAccount } = await getPda([
+      Buffer.from("locked_lp"),
+      ammPda.toBuffer(),
+    ]);
+    // The locked LP is part of the supply, next to what the LPs hold
+    const pool = await program.account.initalizeLiquidityAccount.fetch(ammPda);
+    const lockedAccount = await getAccount(connection, lockedLpTokenAccount);
+    assert.equal(lockedAccount.amount.toString(), "1000");
+    assert.equal(pool.lockedLpAmount.toString(), "1000");
+    const lpMintAccount = await getMint(connection, lpTokenMint);
+    const lpAta = await getAccount(connection, liquidityProviderLpTokenAta);
+    assert.isTrue(lpMintAccount.supply >= lpAta.amount + lockedAccount.amount);
+
+    try {
+      await program.methods
+        .setLockedLiquidity(new anchor.BN(10_000))
+        .accounts({ admin: liquidityProvider.publicKey, ammConfig })
+        .signers([liquidityProvider])
+        .rpc();
+      assert.fail("Only the admin can set the locked liquidity");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "Unauthorized");
+    }
+    try {
+      await program.methods
+        .setLockedLiquidity(new anchor.BN(0))
+        .accounts({ admin: provider.wallet.publicKey, ammConfig })
+        .rpc();
+      assert.fail("Some liquidity has to stay locked");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "InvalidLockedLiquidity");
+    }
+    await program.methods
+      .setLockedLiquidity(new anchor.BN(10_000))
+      .accounts({ admin: provider.wallet.publicKey, ammConfig })
+      .rpc();
+    let config = await program.account.ammConfig.fetch(ammConfig);
+    assert.equal(config.lockedLiquidity.toString(), "10000");
+    await program.methods
+      .setLockedLiquidity(new anchor.BN(1_000))
+      .accounts({ admin: provider.wallet.publicKey, ammConfig })
+      .rpc();
+    config = await program.account.ammConfig.fetch(ammConfig);
+    assert.equal(config.lockedLiquidity.toString(), "1000");
   });
 });